@@ -0,0 +1,331 @@
+use crate::config::{BeamConfig, BeamScore, Config, VisitedConfig};
+use crate::parallel::{self, Deadline, SolveOptions};
+use crate::puzzle::{self, Puzzle};
+use crate::schreier::SchreierSims;
+use crate::validation::validate_solution;
+use crate::visited::VisitedSet;
+use log::{debug, info};
+use rayon::prelude::*;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+fn fingerprint(state: &[usize]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    state.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Successor of a beam state, which is only built again if it is kept
+struct Candidate {
+    score: usize,
+    fingerprint: u64,
+    parent: usize,
+    mov: usize,
+}
+
+/*
+ * Best sequence found by a beam search, which solves the puzzle if the score is 0.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeamResult {
+    pub word: Vec<usize>,
+    pub score: usize,
+    pub solved: bool,
+}
+
+/*
+ * Beam search over the states of a puzzle: every level keeps the width best successors of the
+ * previous one according to the score, skipping states seen on any earlier level. The successors of
+ * a level are scored in parallel.
+ */
+pub struct BeamSearch<'a> {
+    puzzle: &'a Puzzle,
+    config: &'a BeamConfig,
+    visited: &'a VisitedConfig,
+    // Index of the inverse of every move, if it is a move
+    inverses: Vec<Option<usize>>,
+    // Color class of every facelet of the goal state
+    class_of: Vec<usize>,
+    num_classes: usize,
+}
+
+impl<'a> BeamSearch<'a> {
+    pub fn new(
+        puzzle: &'a Puzzle,
+        config: &'a BeamConfig,
+        visited: &'a VisitedConfig,
+    ) -> BeamSearch<'a> {
+        let inverses = puzzle::move_inverses(&puzzle.moves);
+        let classes = SchreierSims::get_stabilizing_color_gens(&puzzle.goal_string);
+        let mut class_of = vec![0; puzzle.goal_state.len()];
+        for (i, class) in classes.iter().enumerate() {
+            for x in class {
+                class_of[*x] = i;
+            }
+        }
+        BeamSearch {
+            puzzle,
+            config,
+            visited,
+            inverses,
+            class_of,
+            num_classes: classes.len(),
+        }
+    }
+
+    fn misplaced(&self, state: &[usize]) -> usize {
+        state
+            .iter()
+            .zip(self.puzzle.goal_state.iter())
+            .filter(|(a, b)| a != b)
+            .count()
+    }
+
+    /*
+     * Misplaced facelets beyond the wildcards, so every state with score 0 is solved. The colors
+     * score ranks states by the number of colors that are not completely in place first.
+     */
+    pub fn score(&self, state: &[usize]) -> usize {
+        let misplaced = self.misplaced(state);
+        if misplaced <= self.puzzle.num_wildcards {
+            return 0;
+        }
+        match self.config.score {
+            BeamScore::Misplaced => misplaced,
+            BeamScore::Colors => {
+                let mut unstabilized = vec![false; self.num_classes];
+                for (i, (a, b)) in state.iter().zip(self.puzzle.goal_state.iter()).enumerate() {
+                    if a != b {
+                        unstabilized[self.class_of[i]] = true;
+                    }
+                }
+                let unstabilized = unstabilized.iter().filter(|u| **u).count();
+                unstabilized * (state.len() + 1) + misplaced
+            }
+        }
+    }
+
+    fn word(levels: &[Vec<(usize, usize)>], mut index: usize) -> Vec<usize> {
+        let mut word = Vec::with_capacity(levels.len());
+        for level in levels.iter().rev() {
+            let (parent, mov) = level[index];
+            word.push(mov);
+            index = parent;
+        }
+        word.reverse();
+        word
+    }
+
+    /*
+     * Runs until a state is solved, the depth limit is reached, the beam runs empty or the
+     * deadline expires, and returns the best state found on the way.
+     */
+    pub fn search(&self, deadline: &Deadline) -> BeamResult {
+        let moves = &self.puzzle.moves;
+        let initial = self.puzzle.initial_state.clone();
+        let mut best = BeamResult {
+            word: Vec::new(),
+            score: self.score(&initial),
+            solved: false,
+        };
+        if best.score == 0 {
+            best.solved = true;
+            return best;
+        }
+        let mut visited = VisitedSet::new(self.visited);
+        visited.set(&fingerprint(&initial));
+        // Beam states with the last move that reached them
+        let mut beam: Vec<(Vec<usize>, Option<usize>)> = vec![(initial, None)];
+        // Parent index in the previous level and move of every beam state of every level
+        let mut levels: Vec<Vec<(usize, usize)>> = Vec::new();
+        while levels.len() < self.config.max_depth && !deadline.is_expired() {
+            let mut candidates = beam
+                .par_iter()
+                .enumerate()
+                .flat_map_iter(|(parent, (state, last))| {
+                    let mut successors = Vec::new();
+                    for (mov, m) in moves.iter().enumerate() {
+                        if let Some(last) = last {
+                            if self.inverses[*last] == Some(mov) {
+                                continue;
+                            }
+                        }
+                        let next = m.permutation.apply(state);
+                        successors.push(Candidate {
+                            score: self.score(&next),
+                            fingerprint: fingerprint(&next),
+                            parent,
+                            mov,
+                        });
+                    }
+                    successors
+                })
+                .collect::<Vec<Candidate>>();
+            // Ties are broken by fingerprint, so the search does not depend on the thread count
+            candidates.sort_unstable_by_key(|c| (c.score, c.fingerprint));
+            let mut kept = Vec::new();
+            for candidate in candidates {
+                if kept.len() == self.config.width {
+                    break;
+                }
+                if visited.check(&candidate.fingerprint) {
+                    continue;
+                }
+                visited.set(&candidate.fingerprint);
+                kept.push(candidate);
+            }
+            if kept.is_empty() {
+                debug!("Beam of puzzle {} ran empty", self.puzzle.id);
+                break;
+            }
+            let new_beam = kept
+                .par_iter()
+                .map(|c| {
+                    let state = moves[c.mov].permutation.apply(&beam[c.parent].0);
+                    (state, Some(c.mov))
+                })
+                .collect();
+            levels.push(kept.iter().map(|c| (c.parent, c.mov)).collect());
+            beam = new_beam;
+            if kept[0].score < best.score {
+                best = BeamResult {
+                    word: BeamSearch::word(&levels, 0),
+                    score: kept[0].score,
+                    solved: kept[0].score == 0,
+                };
+                if best.solved {
+                    break;
+                }
+            }
+            if levels.len().is_multiple_of(100) {
+                debug!(
+                    "Beam of puzzle {} at depth {} with best score {}, visited set: {}",
+                    self.puzzle.id,
+                    levels.len(),
+                    best.score,
+                    visited
+                );
+            }
+        }
+        best
+    }
+}
+
+/*
+ * Solves puzzles by beam search. Meant for large puzzles on which exact searches are hopeless. The
+ * best word found within the time budget is returned if it brings the puzzle within its wildcards,
+ * words that do not are no valid solutions and are only logged.
+ */
+pub fn solve_puzzles(
+    puzzles: &[Puzzle],
+    config: &Config,
+    options: &SolveOptions,
+) -> HashMap<usize, String> {
+    parallel::solve_in_parallel(puzzles, options, |puzzle, deadline| {
+        let search = BeamSearch::new(puzzle, &config.beam, &config.visited);
+        let result = search.search(deadline);
+        let solution = puzzle::word_to_string(&puzzle.moves, &result.word);
+        match validate_solution(puzzle, &solution) {
+            Ok(score) => {
+                parallel::log_solved(puzzle, score);
+                Some(solution)
+            }
+            Err(_) => {
+                info!(
+                    "Beam search of puzzle {} of type {:?} ended at score {} after {} moves",
+                    puzzle.id,
+                    puzzle.puzzle_type,
+                    result.score,
+                    result.word.len()
+                );
+                None
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing_utils::TestingUtils;
+
+    fn get_puzzle(scramble: &[usize], num_wildcards: usize) -> Puzzle {
+        let moves = TestingUtils::moves_from_cycles(
+            &[("a", "(1,2,3,4)"), ("b", "(3,4,5,6)"), ("c", "(5,6,7,8)")],
+            8,
+        );
+        let goal_state = vec![1, 1, 2, 2, 3, 3, 4, 4];
+        let mut initial_state = goal_state.clone();
+        for m in scramble {
+            initial_state = moves[*m].permutation.apply(&initial_state);
+        }
+        let mut puzzle =
+            TestingUtils::puzzle_from_moves(moves, initial_state, goal_state, num_wildcards);
+        puzzle.goal_string = "A;A;B;B;C;C;D;D".to_string();
+        puzzle
+    }
+
+    #[test]
+    fn test_beam_search_solves() {
+        let visited = VisitedConfig::default();
+        for score in [BeamScore::Misplaced, BeamScore::Colors] {
+            let config = BeamConfig {
+                width: 50,
+                max_depth: 30,
+                score,
+            };
+            let puzzle = get_puzzle(&[1, 3, 5, 2, 1], 0);
+            let search = BeamSearch::new(&puzzle, &config, &visited);
+            let result = search.search(&Deadline::unlimited());
+            assert!(result.solved);
+            assert_eq!(result.score, 0);
+            let names = puzzle::word_to_string(&puzzle.moves, &result.word);
+            assert_eq!(validate_solution(&puzzle, &names), Ok(result.word.len()));
+        }
+    }
+
+    #[test]
+    fn test_beam_score() {
+        let visited = VisitedConfig::default();
+        let config = BeamConfig {
+            score: BeamScore::Colors,
+            ..BeamConfig::default()
+        };
+        let puzzle = get_puzzle(&[], 0);
+        let search = BeamSearch::new(&puzzle, &config, &visited);
+        assert_eq!(search.score(&puzzle.goal_state), 0);
+        // Two colors are out of place
+        assert_eq!(search.score(&[1, 1, 2, 3, 2, 3, 4, 4]), 2 * 9 + 2);
+        let puzzle = get_puzzle(&[], 2);
+        let search = BeamSearch::new(&puzzle, &config, &visited);
+        assert_eq!(search.score(&[1, 1, 2, 3, 2, 3, 4, 4]), 0);
+        let expired = Deadline::after(Some(std::time::Duration::ZERO));
+        let puzzle = get_puzzle(&[1, 3], 0);
+        let search = BeamSearch::new(&puzzle, &config, &visited);
+        let result = search.search(&expired);
+        assert!(!result.solved);
+        assert!(result.word.is_empty());
+    }
+
+    #[test]
+    fn test_beam_solve_puzzles() {
+        let mut config = Config::default();
+        config.beam.max_depth = 30;
+        let options = SolveOptions {
+            num_threads: Some(1),
+            ..SolveOptions::default()
+        };
+        // The second puzzle only has to be brought within its wildcards
+        let mut puzzles = vec![get_puzzle(&[1, 3, 5, 2, 1], 0), get_puzzle(&[1, 3, 5], 4)];
+        puzzles[1].id = 1;
+        let results = solve_puzzles(&puzzles, &config, &options);
+        for puzzle in &puzzles {
+            assert!(validate_solution(puzzle, &results[&puzzle.id]).is_ok());
+        }
+        // One move does not solve the first puzzle, so its best word is dropped
+        config.beam.max_depth = 1;
+        let results = solve_puzzles(&puzzles[..1], &config, &options);
+        assert!(results.is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+use crate::permutation::Permutation;
+use crate::puzzle::{self, Move};
+use log::debug;
+use std::collections::HashMap;
+
+// Marks the start of a search in the map of last moves
+const ROOT: usize = usize::MAX;
+
+/*
+ * One side of the search. Every visited element maps to the last move that reached it, so words
+ * are rebuilt by undoing moves instead of being stored.
+ */
+struct Side {
+    visited: HashMap<Permutation, usize>,
+    frontier: Vec<Permutation>,
+    depth: usize,
+}
+
+impl Side {
+    fn new(start: Permutation) -> Side {
+        Side {
+            visited: HashMap::from([(start.clone(), ROOT)]),
+            frontier: vec![start],
+            depth: 0,
+        }
+    }
+
+    /*
+     * Expands the frontier by one level, where reaching an element with move m means composing
+     * it with step(m) from the left. Returns the new elements that the other side has visited.
+     */
+    fn expand<F>(&mut self, moves: &[Move], step: F, other: &Side) -> Vec<Permutation>
+    where
+        F: Fn(usize) -> usize,
+    {
+        let mut frontier = Vec::new();
+        let mut meets = Vec::new();
+        for perm in self.frontier.iter() {
+            for m in 0..moves.len() {
+                let next = moves[step(m)].permutation.compose(perm);
+                if self.visited.contains_key(&next) {
+                    continue;
+                }
+                if other.visited.contains_key(&next) {
+                    meets.push(next.clone());
+                }
+                self.visited.insert(next.clone(), m);
+                frontier.push(next);
+            }
+        }
+        self.frontier = frontier;
+        self.depth += 1;
+        meets
+    }
+
+    /*
+     * Moves from the start of the side to the element, in the order they were taken.
+     */
+    fn word<F>(&self, moves: &[Move], undo: F, element: &Permutation) -> Vec<usize>
+    where
+        F: Fn(usize) -> usize,
+    {
+        let mut word = Vec::new();
+        let mut perm = element.clone();
+        loop {
+            let m = self.visited[&perm];
+            if m == ROOT {
+                break;
+            }
+            word.push(m);
+            perm = moves[undo(m)].permutation.compose(&perm);
+        }
+        word.reverse();
+        word
+    }
+}
+
+/*
+ * Finds a shortest word (move indices, applied in order) whose permutation is the target, i.e.
+ * w_k.compose(...).compose(w_1) == target, if there is one with at most radius moves. Expands
+ * whole levels from the identity and from the target, always the side with the smaller frontier,
+ * so both sides only have to reach about half the radius. Returns None as well if the inverse of
+ * a move is not a move.
+ */
+pub fn bidirectional_search(
+    moves: &[Move],
+    target: &Permutation,
+    radius: usize,
+) -> Option<Vec<usize>> {
+    if target.is_identity() {
+        return Some(Vec::new());
+    }
+    let Some(inverses) = puzzle::move_inverses(moves)
+        .into_iter()
+        .collect::<Option<Vec<usize>>>()
+    else {
+        debug!("Bidirectional search needs the inverses of all moves");
+        return None;
+    };
+    let inverse = |m: usize| inverses[m];
+    // The forward side reaches m.compose(p), the backward side, which holds v^-1.compose(target)
+    // for the words v that end a solution, reaches m^-1.compose(p) when prepending m to v
+    let mut forward = Side::new(Permutation::identity(target.len()));
+    let mut backward = Side::new(target.clone());
+    while forward.depth + backward.depth < radius {
+        if forward.frontier.is_empty() || backward.frontier.is_empty() {
+            return None;
+        }
+        let expand_forward = forward.frontier.len() <= backward.frontier.len();
+        let meets = match expand_forward {
+            true => forward.expand(moves, |m| m, &backward),
+            false => backward.expand(moves, inverse, &forward),
+        };
+        debug!(
+            "Bidirectional search at depths {} and {} with {} and {} elements",
+            forward.depth,
+            backward.depth,
+            forward.visited.len(),
+            backward.visited.len()
+        );
+        // No shorter word exists, as it would have met on an earlier level, so every meet of this
+        // level lies at the full depth of the other side
+        if let Some(meet) = meets.first() {
+            let mut word = forward.word(moves, inverse, meet);
+            word.extend(backward.word(moves, |m| m, meet).into_iter().rev());
+            return Some(word);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing_utils::TestingUtils;
+    use rand::Rng;
+
+    fn get_moves() -> Vec<Move> {
+        TestingUtils::moves_from_cycles(
+            &[("a", "(1,2,3,4,5)"), ("b", "(4,5,6)"), ("c", "(1,7)")],
+            7,
+        )
+    }
+
+    fn word_perm(moves: &[Move], word: &[usize]) -> Permutation {
+        let mut perm = Permutation::identity(7);
+        for m in word {
+            perm = moves[*m].permutation.compose(&perm);
+        }
+        perm
+    }
+
+    #[test]
+    fn test_bidirectional_search_finds_words() {
+        let moves = get_moves();
+        let mut rng = rand::thread_rng();
+        for _ in 0..50 {
+            let word = (0..rng.gen_range(0, 9))
+                .map(|_| rng.gen_range(0, moves.len()))
+                .collect::<Vec<usize>>();
+            let target = word_perm(&moves, &word);
+            let found = bidirectional_search(&moves, &target, 8).unwrap();
+            assert!(found.len() <= word.len());
+            assert_eq!(word_perm(&moves, &found), target);
+        }
+    }
+
+    #[test]
+    fn test_bidirectional_search_is_minimal() {
+        let moves = get_moves();
+        // Lengths of shortest words by breadth first search
+        let mut distances = HashMap::from([(Permutation::identity(7), 0)]);
+        let mut frontier = vec![Permutation::identity(7)];
+        for depth in 1..=5 {
+            let mut next = Vec::new();
+            for perm in frontier.iter() {
+                for m in moves.iter() {
+                    let p = m.permutation.compose(perm);
+                    if !distances.contains_key(&p) {
+                        distances.insert(p.clone(), depth);
+                        next.push(p);
+                    }
+                }
+            }
+            frontier = next;
+        }
+        for (target, distance) in distances.iter() {
+            let found = bidirectional_search(&moves, target, 5).unwrap();
+            assert_eq!(found.len(), *distance);
+            assert_eq!(word_perm(&moves, &found), *target);
+        }
+        let target = frontier[0].clone();
+        assert_eq!(bidirectional_search(&moves, &target, 4), None);
+    }
+
+    #[test]
+    fn test_bidirectional_search_move_order() {
+        // Moves that are not next to their inverses
+        let mut moves = get_moves();
+        moves.rotate_left(1);
+        let word = vec![0, 2, 2, 4];
+        let target = word_perm(&moves, &word);
+        let found = bidirectional_search(&moves, &target, 4).unwrap();
+        assert!(found.len() <= word.len());
+        assert_eq!(word_perm(&moves, &found), target);
+        // a has no inverse among the moves
+        assert_eq!(bidirectional_search(&moves[..3], &target, 4), None);
+    }
+}
@@ -1,30 +1,19 @@
 use crate::permutation::Permutation;
 
 use log::{debug, info};
+use santa_solver_lib::config::Config;
 use santa_solver_lib::conjugated_search as search;
 use santa_solver_lib::permutation;
-use santa_solver_lib::puzzle;
 use std::collections::HashMap;
 
 fn main() {
     env_logger::init();
 
-    let args: Vec<String> = std::env::args().collect();
-    let puzzle_info_path = if args.len() > 1 {
-        &args[1]
-    } else {
-        "./../../data/puzzle_info.csv"
-    };
-    let puzzles_path = if args.len() > 2 {
-        &args[2]
-    } else {
-        "./../../data/puzzles.csv"
-    };
+    let config = Config::from_args().unwrap();
 
     // Load the puzzles
     debug!("Loading puzzle data...");
-    let puzzles_info = puzzle::load_puzzle_info(puzzle_info_path).unwrap();
-    let puzzles = puzzle::load_puzzles(puzzles_path, &puzzles_info).unwrap();
+    let (_, puzzles) = config.load_puzzles().unwrap();
 
     for puzzle in puzzles {
         if puzzle
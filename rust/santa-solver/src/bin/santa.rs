@@ -0,0 +1,550 @@
+use clap::{Args, Parser, Subcommand};
+use log::{error, info, warn};
+use santa_solver_lib::config::{BeamScore, Config, Method, VisitedStrategy};
+use santa_solver_lib::cubes::{kociemba, reduction};
+use santa_solver_lib::minkwitz_solver;
+use santa_solver_lib::parallel::SolutionStore;
+use santa_solver_lib::permutation::{self, Permutation};
+use santa_solver_lib::puzzle::{self, Move, Puzzle, PuzzleFilter, PuzzleType};
+use santa_solver_lib::submission;
+use santa_solver_lib::validation;
+use santa_solver_lib::{
+    beam_search, cycle_extractor, cycle_solver, globe, ida_star, ktt_solver, wreath,
+};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::path::Path;
+
+#[derive(Parser, Debug)]
+#[command(name = "santa", about = "Solvers and tools for the Santa 2023 puzzles")]
+struct Cli {
+    /// Run configuration (.toml or .json), the flags below override its values
+    #[arg(long, global = true)]
+    config: Option<String>,
+    /// Directory all relative paths are relative to [default: ./../../data]
+    #[arg(long, global = true)]
+    data_dir: Option<String>,
+    /// Puzzle info csv [default: puzzle_info.csv]
+    #[arg(long, global = true)]
+    puzzle_info: Option<String>,
+    /// Puzzles csv [default: puzzles.csv]
+    #[arg(long, global = true)]
+    puzzles: Option<String>,
+    /// Visited set of the breadth first searches [default: exact64]
+    #[arg(long, global = true, value_enum)]
+    visited: Option<VisitedStrategy>,
+    /// Number of elements the visited sets are sized for [default: 1000000]
+    #[arg(long, global = true)]
+    visited_items: Option<usize>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Args, Debug)]
+struct FilterArgs {
+    /// Only use puzzles of these types, e.g. cube_3_3_3,globe_1/8
+    #[arg(long = "puzzle-type", value_delimiter = ',')]
+    puzzle_types: Vec<String>,
+    /// Only use puzzles with these ids, e.g. 1,4,10-20
+    #[arg(long)]
+    ids: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct SgsArgs {
+    /// Schreier-Sims bases [default: bases]
+    #[arg(long)]
+    bases: Option<String>,
+    /// Minkwitz SGS tables [default: minkwitz_tables]
+    #[arg(long)]
+    minkwitz_tables: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Solve puzzles with one of the solvers
+    Solve {
+        /// Solver to use [default: the enabled methods of the config, one after another]
+        #[arg(long, value_enum)]
+        method: Option<Method>,
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// Number of worker threads [default: all cores]
+        #[arg(long)]
+        threads: Option<usize>,
+        /// Time budget per puzzle in seconds [default: unlimited]
+        #[arg(long)]
+        time_budget: Option<u64>,
+        /// Solutions of this run, the effective config is written next to it [default: solutions.csv]
+        #[arg(long)]
+        output: Option<String>,
+        /// Every solution is also appended to <solutions-dir>/<id>.csv [default: solutions]
+        #[arg(long)]
+        solutions_dir: Option<String>,
+        /// Cycle libraries of the ktt and cycles methods [default: cycle_libraries]
+        #[arg(long)]
+        cycle_libraries: Option<String>,
+        /// Target permutations of the minkwitz method [default: target.csv]
+        #[arg(long)]
+        targets: Option<String>,
+        #[command(flatten)]
+        sgs: SgsArgs,
+        /// Rounds of table improvement of the minkwitz method [default: 4]
+        #[arg(long)]
+        rounds: Option<usize>,
+        /// States kept per level by the beam method [default: 1000]
+        #[arg(long)]
+        beam_width: Option<usize>,
+        /// Score of the beam method [default: misplaced]
+        #[arg(long, value_enum)]
+        beam_score: Option<BeamScore>,
+    },
+    /// Check that the solutions of a submission solve their puzzles
+    Validate {
+        /// Submission csv [default: solutions.csv]
+        #[arg(long)]
+        submission: Option<String>,
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+    /// Merge the shortest solutions found so far into a new submission
+    Merge {
+        /// Solutions per puzzle [default: solutions]
+        #[arg(long)]
+        solutions_dir: Option<String>,
+        /// Submission to start from [default: baseline.csv]
+        #[arg(long)]
+        submission: Option<String>,
+        /// Directory of complete submissions to merge in [default: improvement_complete_csvs]
+        #[arg(long)]
+        other_submissions: Option<String>,
+        /// Merged submission [default: submission_new.csv]
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// Shorten the solutions of a submission with the optimizers and the wildcard truncation
+    Optimize {
+        /// Submission to optimize [default: baseline.csv]
+        #[arg(long)]
+        submission: Option<String>,
+        /// Optimized submission [default: submission_new.csv]
+        #[arg(long)]
+        output: Option<String>,
+        /// Also replace segments by shorter words from the short word tables
+        #[arg(long)]
+        window: bool,
+        /// Longest segment replaced by the window optimizer [default: 12]
+        #[arg(long)]
+        window_size: Option<usize>,
+        /// Word length up to which the short word tables are built [default: 3]
+        #[arg(long)]
+        short_word_depth: Option<usize>,
+        /// Longest suffix searched for puzzles with wildcards, 0 to only cut [default: 3]
+        #[arg(long)]
+        suffix_depth: Option<usize>,
+    },
+    /// Search c-cycles of the puzzle types and write them to csv files
+    ExtractCycles {
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// Output directory [default: cycles]
+        #[arg(long)]
+        cycles: Option<String>,
+    },
+    /// Print the cycle structure of the targets of puzzles with unique elements
+    Analyze {
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+    /// Build or improve the Minkwitz SGS tables of the puzzle types
+    BuildSgs {
+        #[command(flatten)]
+        filter: FilterArgs,
+        #[command(flatten)]
+        sgs: SgsArgs,
+    },
+    /// Build the pattern databases of the ida method for the goal states of the puzzles
+    BuildPatternDatabases {
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// Output directory [default: pattern_databases]
+        #[arg(long)]
+        pattern_databases: Option<String>,
+        /// Largest number of abstract states of a database [default: 100000000]
+        #[arg(long)]
+        pattern_size: Option<u64>,
+    },
+}
+
+fn set<T: Clone>(field: &mut T, value: &Option<T>) {
+    if let Some(value) = value {
+        *field = value.clone();
+    }
+}
+
+impl FilterArgs {
+    fn apply_to(&self, config: &mut Config) {
+        if !self.puzzle_types.is_empty() {
+            config.filter.puzzle_types = self.puzzle_types.clone();
+        }
+        set(&mut config.filter.ids, &self.ids);
+    }
+}
+
+impl SgsArgs {
+    fn apply_to(&self, config: &mut Config) {
+        set(&mut config.paths.bases, &self.bases);
+        set(&mut config.paths.minkwitz_tables, &self.minkwitz_tables);
+    }
+}
+
+/*
+ * Loads the config file (or the defaults) and overrides it with the flags of the command line.
+ */
+fn build_config(cli: &Cli) -> Result<Config, Box<dyn Error>> {
+    let mut config = match &cli.config {
+        Some(path) => Config::from_file(path)?,
+        None => Config::default(),
+    };
+    set(&mut config.paths.data_dir, &cli.data_dir);
+    set(&mut config.paths.puzzle_info, &cli.puzzle_info);
+    set(&mut config.paths.puzzles, &cli.puzzles);
+    set(&mut config.visited.strategy, &cli.visited);
+    set(&mut config.visited.items, &cli.visited_items);
+    match &cli.command {
+        Command::Solve {
+            method,
+            filter,
+            threads,
+            time_budget,
+            output,
+            solutions_dir,
+            cycle_libraries,
+            targets,
+            sgs,
+            rounds,
+            beam_width,
+            beam_score,
+        } => {
+            if let Some(method) = method {
+                config.enabled_methods = vec![*method];
+            }
+            filter.apply_to(&mut config);
+            if threads.is_some() {
+                config.solve.threads = *threads;
+            }
+            if time_budget.is_some() {
+                config.solve.time_budget_secs = *time_budget;
+            }
+            set(&mut config.paths.solutions, output);
+            set(&mut config.paths.solutions_dir, solutions_dir);
+            set(&mut config.paths.cycle_libraries, cycle_libraries);
+            set(&mut config.paths.targets, targets);
+            sgs.apply_to(&mut config);
+            set(&mut config.minkwitz.rounds, rounds);
+            set(&mut config.beam.width, beam_width);
+            set(&mut config.beam.score, beam_score);
+        }
+        Command::Validate { submission, filter } => {
+            filter.apply_to(&mut config);
+            set(&mut config.paths.solutions, submission);
+        }
+        Command::Merge {
+            solutions_dir,
+            submission,
+            other_submissions,
+            output,
+        } => {
+            set(&mut config.paths.solutions_dir, solutions_dir);
+            set(&mut config.paths.submission, submission);
+            set(&mut config.paths.other_submissions, other_submissions);
+            set(&mut config.paths.merged_submission, output);
+        }
+        Command::Optimize {
+            submission,
+            output,
+            window,
+            window_size,
+            short_word_depth,
+            suffix_depth,
+        } => {
+            set(&mut config.paths.submission, submission);
+            set(&mut config.paths.merged_submission, output);
+            config.solve.window_optimize |= *window;
+            set(&mut config.short_words.window, window_size);
+            set(&mut config.short_words.depth, short_word_depth);
+            set(&mut config.wildcards.suffix_depth, suffix_depth);
+        }
+        Command::ExtractCycles { filter, cycles } => {
+            filter.apply_to(&mut config);
+            set(&mut config.paths.cycles, cycles);
+        }
+        Command::Analyze { filter } => filter.apply_to(&mut config),
+        Command::BuildSgs { filter, sgs } => {
+            filter.apply_to(&mut config);
+            sgs.apply_to(&mut config);
+        }
+        Command::BuildPatternDatabases {
+            filter,
+            pattern_databases,
+            pattern_size,
+        } => {
+            filter.apply_to(&mut config);
+            set(&mut config.paths.pattern_databases, pattern_databases);
+            set(&mut config.ida.pattern_size, pattern_size);
+        }
+    }
+    Ok(config)
+}
+
+fn append_solution(solutions_dir: &str, id: usize, solution: &str) -> Result<(), Box<dyn Error>> {
+    std::fs::create_dir_all(solutions_dir)?;
+    let sol_path = format!("{}/{}.csv", solutions_dir, id);
+    let is_new = !Path::new(&sol_path).exists();
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&sol_path)?;
+    let mut writer = csv::Writer::from_writer(file);
+    if is_new {
+        writer.write_record(&["id", "moves", "length"])?;
+    }
+    writer.write_record(&[
+        &id.to_string(),
+        solution,
+        &solution.split('.').count().to_string(),
+    ])?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_solution_to_file(solution_path: &str, store: &SolutionStore) {
+    info!("Writing {} solutions to {}...", store.len(), solution_path);
+    if let Err(e) = store.write_to_file(solution_path) {
+        error!("Could not write solutions to {}: {}", solution_path, e);
+    }
+}
+
+fn solve(
+    config: &Config,
+    puzzles_info: &HashMap<PuzzleType, Vec<Move>>,
+    puzzles: Vec<Puzzle>,
+) -> Result<(), Box<dyn Error>> {
+    let output = config.paths.resolve(&config.paths.solutions);
+    let config_path = Path::new(&output).with_extension("toml");
+    info!(
+        "Writing the config of this run to {}",
+        config_path.display()
+    );
+    config.write_to_file(&config_path.to_string_lossy())?;
+
+    // Catch interrupts (and SIGTERM) so we can write the solutions found so far to a file
+    let store = SolutionStore::new();
+    let handler_store = store.clone();
+    let handler_output = output.clone();
+    ctrlc2::set_handler(move || {
+        info!("Caught interrupt, writing solution to file...");
+        write_solution_to_file(&handler_output, &handler_store);
+        // The handler is not told which signal arrived, so exit with the status of an interrupt
+        std::process::exit(130);
+    })
+    .expect("Error setting Ctrl-C handler");
+    let mut options = config.solve_options();
+    options.solution_store = Some(store.clone());
+
+    // Every method publishes its solutions to the store, which keeps the shortest one per puzzle
+    for method in &config.enabled_methods {
+        info!("Solving {} puzzles with {:?}", puzzles.len(), method);
+        let results = match method {
+            Method::Ktt => ktt_solver::solve_puzzles(&puzzles, config, &options),
+            Method::Wreath => {
+                let wreath_puzzles = puzzles
+                    .iter()
+                    .filter(|p| matches!(p.puzzle_type, PuzzleType::WREATH(_)))
+                    .cloned()
+                    .collect::<Vec<Puzzle>>();
+                wreath::solve_puzzles(&wreath_puzzles, config, &options)
+            }
+            Method::Minkwitz => {
+                let id_to_target: HashMap<usize, Permutation> =
+                    puzzle::load_id_to_target_permutation(
+                        &config.paths.resolve(&config.paths.targets),
+                    )?;
+                minkwitz_solver::solve_puzzles(
+                    &puzzles,
+                    puzzles_info,
+                    &id_to_target,
+                    config,
+                    &options,
+                )
+            }
+            Method::Cycles => cycle_solver::solve_puzzles(&puzzles, config, &options),
+            Method::Ida => ida_star::solve_puzzles(&puzzles, config, &options),
+            Method::Beam => beam_search::solve_puzzles(&puzzles, config, &options),
+            Method::Kociemba => kociemba::solve_puzzles(&puzzles, config, &options),
+            Method::Reduction => reduction::solve_puzzles(&puzzles, config, &options),
+            Method::Globe => globe::solve_puzzles(&puzzles, config, &options),
+        };
+        info!("Solved {} puzzles with {:?}", results.len(), method);
+    }
+
+    let solutions_dir = config.paths.resolve(&config.paths.solutions_dir);
+    let solutions = store.snapshot();
+    let mut ids = solutions.keys().collect::<Vec<_>>();
+    ids.sort();
+    for id in ids {
+        if let Err(e) = append_solution(&solutions_dir, *id, &solutions[id]) {
+            error!("Could not append solution of puzzle {}: {}", id, e);
+        }
+    }
+    write_solution_to_file(&output, &store);
+    Ok(())
+}
+
+/*
+ * Prints the score of every row of the submission and the total score. Fails if any row is
+ * invalid.
+ */
+fn validate(
+    puzzles: &Vec<Puzzle>,
+    filter: &PuzzleFilter,
+    submission_path: &str,
+) -> Result<(), Box<dyn Error>> {
+    let report = validation::validate_submission(puzzles, filter, submission_path)?;
+    for (id, result) in &report.rows {
+        match result {
+            Ok(score) => println!("{},{}", id, score),
+            Err(e) => {
+                error!("Puzzle {}: {}", id, e);
+                println!("{},invalid", id);
+            }
+        }
+    }
+    if !report.missing.is_empty() {
+        warn!(
+            "{} puzzles have no solution in {}",
+            report.missing.len(),
+            submission_path
+        );
+    }
+    println!(
+        "Total score {} of {} valid rows, {} invalid rows",
+        report.total_score(),
+        report.rows.len() - report.num_invalid(),
+        report.num_invalid()
+    );
+    if !report.is_valid() {
+        return Err(format!(
+            "{} invalid rows in {}",
+            report.num_invalid(),
+            submission_path
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn analyze(puzzles: &Vec<Puzzle>) {
+    // Only keep the puzzles with unique facelets
+    let unique_puzzles = puzzles
+        .iter()
+        .filter(|p| p.initial_state.iter().collect::<HashSet<_>>().len() == p.initial_state.len());
+
+    let mut cycles = HashSet::new();
+    for p in unique_puzzles {
+        // Get the target permutation
+        let target_perm = permutation::get_permutation(&p.initial_state, &p.goal_state);
+        let perm_info = target_perm.compute_info();
+        for c in perm_info.cycles {
+            if c.len() > 1 {
+                cycles.insert(c);
+            }
+        }
+    }
+
+    // Count the number of elements for each cycle length
+    let cycle_orders = cycles.iter().fold(HashMap::new(), |mut acc, c| {
+        *acc.entry(c.len()).or_insert(0) += 1;
+        acc
+    });
+    // Print the map for orders >= 2 in a sorted order
+    let mut cycle_orders = cycle_orders.iter().collect::<Vec<_>>();
+    cycle_orders.sort_by(|a, b| a.0.cmp(b.0));
+    for (order, count) in cycle_orders {
+        println!("Cycles of order {}: {}", order, count);
+    }
+}
+
+fn build_sgs(
+    puzzles: &Vec<Puzzle>,
+    puzzles_info: &HashMap<PuzzleType, Vec<Move>>,
+    config: &Config,
+) {
+    let mut done: HashSet<&PuzzleType> = HashSet::new();
+    for puzzle in puzzles {
+        if done.insert(&puzzle.puzzle_type) {
+            minkwitz_solver::build_type_table(puzzle, puzzles_info, config);
+        }
+    }
+}
+
+fn run(cli: &Cli) -> Result<(), Box<dyn Error>> {
+    let config = build_config(cli)?;
+    let filter = config.puzzle_filter()?;
+    let puzzles_info = puzzle::load_puzzle_info(&config.paths.resolve(&config.paths.puzzle_info))?;
+    let load_puzzles =
+        || puzzle::load_puzzles(&config.paths.resolve(&config.paths.puzzles), &puzzles_info);
+    let load_filtered = || -> Result<Vec<Puzzle>, Box<dyn Error>> {
+        let filtered = filter.apply(&load_puzzles()?);
+        if filtered.is_empty() {
+            warn!("No puzzles match the filter");
+        }
+        Ok(filtered)
+    };
+
+    match &cli.command {
+        Command::Solve { .. } => solve(&config, &puzzles_info, load_filtered()?),
+        Command::Validate { .. } => validate(
+            &load_puzzles()?,
+            &filter,
+            &config.paths.resolve(&config.paths.solutions),
+        ),
+        Command::Merge { .. } => {
+            submission::merge(&config.paths);
+            Ok(())
+        }
+        Command::Optimize { .. } => {
+            let score = submission::optimize(&load_puzzles()?, &config);
+            println!("Score of the optimized submission: {}", score);
+            Ok(())
+        }
+        Command::ExtractCycles { .. } => {
+            cycle_extractor::extract_cycles(&puzzles_info, &filter, &config);
+            Ok(())
+        }
+        Command::Analyze { .. } => {
+            analyze(&load_filtered()?);
+            Ok(())
+        }
+        Command::BuildSgs { .. } => {
+            build_sgs(&load_filtered()?, &puzzles_info, &config);
+            Ok(())
+        }
+        Command::BuildPatternDatabases { .. } => {
+            let databases = ida_star::load_pattern_databases(&load_filtered()?, &config);
+            println!("Built or loaded {} pattern databases", databases.len());
+            Ok(())
+        }
+    }
+}
+
+fn main() {
+    env_logger::init();
+    let cli = Cli::parse();
+    if let Err(e) = run(&cli) {
+        error!("{}", e);
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -1,28 +1,17 @@
 use log::{debug, info};
+use santa_solver_lib::config::Config;
 use santa_solver_lib::permutation::{self};
-use santa_solver_lib::puzzle::{self, PuzzleType};
+use santa_solver_lib::puzzle::PuzzleType;
 use santa_solver_lib::{minkwitz, schreier};
 use std::collections::HashSet;
 
 fn main() {
     env_logger::init();
-    let args: Vec<String> = std::env::args().collect();
-    let _solution_path = "./../../data/solutions/";
-    let puzzle_info_path = if args.len() > 2 {
-        &args[2]
-    } else {
-        "./../../data/puzzle_info.csv"
-    };
-    let puzzles_path = if args.len() > 3 {
-        &args[3]
-    } else {
-        "./../../data/puzzles.csv"
-    };
+    let config = Config::from_args().unwrap();
 
     // Load the puzzles
     debug!("Loading puzzle data...");
-    let puzzles_info = puzzle::load_puzzle_info(puzzle_info_path).unwrap();
-    let puzzles = puzzle::load_puzzles(puzzles_path, &puzzles_info).unwrap();
+    let (puzzles_info, puzzles) = config.load_puzzles().unwrap();
     // filter irrelevant puzzles
     let mut relevant_types: HashSet<PuzzleType> = HashSet::new();
     relevant_types.insert(PuzzleType::CUBE(4));
@@ -1,5 +0,0 @@
-func () {
-  cd ../../cpp/RCube/build/
-  ./RCube/RCube $1
-}
-func $1
@@ -0,0 +1,399 @@
+use crate::permutation::Permutation;
+use crate::puzzle::{self, Move};
+use log::debug;
+use std::collections::{HashMap, HashSet};
+
+/*
+ * A word in move indices, composed in order like PermutationPath, with its permutation and the
+ * 0-indexed points the permutation moves.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct CycleWord {
+    pub permutation: Permutation,
+    pub word: Vec<usize>,
+    pub support: Vec<usize>,
+}
+
+// Permutation given by the points it moves with their images, sorted by point, all 0-indexed
+type SparsePermutation = Vec<(usize, usize)>;
+
+fn image(p: &SparsePermutation, x: usize) -> usize {
+    match p.binary_search_by_key(&x, |(y, _)| *y) {
+        Ok(i) => p[i].1,
+        Err(_) => x,
+    }
+}
+
+fn sparse_inverse(p: &SparsePermutation) -> SparsePermutation {
+    let mut inverse = p
+        .iter()
+        .map(|(x, y)| (*y, *x))
+        .collect::<SparsePermutation>();
+    inverse.sort_unstable();
+    inverse
+}
+
+// Applies the permutations in order
+fn sparse_product(factors: &[&SparsePermutation]) -> SparsePermutation {
+    let mut points = factors
+        .iter()
+        .flat_map(|p| p.iter().map(|(x, _)| *x))
+        .collect::<Vec<usize>>();
+    points.sort_unstable();
+    points.dedup();
+    points
+        .into_iter()
+        .map(|x| (x, factors.iter().fold(x, |y, p| image(p, y))))
+        .filter(|(x, y)| x != y)
+        .collect()
+}
+
+fn is_disjoint(a: &SparsePermutation, b: &SparsePermutation) -> bool {
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].0.cmp(&b[j].0) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => return false,
+        }
+    }
+    true
+}
+
+/*
+ * Group element given by a short word, with the word of its inverse.
+ */
+struct ShortWord {
+    word: Vec<usize>,
+    inverse_word: Vec<usize>,
+    permutation: SparsePermutation,
+    inverse: SparsePermutation,
+}
+
+/*
+ * Enumerates commutators [A, B] = A B A⁻¹ B⁻¹ and conjugates C X C⁻¹ for short words A, B and C in
+ * the moves of a puzzle. Every permutation with a support of at most max_support points is kept
+ * with its shortest word found and indexed by its cycle structure (PermutationInfo::cycles_id) and
+ * by its support. Products are only evaluated on the points their factors move, so the search
+ * stays cheap for large puzzles as long as the words are short.
+ */
+pub struct CommutatorSearch<'a> {
+    moves: &'a Vec<Move>,
+    // Index of the inverse of every move, None if the inverse is not a move
+    inverses: Vec<Option<usize>>,
+    max_support: usize,
+    // Products tried by every enumeration
+    limit: usize,
+    entries: Vec<CycleWord>,
+    by_permutation: HashMap<Permutation, usize>,
+    by_cycles: HashMap<Vec<usize>, Vec<usize>>,
+    by_support: HashMap<Vec<usize>, Vec<usize>>,
+}
+
+impl<'a> CommutatorSearch<'a> {
+    pub fn new(moves: &'a Vec<Move>, max_support: usize, limit: usize) -> CommutatorSearch<'a> {
+        let inverses = puzzle::move_inverses(moves);
+        CommutatorSearch {
+            moves,
+            inverses,
+            max_support,
+            limit,
+            entries: Vec::new(),
+            by_permutation: HashMap::new(),
+            by_cycles: HashMap::new(),
+            by_support: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> &Vec<CycleWord> {
+        &self.entries
+    }
+
+    /*
+     * Permutations with the given cycle structure, shortest words first.
+     */
+    pub fn with_cycles(&self, cycles_id: &Vec<usize>) -> Vec<&CycleWord> {
+        self.lookup(self.by_cycles.get(cycles_id))
+    }
+
+    /*
+     * Permutations that move exactly the given sorted 0-indexed points, shortest words first.
+     */
+    pub fn with_support(&self, support: &Vec<usize>) -> Vec<&CycleWord> {
+        self.lookup(self.by_support.get(support))
+    }
+
+    fn lookup(&self, indices: Option<&Vec<usize>>) -> Vec<&CycleWord> {
+        let mut entries = indices
+            .into_iter()
+            .flatten()
+            .map(|i| &self.entries[*i])
+            .collect::<Vec<&CycleWord>>();
+        entries.sort_by_key(|entry| entry.word.len());
+        entries
+    }
+
+    /*
+     * Stores the permutation with the word unless it is the identity, moves too many points or
+     * already has a word that is not longer. Returns whether it was stored.
+     */
+    fn insert(&mut self, permutation: &SparsePermutation, word: Vec<usize>) -> bool {
+        if permutation.is_empty() || permutation.len() > self.max_support {
+            return false;
+        }
+        let mut p = (1..=self.moves[0].permutation.len()).collect::<Vec<usize>>();
+        for (x, y) in permutation {
+            p[*x] = y + 1;
+        }
+        let permutation = Permutation::new(p);
+        if let Some(i) = self.by_permutation.get(&permutation) {
+            if self.entries[*i].word.len() <= word.len() {
+                return false;
+            }
+            self.entries[*i].word = word;
+            return true;
+        }
+        let cycles_id = permutation.compute_info().cycles_id;
+        let support = (0..permutation.len())
+            .filter(|x| permutation.p[*x] != x + 1)
+            .collect::<Vec<usize>>();
+        let i = self.entries.len();
+        self.by_permutation.insert(permutation.clone(), i);
+        self.by_cycles.entry(cycles_id).or_default().push(i);
+        self.by_support.entry(support.clone()).or_default().push(i);
+        self.entries.push(CycleWord {
+            permutation,
+            word,
+            support,
+        });
+        true
+    }
+
+    /*
+     * All group elements with words of at most max_length moves that have an inverse, one word per
+     * element, found breadth first.
+     */
+    fn short_words(&self, max_length: usize) -> Vec<ShortWord> {
+        let generators = (0..self.moves.len())
+            .filter(|m| self.inverses[*m].is_some())
+            .map(|m| {
+                let p = &self.moves[m].permutation.p;
+                let permutation = (0..p.len())
+                    .filter(|x| p[*x] != x + 1)
+                    .map(|x| (x, p[x] - 1))
+                    .collect::<SparsePermutation>();
+                (m, permutation)
+            })
+            .collect::<Vec<(usize, SparsePermutation)>>();
+        let mut seen: HashSet<SparsePermutation> = HashSet::from([Vec::new()]);
+        let mut words = vec![ShortWord {
+            word: Vec::new(),
+            inverse_word: Vec::new(),
+            permutation: Vec::new(),
+            inverse: Vec::new(),
+        }];
+        let mut level = 0..1;
+        for _ in 0..max_length {
+            let mut next = Vec::new();
+            for short_word in &words[level.clone()] {
+                for (m, generator) in &generators {
+                    let permutation = sparse_product(&[&short_word.permutation, generator]);
+                    if !seen.insert(permutation.clone()) {
+                        continue;
+                    }
+                    let mut word = short_word.word.clone();
+                    word.push(*m);
+                    let mut inverse_word = vec![self.inverses[*m].unwrap()];
+                    inverse_word.extend(&short_word.inverse_word);
+                    next.push(ShortWord {
+                        word,
+                        inverse_word,
+                        inverse: sparse_inverse(&permutation),
+                        permutation,
+                    });
+                }
+            }
+            level = words.len()..words.len() + next.len();
+            words.extend(next);
+            if words.len() > self.limit {
+                break;
+            }
+        }
+        words.remove(0);
+        words
+    }
+
+    /*
+     * Adds the commutators [A, B] of all words A and B with at most max_length moves, in the order
+     * of their lengths, and returns the number of new permutations.
+     */
+    pub fn add_commutators(&mut self, max_length: usize) -> usize {
+        let words = self.short_words(max_length);
+        let mut by_length: Vec<Vec<&ShortWord>> = vec![Vec::new(); max_length + 1];
+        for short_word in &words {
+            by_length[short_word.word.len()].push(short_word);
+        }
+        let mut tried = 0;
+        let mut added = 0;
+        'search: for length in 2..=2 * max_length {
+            for length_a in 1..length {
+                let length_b = length - length_a;
+                if length_a > max_length || length_b > max_length {
+                    continue;
+                }
+                for a in &by_length[length_a] {
+                    for b in &by_length[length_b] {
+                        if is_disjoint(&a.permutation, &b.permutation) {
+                            continue;
+                        }
+                        tried += 1;
+                        if tried > self.limit {
+                            debug!("Stopping after {} commutators", self.limit);
+                            break 'search;
+                        }
+                        let commutator = sparse_product(&[
+                            &a.permutation,
+                            &b.permutation,
+                            &a.inverse,
+                            &b.inverse,
+                        ]);
+                        let mut word = a.word.clone();
+                        word.extend(&b.word);
+                        word.extend(&a.inverse_word);
+                        word.extend(&b.inverse_word);
+                        if self.insert(&commutator, word) {
+                            added += 1;
+                        }
+                    }
+                }
+            }
+        }
+        debug!(
+            "Added {} of {} commutators of words up to length {}",
+            added, tried, max_length
+        );
+        added
+    }
+
+    /*
+     * Adds the conjugates C X C⁻¹ of the stored permutations X with the given cycle structure by
+     * all words C with at most max_length moves and returns the number of new permutations.
+     */
+    pub fn add_conjugates(&mut self, cycles_id: &Vec<usize>, max_length: usize) -> usize {
+        let words = self.short_words(max_length);
+        let bases = self
+            .with_cycles(cycles_id)
+            .into_iter()
+            .map(|entry| {
+                let permutation = entry
+                    .support
+                    .iter()
+                    .map(|x| (*x, entry.permutation.p[*x] - 1))
+                    .collect::<SparsePermutation>();
+                (permutation, entry.word.clone())
+            })
+            .collect::<Vec<(SparsePermutation, Vec<usize>)>>();
+        let mut tried = 0;
+        let mut added = 0;
+        'search: for c in &words {
+            for (x, x_word) in &bases {
+                tried += 1;
+                if tried > self.limit {
+                    debug!("Stopping after {} conjugates", self.limit);
+                    break 'search;
+                }
+                // C X C⁻¹ maps C⁻¹(y) to C⁻¹(X(y))
+                let mut conjugate = x
+                    .iter()
+                    .map(|(y, z)| (image(&c.inverse, *y), image(&c.inverse, *z)))
+                    .collect::<SparsePermutation>();
+                conjugate.sort_unstable();
+                let mut word = c.word.clone();
+                word.extend(x_word);
+                word.extend(&c.inverse_word);
+                if self.insert(&conjugate, word) {
+                    added += 1;
+                }
+            }
+        }
+        debug!(
+            "Added {} of {} conjugates of {:?} with words up to length {}",
+            added, tried, cycles_id, max_length
+        );
+        added
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::puzzle::{self, PuzzleType};
+    use crate::testing_utils::TestingUtils;
+
+    fn get_moves(puzzle_type: PuzzleType) -> Vec<Move> {
+        puzzle::load_puzzle_info("./../../data/puzzle_info.csv")
+            .unwrap()
+            .remove(&puzzle_type)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_commutators_of_cube() {
+        let moves = get_moves(PuzzleType::CUBE(2));
+        let generators = moves.iter().map(|m| m.permutation.clone()).collect();
+        let mut search = CommutatorSearch::new(&moves, 9, 100_000);
+        assert!(search.add_commutators(3) > 0);
+        for entry in search.entries() {
+            TestingUtils::assert_index_path_equals_permutation(
+                &entry.word,
+                &entry.permutation,
+                &generators,
+            );
+            assert!(search.with_support(&entry.support).contains(&entry));
+        }
+        // A commutator that 3-cycles corners moves three stickers of each
+        let three_corners = search.with_cycles(&vec![3, 3, 3]);
+        assert!(!three_corners.is_empty());
+        assert!(three_corners
+            .windows(2)
+            .all(|pair| pair[0].word.len() <= pair[1].word.len()));
+    }
+
+    #[test]
+    fn test_conjugates_close_3_cycles() {
+        let mut moves = Vec::new();
+        for (i, gen) in TestingUtils::get_s_n_generators(6).iter().enumerate() {
+            moves.push(Move {
+                name: format!("gen{}", i),
+                permutation: gen.clone(),
+            });
+            moves.push(Move {
+                name: format!("-gen{}", i),
+                permutation: gen.inverse(),
+            });
+        }
+        let generators = moves.iter().map(|m| m.permutation.clone()).collect();
+        let mut search = CommutatorSearch::new(&moves, 3, 1_000_000);
+        search.add_commutators(2);
+        assert!(!search.with_cycles(&vec![3]).is_empty());
+        search.add_conjugates(&vec![3], 4);
+        // All 40 3-cycles of S_6
+        let three_cycles = search.with_cycles(&vec![3]);
+        assert_eq!(three_cycles.len(), 40);
+        for entry in three_cycles {
+            TestingUtils::assert_index_path_equals_permutation(
+                &entry.word,
+                &entry.permutation,
+                &generators,
+            );
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use crate::permutation::Permutation;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::Debug;
+use std::hash::Hash;
+
+/*
+ * Unsigned integer type the points of a compact permutation are stored in.
+ */
+pub trait Point:
+    Copy + Eq + Ord + Hash + Debug + Default + Serialize + DeserializeOwned + Send + Sync
+{
+    // Largest degree of a permutation whose points fit into the type
+    const MAX_DEGREE: usize;
+
+    fn from_usize(i: usize) -> Self;
+
+    fn to_usize(self) -> usize;
+}
+
+impl Point for u8 {
+    const MAX_DEGREE: usize = u8::MAX as usize + 1;
+
+    fn from_usize(i: usize) -> Self {
+        i as u8
+    }
+
+    fn to_usize(self) -> usize {
+        self as usize
+    }
+}
+
+impl Point for u16 {
+    const MAX_DEGREE: usize = u16::MAX as usize + 1;
+
+    fn from_usize(i: usize) -> Self {
+        i as u16
+    }
+
+    fn to_usize(self) -> usize {
+        self as usize
+    }
+}
+
+/*
+ * Permutation with 0-based points stored as u8 or u16, so it takes an eighth or a quarter of the
+ * memory of a Permutation. The operations have the same meaning as the ones of Permutation, e.g.
+ * a.compose(b) applies b first, and the *_into variants write into an existing permutation
+ * instead of allocating a new one.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+// The serde bounds of the points come with Point
+#[serde(bound = "")]
+pub struct CompactPermutation<T: Point> {
+    p: Vec<T>,
+}
+
+impl<T: Point> CompactPermutation<T> {
+    pub fn identity(n: usize) -> CompactPermutation<T> {
+        assert!(n <= T::MAX_DEGREE, "Degree {} is too large", n);
+        CompactPermutation {
+            p: (0..n).map(T::from_usize).collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.p.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.p.is_empty()
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.p.iter().enumerate().all(|(i, x)| x.to_usize() == i)
+    }
+
+    // Image of the 0-based point i
+    pub fn get(&self, i: usize) -> usize {
+        self.p[i].to_usize()
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        &self.p
+    }
+
+    /*
+     * Writes self.compose(other) into result, which must have the same degree.
+     */
+    pub fn compose_into(&self, other: &CompactPermutation<T>, result: &mut CompactPermutation<T>) {
+        for (r, o) in result.p.iter_mut().zip(other.p.iter()) {
+            *r = self.p[o.to_usize()];
+        }
+    }
+
+    pub fn inverse_into(&self, result: &mut CompactPermutation<T>) {
+        for (i, x) in self.p.iter().enumerate() {
+            result.p[x.to_usize()] = T::from_usize(i);
+        }
+    }
+
+    /*
+     * Writes self.apply(v) into result, i.e. result[i] = v[self(i)].
+     */
+    pub fn apply_into<U: Clone>(&self, v: &[U], result: &mut [U]) {
+        for (r, x) in result.iter_mut().zip(self.p.iter()) {
+            *r = v[x.to_usize()].clone();
+        }
+    }
+
+    pub fn compose(&self, other: &CompactPermutation<T>) -> CompactPermutation<T> {
+        let mut result = self.clone();
+        self.compose_into(other, &mut result);
+        result
+    }
+
+    pub fn inverse(&self) -> CompactPermutation<T> {
+        let mut result = self.clone();
+        self.inverse_into(&mut result);
+        result
+    }
+
+    pub fn apply<U: Clone>(&self, v: &[U]) -> Vec<U> {
+        let mut result = v.to_vec();
+        self.apply_into(v, &mut result);
+        result
+    }
+}
+
+impl<T: Point> TryFrom<&Permutation> for CompactPermutation<T> {
+    type Error = Box<dyn Error>;
+
+    fn try_from(perm: &Permutation) -> Result<Self, Self::Error> {
+        if perm.len() > T::MAX_DEGREE {
+            return Err(format!(
+                "Permutation of degree {} does not fit into {} points",
+                perm.len(),
+                T::MAX_DEGREE
+            )
+            .into());
+        }
+        Ok(CompactPermutation {
+            p: perm.p.iter().map(|x| T::from_usize(x - 1)).collect(),
+        })
+    }
+}
+
+impl<T: Point> From<&CompactPermutation<T>> for Permutation {
+    fn from(perm: &CompactPermutation<T>) -> Self {
+        Permutation::new(perm.p.iter().map(|x| x.to_usize() + 1).collect())
+    }
+}
+
+/*
+ * Compact permutation with the smallest point type that fits the degree.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AnyCompactPermutation {
+    U8(CompactPermutation<u8>),
+    U16(CompactPermutation<u16>),
+}
+
+impl AnyCompactPermutation {
+    pub fn from_permutation(perm: &Permutation) -> Result<AnyCompactPermutation, Box<dyn Error>> {
+        if perm.len() <= u8::MAX_DEGREE {
+            Ok(AnyCompactPermutation::U8(perm.try_into()?))
+        } else {
+            Ok(AnyCompactPermutation::U16(perm.try_into()?))
+        }
+    }
+
+    pub fn to_permutation(&self) -> Permutation {
+        match self {
+            AnyCompactPermutation::U8(p) => p.into(),
+            AnyCompactPermutation::U16(p) => p.into(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            AnyCompactPermutation::U8(p) => p.len(),
+            AnyCompactPermutation::U16(p) => p.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing_utils::TestingUtils;
+
+    #[test]
+    fn test_compact_permutation_matches_permutation() {
+        let a = TestingUtils::cycle_str_to_perm("(1,2,3,4)(5,6)", 7);
+        let b = TestingUtils::cycle_str_to_perm("(2,7,3)", 7);
+        let ca = CompactPermutation::<u8>::try_from(&a).unwrap();
+        let cb = CompactPermutation::<u8>::try_from(&b).unwrap();
+        assert_eq!(Permutation::from(&ca.compose(&cb)), a.compose(&b));
+        assert_eq!(Permutation::from(&ca.inverse()), a.inverse());
+        let v = vec!['a', 'b', 'c', 'd', 'e', 'f', 'g'];
+        assert_eq!(ca.apply(&v), a.apply(&v));
+
+        let mut result = CompactPermutation::identity(7);
+        cb.compose_into(&ca, &mut result);
+        assert_eq!(Permutation::from(&result), b.compose(&a));
+        ca.inverse_into(&mut result);
+        assert!(ca.compose(&result).is_identity());
+    }
+
+    #[test]
+    fn test_compact_permutation_degree() {
+        let small = Permutation::identity(256);
+        let large = Permutation::identity(257);
+        assert!(CompactPermutation::<u8>::try_from(&small).is_ok());
+        assert!(CompactPermutation::<u8>::try_from(&large).is_err());
+        assert!(matches!(
+            AnyCompactPermutation::from_permutation(&small).unwrap(),
+            AnyCompactPermutation::U8(_)
+        ));
+        let any = AnyCompactPermutation::from_permutation(&large).unwrap();
+        assert!(matches!(any, AnyCompactPermutation::U16(_)));
+        assert_eq!(any.to_permutation(), large);
+    }
+}
@@ -0,0 +1,646 @@
+use crate::parallel::SolveOptions;
+use crate::puzzle::{self, Move, Puzzle, PuzzleFilter, PuzzleType};
+use crate::window_optimizer::WindowOptions;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Method {
+    /// Products of 2- and 3-cycles from the per-type cycle libraries
+    Ktt,
+    /// IDA* for wreath puzzles
+    Wreath,
+    /// Factorization with the Minkwitz SGS tables, needs a target file
+    Minkwitz,
+    /// Cycle libraries as in ktt, with setup moves for the cycles they miss
+    Cycles,
+    /// IDA* with misplaced facelets and pattern databases for all puzzle types
+    Ida,
+    /// Beam search for large puzzles
+    Beam,
+    /// Two-phase algorithm for the 3x3x3 cubes
+    Kociemba,
+    /// Reduction to a 3x3x3 cube for the larger cubes
+    Reduction,
+    /// Ring by ring with flip-rotate-flip commutators for globe puzzles
+    Globe,
+}
+
+// The moves of every puzzle type and the puzzles selected by the filter
+pub type LoadedPuzzles = (HashMap<PuzzleType, Vec<Move>>, Vec<Puzzle>);
+
+/*
+ * Configuration of a run. Every section (and every field of a section) is optional in the file and
+ * falls back to the defaults below, so a config only has to list what differs from them. The
+ * effective config can be written back to a file to reproduce a run.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // Methods tried by `solve` when no method is given, in this order
+    pub enabled_methods: Vec<Method>,
+    pub filter: FilterConfig,
+    pub solve: SolveConfig,
+    pub minkwitz: MinkwitzConfig,
+    pub search: SearchConfig,
+    pub ida: IdaConfig,
+    pub beam: BeamConfig,
+    pub kociemba: KociembaConfig,
+    pub reduction: ReductionConfig,
+    pub globe: GlobeConfig,
+    pub short_words: ShortWordConfig,
+    pub wildcards: WildcardConfig,
+    pub visited: VisitedConfig,
+    pub paths: PathsConfig,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterConfig {
+    // Puzzle types like "cube_3/3/3", all types if empty
+    pub puzzle_types: Vec<String>,
+    // Puzzle ids like "1,4,10-20", all ids if empty
+    pub ids: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SolveConfig {
+    // Number of worker threads, all cores if not set
+    pub threads: Option<usize>,
+    // Time budget per puzzle in seconds, unlimited if not set
+    pub time_budget_secs: Option<u64>,
+    // Shorten the solutions with the peephole optimizer
+    pub optimize: bool,
+    // Replace segments of the solutions by shorter words from the short word tables
+    pub window_optimize: bool,
+}
+
+impl Default for SolveConfig {
+    fn default() -> Self {
+        SolveConfig {
+            threads: None,
+            time_budget_secs: None,
+            optimize: true,
+            window_optimize: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BaseKind {
+    // The points 0, ..., n-1 in order
+    Identity,
+    // The base found by Schreier-Sims, cached in the bases directory
+    Schreier,
+    // The order in which the coordinate calculation solves the pieces
+    Custom,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MinkwitzConfig {
+    // Group elements processed when building a new SGS table
+    pub n: usize,
+    // Number of new table entries after which the table is improved
+    pub s: usize,
+    // Initial word length limit of the table entries
+    pub w: usize,
+    // Group elements processed when improving an existing SGS table, 0 to use it as it is
+    pub improve_steps: usize,
+    // Rounds of improving the tables and solving all puzzles again
+    pub rounds: usize,
+    pub base: BaseKind,
+    pub use_dijkstra_search: bool,
+}
+
+impl Default for MinkwitzConfig {
+    fn default() -> Self {
+        MinkwitzConfig {
+            n: 100_000,
+            s: 1_000_000,
+            w: 40,
+            improve_steps: 1_000_000,
+            rounds: 4,
+            base: BaseKind::Identity,
+            use_dijkstra_search: false,
+        }
+    }
+}
+
+/*
+ * Limits of the breadth first searches (number of group elements visited unless noted otherwise).
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchConfig {
+    // Words tried when searching for the first c-cycle of every orbit of a cycle library
+    pub seed_search_limit: usize,
+    // Bound on the permutation entries (words tried * degree) kept in memory by the seed search
+    pub seed_search_entries: usize,
+    // Conjugates stored per seed of a cycle library and per orbit of a factorization
+    pub conjugate_limit: usize,
+    // Longest words A, B of the commutators [A, B] tried as seeds by cycle libraries and
+    // extract-cycles before the breadth first search
+    pub commutator_length: usize,
+    // Commutators tried per puzzle type
+    pub commutator_limit: usize,
+    // Nodes searched when choosing an in-group target of a puzzle with colors
+    pub target_node_limit: usize,
+    // Longest setup word conjugating a library cycle onto a missing one in the cycles method
+    pub cycle_setup_depth: usize,
+    // Largest IDA* bound tried for wreath puzzles
+    pub wreath_max_bound: usize,
+    // Elements searched for c-cycles by extract-cycles
+    pub extract_cube_limit: usize,
+    pub extract_globe_limit: usize,
+    // Globes with both dimensions below 25
+    pub extract_small_globe_limit: usize,
+    pub extract_wreath_limit: usize,
+    // Wreaths with fewer than 30 pieces per ring
+    pub extract_small_wreath_limit: usize,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            seed_search_limit: 100_000,
+            seed_search_entries: 50_000_000,
+            conjugate_limit: 1_000_000,
+            commutator_length: 2,
+            commutator_limit: 1_000_000,
+            target_node_limit: 1_000_000,
+            cycle_setup_depth: 2,
+            wreath_max_bound: 10_000,
+            extract_cube_limit: 100_000,
+            extract_globe_limit: 500_000,
+            extract_small_globe_limit: 1_000_000,
+            extract_wreath_limit: 1_000_000,
+            extract_small_wreath_limit: 4_000_000,
+        }
+    }
+}
+
+impl SearchConfig {
+    pub fn extract_limit(&self, puzzle_type: &PuzzleType) -> usize {
+        match puzzle_type {
+            PuzzleType::CUBE(_) => self.extract_cube_limit,
+            PuzzleType::GLOBE(n, m) if *n < 25 && *m < 25 => self.extract_small_globe_limit,
+            PuzzleType::GLOBE(_, _) => self.extract_globe_limit,
+            PuzzleType::WREATH(n) if *n < 30 => self.extract_small_wreath_limit,
+            PuzzleType::WREATH(_) => self.extract_wreath_limit,
+        }
+    }
+}
+
+/*
+ * Generic IDA* of the ida method.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdaConfig {
+    // Largest bound (solution length) tried
+    pub max_bound: usize,
+    // Largest number of abstract states of a pattern database, 0 to not use them
+    pub pattern_size: u64,
+}
+
+impl Default for IdaConfig {
+    fn default() -> Self {
+        IdaConfig {
+            max_bound: 30,
+            pattern_size: 100_000_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum BeamScore {
+    /// Number of misplaced facelets
+    Misplaced,
+    /// Number of colors not completely in place, then misplaced facelets
+    Colors,
+}
+
+/*
+ * Beam search of the beam method.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BeamConfig {
+    // States kept per level
+    pub width: usize,
+    // Longest sequence searched
+    pub max_depth: usize,
+    pub score: BeamScore,
+}
+
+impl Default for BeamConfig {
+    fn default() -> Self {
+        BeamConfig {
+            width: 1000,
+            max_depth: 10_000,
+            score: BeamScore::Misplaced,
+        }
+    }
+}
+
+/*
+ * Two-phase solver of the kociemba method.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KociembaConfig {
+    // Longest solution (quarter turns, without the center moves) searched
+    pub max_length: usize,
+    // Phase 1 solutions completed by phase 2 before the best solution is taken
+    pub probes: usize,
+}
+
+impl Default for KociembaConfig {
+    fn default() -> Self {
+        KociembaConfig {
+            max_length: 50,
+            probes: 1000,
+        }
+    }
+}
+
+/*
+ * Reduction of the cubes of size 4 and larger.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReductionConfig {
+    // Longest setup of the conjugated 3-cycles that solve wings and centers
+    pub setup_depth: usize,
+}
+
+impl Default for ReductionConfig {
+    fn default() -> Self {
+        ReductionConfig { setup_depth: 6 }
+    }
+}
+
+/*
+ * Search limits of the globe method, checked for every sticker that is put in place.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GlobeConfig {
+    // Largest rotation of the upper row against the lower row a translated commutator may use,
+    // half the 66 columns of the largest globes by default, i.e. no limit
+    pub max_offset: usize,
+    // Whether commutators may be conjugated with a flip to reach stickers of the other row
+    pub setup_flips: bool,
+}
+
+impl Default for GlobeConfig {
+    fn default() -> Self {
+        GlobeConfig {
+            max_offset: 33,
+            setup_flips: true,
+        }
+    }
+}
+
+/*
+ * Short word tables of the window optimizer, one per puzzle type.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShortWordConfig {
+    // Word length up to which all group elements are stored
+    pub depth: usize,
+    // Bound on the permutation entries (non-fixed points) of the stored elements
+    pub entries: usize,
+    // Longest segment of a solution that is looked up in the table
+    pub window: usize,
+}
+
+impl Default for ShortWordConfig {
+    fn default() -> Self {
+        ShortWordConfig {
+            depth: 3,
+            entries: 20_000_000,
+            window: 12,
+        }
+    }
+}
+
+/*
+ * Shortening of solutions of puzzles with wildcards, which only have to end within num_wildcards
+ * mismatches of the goal state.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WildcardConfig {
+    // Cut the solutions after the first state within the wildcards
+    pub truncate: bool,
+    // Number of moves at the end of a solution after which a shorter suffix is searched
+    pub suffix_window: usize,
+    // Longest suffix searched, 0 to only cut the solutions
+    pub suffix_depth: usize,
+    // Nodes searched for shorter suffixes per solution
+    pub node_limit: usize,
+}
+
+impl Default for WildcardConfig {
+    fn default() -> Self {
+        WildcardConfig {
+            truncate: true,
+            suffix_window: 8,
+            suffix_depth: 3,
+            node_limit: 200_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum VisitedStrategy {
+    /// Hash set of 64-bit fingerprints
+    Exact64,
+    /// Hash set of 128-bit fingerprints
+    Exact128,
+    /// Bloom filter, drops reachable elements at its false positive rate
+    Bloom,
+    /// Hash table of 64-bit fingerprints in a temporary file
+    Disk,
+}
+
+/*
+ * Set holding the visited elements of the depth limited group iterators.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VisitedConfig {
+    pub strategy: VisitedStrategy,
+    // Number of elements the bloom filter and the disk set are sized for and above which a set
+    // logs that it exceeds its capacity, the exact sets grow from empty
+    pub items: usize,
+    // False positive rate of the bloom filter at that number of elements
+    pub fp_rate: f64,
+    // Directory of the disk backed sets, the temporary directory if not set
+    pub disk_dir: Option<String>,
+}
+
+impl Default for VisitedConfig {
+    fn default() -> Self {
+        VisitedConfig {
+            strategy: VisitedStrategy::Exact64,
+            items: 1_000_000,
+            fp_rate: 0.0000001,
+            disk_dir: None,
+        }
+    }
+}
+
+/*
+ * Data files and directories. Relative paths are relative to data_dir.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathsConfig {
+    pub data_dir: String,
+    pub puzzle_info: String,
+    pub puzzles: String,
+    // Solutions of a solve run
+    pub solutions: String,
+    // Directory with one csv of solutions (id, moves, length) per puzzle
+    pub solutions_dir: String,
+    pub cycle_libraries: String,
+    pub short_word_tables: String,
+    pub pattern_databases: String,
+    pub kociemba_tables: String,
+    // c-cycles written by extract-cycles
+    pub cycles: String,
+    // Target permutations of the minkwitz method
+    pub targets: String,
+    pub bases: String,
+    pub minkwitz_tables: String,
+    // Submission a merge starts from
+    pub submission: String,
+    // Directory of other complete submissions whose solutions are merged in
+    pub other_submissions: String,
+    pub merged_submission: String,
+}
+
+impl Default for PathsConfig {
+    fn default() -> Self {
+        PathsConfig {
+            data_dir: "./../../data".to_string(),
+            puzzle_info: "puzzle_info.csv".to_string(),
+            puzzles: "puzzles.csv".to_string(),
+            solutions: "solutions.csv".to_string(),
+            solutions_dir: "solutions".to_string(),
+            cycle_libraries: "cycle_libraries".to_string(),
+            short_word_tables: "short_word_tables".to_string(),
+            pattern_databases: "pattern_databases".to_string(),
+            kociemba_tables: "kociemba_tables".to_string(),
+            cycles: "cycles".to_string(),
+            targets: "target.csv".to_string(),
+            bases: "bases".to_string(),
+            minkwitz_tables: "minkwitz_tables".to_string(),
+            submission: "baseline.csv".to_string(),
+            other_submissions: "improvement_complete_csvs".to_string(),
+            merged_submission: "submission_new.csv".to_string(),
+        }
+    }
+}
+
+impl PathsConfig {
+    pub fn resolve(&self, path: &str) -> String {
+        Path::new(&self.data_dir)
+            .join(path)
+            .to_string_lossy()
+            .to_string()
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            enabled_methods: vec![Method::Ktt, Method::Wreath],
+            filter: FilterConfig::default(),
+            solve: SolveConfig::default(),
+            minkwitz: MinkwitzConfig::default(),
+            search: SearchConfig::default(),
+            ida: IdaConfig::default(),
+            beam: BeamConfig::default(),
+            kociemba: KociembaConfig::default(),
+            reduction: ReductionConfig::default(),
+            globe: GlobeConfig::default(),
+            short_words: ShortWordConfig::default(),
+            wildcards: WildcardConfig::default(),
+            visited: VisitedConfig::default(),
+            paths: PathsConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /*
+     * Reads a config from a .toml or .json file.
+     */
+    pub fn from_file(path: &str) -> Result<Config, Box<dyn Error>> {
+        let content = fs::read_to_string(path)?;
+        match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("toml") => Ok(toml::from_str(&content)?),
+            Some("json") => Ok(serde_json::from_str(&content)?),
+            _ => Err(format!("Config {} is neither a .toml nor a .json file", path).into()),
+        }
+    }
+
+    /*
+     * Loads the config file given as the first command line argument, or the defaults if there is
+     * none.
+     */
+    pub fn from_args() -> Result<Config, Box<dyn Error>> {
+        match std::env::args().nth(1) {
+            Some(path) => Config::from_file(&path),
+            None => Ok(Config::default()),
+        }
+    }
+
+    /*
+     * Writes the config as TOML, e.g. next to the solutions of a run.
+     */
+    pub fn write_to_file(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn is_method_enabled(&self, method: Method) -> bool {
+        self.enabled_methods.contains(&method)
+    }
+
+    pub fn puzzle_filter(&self) -> Result<PuzzleFilter, Box<dyn Error>> {
+        let mut puzzle_types = Vec::new();
+        for puzzle_type in &self.filter.puzzle_types {
+            puzzle_types.push(PuzzleType::from_str(puzzle_type)?);
+        }
+        let ids = if self.filter.ids.is_empty() {
+            Vec::new()
+        } else {
+            puzzle::parse_ids(&self.filter.ids)?
+        };
+        Ok(PuzzleFilter { puzzle_types, ids })
+    }
+
+    /*
+     * Loads the moves of every puzzle type and the puzzles selected by the filter.
+     */
+    pub fn load_puzzles(&self) -> Result<LoadedPuzzles, Box<dyn Error>> {
+        let puzzles_info = puzzle::load_puzzle_info(&self.paths.resolve(&self.paths.puzzle_info))?;
+        let puzzles =
+            puzzle::load_puzzles(&self.paths.resolve(&self.paths.puzzles), &puzzles_info)?;
+        let puzzles = self.puzzle_filter()?.apply(&puzzles);
+        Ok((puzzles_info, puzzles))
+    }
+
+    /*
+     * Options of the parallel driver, without a solution store.
+     */
+    pub fn solve_options(&self) -> SolveOptions {
+        SolveOptions {
+            num_threads: self.solve.threads,
+            time_budget: self.solve.time_budget_secs.map(Duration::from_secs),
+            solution_store: None,
+            optimize: self.solve.optimize,
+            window: self.window_options(),
+            wildcards: self.wildcard_options(),
+        }
+    }
+
+    /*
+     * Options of the window optimizer, None if it is disabled.
+     */
+    pub fn window_options(&self) -> Option<WindowOptions> {
+        if !self.solve.window_optimize {
+            return None;
+        }
+        Some(WindowOptions {
+            tables_path: self.paths.resolve(&self.paths.short_word_tables),
+            short_words: self.short_words.clone(),
+            visited: self.visited.clone(),
+        })
+    }
+
+    /*
+     * Options of the wildcard truncation, None if it is disabled.
+     */
+    pub fn wildcard_options(&self) -> Option<WildcardConfig> {
+        match self.wildcards.truncate {
+            true => Some(self.wildcards.clone()),
+            false => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_from_toml() {
+        let mut config: Config = toml::from_str(
+            r#"
+            enabled_methods = ["minkwitz"]
+
+            [filter]
+            puzzle_types = ["globe_1/8"]
+            ids = "1,4-5"
+
+            [minkwitz]
+            w = 20
+
+            [paths]
+            data_dir = "/data"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.enabled_methods, vec![Method::Minkwitz]);
+        assert!(config.is_method_enabled(Method::Minkwitz));
+        assert!(!config.is_method_enabled(Method::Ktt));
+        assert_eq!(config.minkwitz.w, 20);
+        assert_eq!(config.minkwitz.n, MinkwitzConfig::default().n);
+        assert_eq!(config.search, SearchConfig::default());
+        assert_eq!(config.paths.resolve(&config.paths.cycles), "/data/cycles");
+        assert_eq!(config.paths.resolve("/tmp/cycles"), "/tmp/cycles");
+
+        let filter = config.puzzle_filter().unwrap();
+        assert_eq!(filter.puzzle_types, vec![PuzzleType::GLOBE(1, 8)]);
+        assert_eq!(filter.ids, vec![1, 4, 5]);
+        config.filter.puzzle_types = vec!["cube".to_string()];
+        assert!(config.puzzle_filter().is_err());
+
+        assert!(toml::from_str::<Config>("unknown = 1").is_err());
+    }
+
+    #[test]
+    fn test_config_file_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("santa_config_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut config = Config::default();
+        config.solve.threads = Some(3);
+        config.visited.items = 42;
+        config.visited.strategy = VisitedStrategy::Disk;
+
+        let toml_path = dir.join("run.toml").to_string_lossy().to_string();
+        config.write_to_file(&toml_path).unwrap();
+        assert_eq!(Config::from_file(&toml_path).unwrap(), config);
+
+        let json_path = dir.join("run.json").to_string_lossy().to_string();
+        fs::write(&json_path, serde_json::to_string(&config).unwrap()).unwrap();
+        assert_eq!(Config::from_file(&json_path).unwrap(), config);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -21,7 +21,7 @@ pub fn find_target_c_cycle_in_conjugated_group(
     let gens = index_to_move
         .iter()
         .map(|x| x.permutation.clone())
-        .collect();
+        .collect::<Vec<Permutation>>();
     let generator_iterator = DepthLimitedPermutationGroupIterator::new(&gens, 10000);
     for (_, _gen_path) in generator_iterator {
         if counter % 10000 == 0 {
@@ -145,6 +145,22 @@ pub fn get_layers(cube_size: usize) -> Vec<Vec<usize>> {
     layers
 }
 
+/*
+ * Facelets (0-based) of the corners of every face, all facelets of a 2x2x2 cube.
+ */
+pub fn get_corner_facelets(cube_size: usize) -> Vec<usize> {
+    let solution_state: Vec<usize> = (1..=6 * cube_size * cube_size).collect();
+    let cube_side_pos = create_cube_pos(solution_state, cube_size).unwrap();
+    let corner_pos = create_corners_pos(&cube_side_pos, cube_size);
+    let mut result = corner_pos
+        .values()
+        .flat_map(|corners| corners.values().map(|x| x - 1))
+        .collect::<Vec<_>>();
+    result.sort();
+    result.dedup();
+    result
+}
+
 pub fn get_cube_order_to_traverse(cube_size: usize) -> Vec<usize> {
     let layers = get_layers(cube_size);
     let mut result = Vec::new();
@@ -217,6 +233,14 @@ mod tests {
         assert_eq!(layers, expected_layers);
     }
 
+    #[test]
+    fn test_get_corner_facelets() {
+        assert_eq!(get_corner_facelets(2), (0..24).collect::<Vec<_>>());
+        let corners = get_corner_facelets(3);
+        assert_eq!(corners.len(), 24);
+        assert_eq!(&corners[..5], &[0, 2, 6, 8, 9]);
+    }
+
     #[test]
     fn test_get_cube_order_to_traverse() {
         let cube_size = 4;
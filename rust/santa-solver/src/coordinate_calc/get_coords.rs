@@ -14,3 +14,17 @@ pub fn get_moves_to_solve(puzzle: &crate::puzzle::Puzzle) -> Vec<usize> {
         }
     }
 }
+
+/*
+ * Facelets a pattern database of the puzzle type is projected onto: the corners of a cube, the top
+ * ring of a globe or the left ring of a wreath.
+ */
+pub fn get_pattern_facelets(puzzle_type: &crate::puzzle::PuzzleType) -> Vec<usize> {
+    match puzzle_type {
+        crate::puzzle::PuzzleType::CUBE(n) => crate::coordinate_calc::cube::get_corner_facelets(*n),
+        crate::puzzle::PuzzleType::GLOBE(m, n) => {
+            crate::coordinate_calc::globe::get_globe_ring(*m, *n, 0)
+        }
+        crate::puzzle::PuzzleType::WREATH(n) => crate::coordinate_calc::wreath::get_left_ring(*n),
+    }
+}
@@ -18,6 +18,14 @@ pub fn get_globe_order_to_traverse(m: usize, n: usize) -> Vec<usize> {
     return result;
 }
 
+/*
+ * Faces of the ring between two horizontal cuts, row 0 being the top one
+ */
+pub fn get_globe_ring(m: usize, n: usize, row: usize) -> Vec<usize> {
+    assert!(row <= m, "Globe {}/{} has no row {}", m, n, row);
+    (row * 2 * n..(row + 1) * 2 * n).collect()
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -0,0 +1,7 @@
+/*
+ * Wreath n/n has two rings of n pieces that share two of them, so it has 2n - 2 pieces. The left
+ * ring (move l) consists of the first n pieces, the right one of the others and the two shared ones.
+ */
+pub fn get_left_ring(n: usize) -> Vec<usize> {
+    (0..n).collect()
+}
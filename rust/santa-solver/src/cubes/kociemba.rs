@@ -1,37 +1,1129 @@
-// Define the Rubik's Cube struct to represent the cube state
-struct Cube3x3 {
-    // Your cube representation goes here
+use crate::config::{Config, KociembaConfig};
+use crate::parallel::{self, Deadline, SolveOptions};
+use crate::puzzle::{self, Move, Puzzle, PuzzleType};
+use crate::versioned_file;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::error::Error;
+
+// Header of a tables file, bumped whenever the layout of the tables changes
+const KOCIEMBA_TABLES_MAGIC: [u8; 4] = *b"SKOC";
+const KOCIEMBA_TABLES_VERSION: u32 = 1;
+
+const NUM_FACELETS: usize = 54;
+const FACELETS_PER_FACE: usize = 9;
+const NUM_CORNERS: usize = 8;
+const NUM_EDGES: usize = 12;
+// Edges in the U and D layers, the remaining 4 are in the slice between them
+const NUM_UD_EDGES: usize = 8;
+const NUM_TWISTS: usize = 2187;
+const NUM_FLIPS: usize = 2048;
+const NUM_SLICES: usize = 495;
+const NUM_PERMUTATIONS_8: usize = 40320;
+const NUM_PERMUTATIONS_4: usize = 24;
+const UNKNOWN: u8 = u8::MAX;
+// Nodes between two checks of the deadline
+const DEADLINE_INTERVAL: usize = 100_000;
+
+fn binomial(n: usize, k: usize) -> usize {
+    if k > n {
+        return 0;
+    }
+    (0..k).fold(1, |acc, i| acc * (n - i) / (i + 1))
+}
+
+fn factorial(n: usize) -> usize {
+    (1..=n).product()
+}
+
+/*
+ * Colexicographic rank of a set of slots, given as a bit mask.
+ */
+fn rank_combination(mask: u16) -> usize {
+    let mut rank = 0;
+    let mut k = 0;
+    for slot in 0..NUM_EDGES {
+        if mask & (1 << slot) != 0 {
+            k += 1;
+            rank += binomial(slot, k);
+        }
+    }
+    rank
+}
+
+fn unrank_combination(mut rank: usize, k: usize) -> u16 {
+    let mut mask = 0;
+    for i in (1..=k).rev() {
+        let mut slot = i - 1;
+        while binomial(slot + 1, i) <= rank {
+            slot += 1;
+        }
+        rank -= binomial(slot, i);
+        mask |= 1 << slot;
+    }
+    mask
+}
+
+/*
+ * Lehmer code of a permutation of 0..n.
+ */
+fn rank_permutation(values: &[u8]) -> usize {
+    let n = values.len();
+    let mut rank = 0;
+    for i in 0..n {
+        let smaller = values[i + 1..].iter().filter(|v| **v < values[i]).count();
+        rank += smaller * factorial(n - 1 - i);
+    }
+    rank
+}
+
+fn unrank_permutation(mut rank: usize, n: usize) -> Vec<u8> {
+    let mut remaining: Vec<u8> = (0..n as u8).collect();
+    let mut values = Vec::with_capacity(n);
+    for i in 0..n {
+        let f = factorial(n - 1 - i);
+        values.push(remaining.remove(rank / f));
+        rank %= f;
+    }
+    values
+}
+
+/*
+ * State of the cube on the level of its pieces: the cubie in every corner and edge slot and its
+ * orientation, i.e. at which facelet of the slot the reference facelet of the cubie is.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CubieCube {
+    cp: [u8; NUM_CORNERS],
+    co: [u8; NUM_CORNERS],
+    ep: [u8; NUM_EDGES],
+    eo: [u8; NUM_EDGES],
+}
+
+impl CubieCube {
+    pub fn solved() -> CubieCube {
+        CubieCube {
+            cp: [0, 1, 2, 3, 4, 5, 6, 7],
+            co: [0; NUM_CORNERS],
+            ep: [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+            eo: [0; NUM_EDGES],
+        }
+    }
+
+    /*
+     * The state after applying other to self, e.g. a move to a state.
+     */
+    pub fn multiply(&self, other: &CubieCube) -> CubieCube {
+        let mut result = CubieCube::solved();
+        for i in 0..NUM_CORNERS {
+            let from = other.cp[i] as usize;
+            result.cp[i] = self.cp[from];
+            result.co[i] = (self.co[from] + other.co[i]) % 3;
+        }
+        for i in 0..NUM_EDGES {
+            let from = other.ep[i] as usize;
+            result.ep[i] = self.ep[from];
+            result.eo[i] = (self.eo[from] + other.eo[i]) % 2;
+        }
+        result
+    }
+
+    // Swaps the first two edges if the corner permutation is odd, so both have the same parity
+    fn match_edge_parity(&mut self) {
+        let mut inversions = 0;
+        for i in 0..NUM_CORNERS {
+            inversions += self.cp[i + 1..].iter().filter(|c| **c < self.cp[i]).count();
+        }
+        if inversions % 2 == 1 {
+            self.ep.swap(0, 1);
+        }
+    }
+
+    fn twist(&self) -> usize {
+        self.co[..NUM_CORNERS - 1]
+            .iter()
+            .fold(0, |acc, o| acc * 3 + *o as usize)
+    }
+
+    fn set_twist(&mut self, mut twist: usize) {
+        let mut sum = 0;
+        for i in (0..NUM_CORNERS - 1).rev() {
+            self.co[i] = (twist % 3) as u8;
+            sum += self.co[i];
+            twist /= 3;
+        }
+        self.co[NUM_CORNERS - 1] = (3 - sum % 3) % 3;
+    }
+
+    fn flip(&self) -> usize {
+        self.eo[..NUM_EDGES - 1]
+            .iter()
+            .fold(0, |acc, o| acc * 2 + *o as usize)
+    }
+
+    fn set_flip(&mut self, mut flip: usize) {
+        let mut sum = 0;
+        for i in (0..NUM_EDGES - 1).rev() {
+            self.eo[i] = (flip % 2) as u8;
+            sum += self.eo[i];
+            flip /= 2;
+        }
+        self.eo[NUM_EDGES - 1] = sum % 2;
+    }
+
+    // Slots of the slice edges, regardless of their order
+    fn slice(&self) -> usize {
+        let mut mask = 0;
+        for (slot, edge) in self.ep.iter().enumerate() {
+            if *edge as usize >= NUM_UD_EDGES {
+                mask |= 1 << slot;
+            }
+        }
+        rank_combination(mask)
+    }
+
+    fn set_slice(&mut self, slice: usize) {
+        let mask = unrank_combination(slice, NUM_EDGES - NUM_UD_EDGES);
+        let mut ud_edges = 0..NUM_UD_EDGES as u8;
+        let mut slice_edges = NUM_UD_EDGES as u8..NUM_EDGES as u8;
+        for slot in 0..NUM_EDGES {
+            self.ep[slot] = match mask & (1 << slot) != 0 {
+                true => slice_edges.next().unwrap(),
+                false => ud_edges.next().unwrap(),
+            };
+        }
+    }
+
+    fn corners(&self) -> usize {
+        rank_permutation(&self.cp)
+    }
+
+    fn set_corners(&mut self, corners: usize) {
+        self.cp
+            .copy_from_slice(&unrank_permutation(corners, NUM_CORNERS));
+    }
+
+    // Permutation of the UD edges, only meaningful if they are all in the UD layers
+    fn ud_edges(&self) -> usize {
+        rank_permutation(&self.ep[..NUM_UD_EDGES])
+    }
+
+    fn set_ud_edges(&mut self, ud_edges: usize) {
+        self.ep[..NUM_UD_EDGES].copy_from_slice(&unrank_permutation(ud_edges, NUM_UD_EDGES));
+    }
+
+    // Permutation of the slice edges, only meaningful if they are all in the slice
+    fn slice_edges(&self) -> usize {
+        let values = self.ep[NUM_UD_EDGES..]
+            .iter()
+            .map(|e| e - NUM_UD_EDGES as u8)
+            .collect::<Vec<u8>>();
+        rank_permutation(&values)
+    }
+
+    fn set_slice_edges(&mut self, slice_edges: usize) {
+        let values = unrank_permutation(slice_edges, NUM_EDGES - NUM_UD_EDGES);
+        for (i, v) in values.iter().enumerate() {
+            self.ep[NUM_UD_EDGES + i] = v + NUM_UD_EDGES as u8;
+        }
+    }
+}
+
+/*
+ * Where the cubies of the 3x3x3 cube are in the facelet layout of the puzzle, derived from its moves
+ * alone: the outer layer moves are the ones fixing the centers, facelets moved by the same three
+ * (two) faces form a corner (edge), and faces whose moves commute are opposite. The axis of face 0
+ * is the UD axis and the axis of the next face the FB axis of the two-phase algorithm.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Layout {
+    // Facelets of every corner slot, starting with the one on a UD face, all in the same rotation
+    corners: Vec<[usize; 3]>,
+    // Facelets of every edge slot, starting with the one on a UD face (or on a FB face for the
+    // slice edges), the slots of the UD layers first
+    edges: Vec<[usize; 2]>,
+    centers: Vec<usize>,
+    // Quarter turns of the outer layers (move indices) with their face
+    face_turns: Vec<(usize, usize)>,
+    // Moves that turn a middle slice and so move the centers
+    slice_turns: Vec<usize>,
+    // Axis of every face, 0 for UD and 1 for FB
+    axis_of_face: Vec<usize>,
+}
+
+impl Layout {
+    pub fn new(moves: &[Move]) -> Result<Layout, Box<dyn Error>> {
+        if moves.iter().any(|m| m.permutation.p.len() != NUM_FACELETS) {
+            return Err("Moves do not act on the facelets of a 3x3x3 cube".into());
+        }
+        let mut face_turns = Vec::new();
+        let mut slice_turns = Vec::new();
+        for (i, m) in moves.iter().enumerate() {
+            let mut moved = [0; NUM_FACELETS / FACELETS_PER_FACE];
+            for (x, y) in m.permutation.p.iter().enumerate() {
+                if x + 1 != *y {
+                    moved[x / FACELETS_PER_FACE] += 1;
+                }
+            }
+            match moved.iter().position(|c| *c == FACELETS_PER_FACE - 1) {
+                Some(face) => face_turns.push((i, face)),
+                None => slice_turns.push(i),
+            }
+        }
+        if face_turns.len() != 12 {
+            return Err(format!("Found {} instead of 12 face turns", face_turns.len()).into());
+        }
+        let mut turn_of_face: BTreeMap<usize, usize> = BTreeMap::new();
+        for (i, face) in &face_turns {
+            turn_of_face.entry(*face).or_insert(*i);
+        }
+        let moves_facelet = |turn: usize, x: usize| moves[turn].permutation.p[x] != x + 1;
+        let centers = (0..NUM_FACELETS)
+            .filter(|x| face_turns.iter().all(|(i, _)| !moves_facelet(*i, *x)))
+            .collect::<Vec<usize>>();
+        if centers.len() != 6 || turn_of_face.len() != 6 {
+            return Err("Face turns do not fix exactly one center per face".into());
+        }
+
+        // Faces whose turns commute are opposite
+        let mut axis_of_face = vec![usize::MAX; 6];
+        let mut num_axes = 0;
+        for face in 0..6 {
+            if axis_of_face[face] != usize::MAX {
+                continue;
+            }
+            let a = &moves[turn_of_face[&face]].permutation;
+            for other in face + 1..6 {
+                let b = &moves[turn_of_face[&other]].permutation;
+                if a.compose(b) == b.compose(a) {
+                    axis_of_face[other] = num_axes;
+                }
+            }
+            axis_of_face[face] = num_axes;
+            num_axes += 1;
+        }
+        if num_axes != 3 {
+            return Err("Faces do not form three axes".into());
+        }
+        let face_of = |x: usize| x / FACELETS_PER_FACE;
+        let axis_of = |x: usize| axis_of_face[face_of(x)];
+
+        // Facelets turned by the same faces belong to the same cubie
+        let mut cubies: BTreeMap<Vec<usize>, Vec<usize>> = BTreeMap::new();
+        for x in (0..NUM_FACELETS).filter(|x| !centers.contains(x)) {
+            let faces = turn_of_face
+                .iter()
+                .filter(|(_, turn)| moves_facelet(**turn, x))
+                .map(|(face, _)| *face)
+                .collect::<Vec<usize>>();
+            cubies.entry(faces).or_default().push(x);
+        }
+        let mut corners = Vec::new();
+        let mut ud_edges = Vec::new();
+        let mut slice_edges = Vec::new();
+        for facelets in cubies.values() {
+            match facelets.len() {
+                3 => corners.push([facelets[0], facelets[1], facelets[2]]),
+                2 => {
+                    let mut edge = [facelets[0], facelets[1]];
+                    if edge.iter().any(|x| axis_of(*x) == 0) {
+                        if axis_of(edge[0]) != 0 {
+                            edge.swap(0, 1);
+                        }
+                        ud_edges.push(edge);
+                    } else {
+                        if axis_of(edge[0]) != 1 {
+                            edge.swap(0, 1);
+                        }
+                        slice_edges.push(edge);
+                    }
+                }
+                _ => return Err("Facelets do not form corners and edges".into()),
+            }
+        }
+        if corners.len() != NUM_CORNERS
+            || ud_edges.len() != NUM_UD_EDGES
+            || slice_edges.len() != NUM_EDGES - NUM_UD_EDGES
+        {
+            return Err("Facelets do not form 8 corners and 12 edges".into());
+        }
+        let mut edges = ud_edges;
+        edges.append(&mut slice_edges);
+
+        // Face turns rotate the corners rigidly, so spreading the order of the facelets of one
+        // corner along them orders all corners in the same rotation
+        let to_reference = |mut corner: [usize; 3]| {
+            while axis_of(corner[0]) != 0 {
+                corner.rotate_left(1);
+            }
+            corner
+        };
+        let mut oriented: Vec<Option<[usize; 3]>> = vec![None; NUM_CORNERS];
+        oriented[0] = Some(to_reference(corners[0]));
+        let mut queue = VecDeque::from([0]);
+        while let Some(slot) = queue.pop_front() {
+            let corner = oriented[slot].unwrap();
+            for (turn, _) in &face_turns {
+                let p = &moves[*turn].permutation.p;
+                let image = corner.map(|x| p[x] - 1);
+                let target = corners.iter().position(|c| c.contains(&image[0])).unwrap();
+                if oriented[target].is_none() {
+                    oriented[target] = Some(to_reference(image));
+                    queue.push_back(target);
+                }
+            }
+        }
+        let corners = oriented.into_iter().collect::<Option<Vec<[usize; 3]>>>();
+        let corners = corners.ok_or("Face turns do not connect all corners")?;
+
+        Ok(Layout {
+            corners,
+            edges,
+            centers,
+            face_turns,
+            slice_turns,
+            axis_of_face,
+        })
+    }
+
+    /*
+     * The cubies of a state, identified by the colors the goal state has at their facelets. None
+     * if the colors do not identify every cubie and its orientation.
+     */
+    pub fn cubies(&self, state: &[usize], goal: &[usize]) -> Option<CubieCube> {
+        let mut cube = CubieCube::solved();
+        for (slot, facelets) in self.corners.iter().enumerate() {
+            let mut matches = self.corners.iter().enumerate().flat_map(|(cubie, home)| {
+                (0..3)
+                    .filter(move |o| (0..3).all(|j| state[facelets[(j + o) % 3]] == goal[home[j]]))
+                    .map(move |o| (cubie, o))
+            });
+            let (cubie, o) = matches.next()?;
+            if matches.next().is_some() {
+                return None;
+            }
+            cube.cp[slot] = cubie as u8;
+            cube.co[slot] = o as u8;
+        }
+        for (slot, facelets) in self.edges.iter().enumerate() {
+            let mut matches = self.edges.iter().enumerate().flat_map(|(cubie, home)| {
+                (0..2)
+                    .filter(move |o| (0..2).all(|j| state[facelets[(j + o) % 2]] == goal[home[j]]))
+                    .map(move |o| (cubie, o))
+            });
+            let (cubie, o) = matches.next()?;
+            if matches.next().is_some() {
+                return None;
+            }
+            cube.ep[slot] = cubie as u8;
+            cube.eo[slot] = o as u8;
+        }
+        Some(cube)
+    }
+
+    /*
+     * Effect of a move on the cubies, read off the move applied to distinct labels.
+     */
+    fn cubie_move(&self, m: &Move) -> CubieCube {
+        let labels = (0..NUM_FACELETS).collect::<Vec<usize>>();
+        self.cubies(&m.permutation.apply(&labels), &labels).unwrap()
+    }
+}
+
+/*
+ * Move of one of the two phases: a quarter turn, or a half turn done as two quarter turns.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct PhaseMove {
+    moves: Vec<usize>,
+    face: usize,
+    axis: usize,
+    cubies: CubieCube,
+}
+
+/*
+ * Coordinate move tables and pruning tables of the two phases. Phase 1 brings the cube into the
+ * subgroup generated by the UD quarter turns and the other half turns, tracking the corner twist,
+ * the edge flip and the slots of the slice edges. Phase 2 solves the cube within that subgroup,
+ * tracking the permutations of the corners, the UD edges and the slice edges. Distances count
+ * quarter turns, as every move of the puzzle does.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KociembaTables {
+    move_names: Vec<String>,
+    layout: Layout,
+    phase1_moves: Vec<PhaseMove>,
+    phase2_moves: Vec<PhaseMove>,
+    // New coordinate of every coordinate (row) and move (column)
+    twist_moves: Vec<u16>,
+    flip_moves: Vec<u16>,
+    slice_moves: Vec<u16>,
+    corner_moves: Vec<u16>,
+    ud_edge_moves: Vec<u16>,
+    slice_edge_moves: Vec<u16>,
+    // Distances to the goal of pairs of coordinates
+    twist_slice_distances: Vec<u8>,
+    flip_slice_distances: Vec<u8>,
+    corner_slice_edge_distances: Vec<u8>,
+    ud_edge_slice_edge_distances: Vec<u8>,
 }
 
-// Implement methods for cube manipulation (rotations, etc.)
-impl Cube3x3 {
-    // Methods to perform cube rotations
+fn move_table<S, G>(size: usize, moves: &Vec<PhaseMove>, set: S, get: G) -> Vec<u16>
+where
+    S: Fn(&mut CubieCube, usize),
+    G: Fn(&CubieCube) -> usize,
+{
+    let mut table = Vec::with_capacity(size * moves.len());
+    for coordinate in 0..size {
+        let mut cube = CubieCube::solved();
+        set(&mut cube, coordinate);
+        for m in moves {
+            table.push(get(&cube.multiply(&m.cubies)) as u16);
+        }
+    }
+    table
+}
+
+/*
+ * Distances of all pairs of two coordinates to the goal pair, where every move costs as many
+ * quarter turns as it has. Filled level by level, keeping the smallest distance.
+ */
+fn pruning_table(
+    table_a: &[u16],
+    table_b: &[u16],
+    size_b: usize,
+    moves: &[PhaseMove],
+    goal: usize,
+) -> Vec<u8> {
+    let num_moves = moves.len();
+    let size = table_a.len() / num_moves * size_b;
+    let mut distances = vec![UNKNOWN; size];
+    distances[goal] = 0;
+    let mut depth = 0;
+    let mut max_depth = 0;
+    while depth <= max_depth {
+        for index in 0..size {
+            if distances[index] != depth {
+                continue;
+            }
+            let (a, b) = (index / size_b, index % size_b);
+            for (i, m) in moves.iter().enumerate() {
+                let next = table_a[a * num_moves + i] as usize * size_b
+                    + table_b[b * num_moves + i] as usize;
+                let distance = depth + m.moves.len() as u8;
+                if distance < distances[next] {
+                    distances[next] = distance;
+                    max_depth = max_depth.max(distance);
+                }
+            }
+        }
+        depth += 1;
+    }
+    distances
+}
+
+impl KociembaTables {
+    pub fn build(moves: &[Move]) -> Result<KociembaTables, Box<dyn Error>> {
+        let layout = Layout::new(moves)?;
+        let mut phase1_moves = Vec::new();
+        let mut phase2_moves: Vec<PhaseMove> = Vec::new();
+        for (turn, face) in &layout.face_turns {
+            let cubies = layout.cubie_move(&moves[*turn]);
+            let axis = layout.axis_of_face[*face];
+            let quarter_turn = PhaseMove {
+                moves: vec![*turn],
+                face: *face,
+                axis,
+                cubies: cubies.clone(),
+            };
+            if axis == 0 {
+                phase2_moves.push(quarter_turn.clone());
+            } else if !phase2_moves.iter().any(|m| m.face == *face) {
+                phase2_moves.push(PhaseMove {
+                    moves: vec![*turn, *turn],
+                    face: *face,
+                    axis,
+                    cubies: cubies.multiply(&cubies),
+                });
+            }
+            phase1_moves.push(quarter_turn);
+        }
+
+        let twist_moves = move_table(NUM_TWISTS, &phase1_moves, CubieCube::set_twist, |c| {
+            c.twist()
+        });
+        let flip_moves = move_table(NUM_FLIPS, &phase1_moves, CubieCube::set_flip, |c| c.flip());
+        let slice_moves = move_table(NUM_SLICES, &phase1_moves, CubieCube::set_slice, |c| {
+            c.slice()
+        });
+        let corner_moves = move_table(
+            NUM_PERMUTATIONS_8,
+            &phase2_moves,
+            CubieCube::set_corners,
+            |c| c.corners(),
+        );
+        let ud_edge_moves = move_table(
+            NUM_PERMUTATIONS_8,
+            &phase2_moves,
+            CubieCube::set_ud_edges,
+            |c| c.ud_edges(),
+        );
+        let slice_edge_moves = move_table(
+            NUM_PERMUTATIONS_4,
+            &phase2_moves,
+            CubieCube::set_slice_edges,
+            |c| c.slice_edges(),
+        );
+        let goal_slice = CubieCube::solved().slice();
+        let twist_slice_distances = pruning_table(
+            &twist_moves,
+            &slice_moves,
+            NUM_SLICES,
+            &phase1_moves,
+            goal_slice,
+        );
+        let flip_slice_distances = pruning_table(
+            &flip_moves,
+            &slice_moves,
+            NUM_SLICES,
+            &phase1_moves,
+            goal_slice,
+        );
+        let corner_slice_edge_distances = pruning_table(
+            &corner_moves,
+            &slice_edge_moves,
+            NUM_PERMUTATIONS_4,
+            &phase2_moves,
+            0,
+        );
+        let ud_edge_slice_edge_distances = pruning_table(
+            &ud_edge_moves,
+            &slice_edge_moves,
+            NUM_PERMUTATIONS_4,
+            &phase2_moves,
+            0,
+        );
+        Ok(KociembaTables {
+            move_names: moves.iter().map(|m| m.name.clone()).collect(),
+            layout,
+            phase1_moves,
+            phase2_moves,
+            twist_moves,
+            flip_moves,
+            slice_moves,
+            corner_moves,
+            ud_edge_moves,
+            slice_edge_moves,
+            twist_slice_distances,
+            flip_slice_distances,
+            corner_slice_edge_distances,
+            ud_edge_slice_edge_distances,
+        })
+    }
+
+    pub fn write_to_file(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        versioned_file::write_versioned(path, KOCIEMBA_TABLES_MAGIC, KOCIEMBA_TABLES_VERSION, self)
+    }
+
+    pub fn read_from_file(path: &str) -> Option<KociembaTables> {
+        versioned_file::read_versioned(
+            path,
+            KOCIEMBA_TABLES_MAGIC,
+            KOCIEMBA_TABLES_VERSION,
+            "Kociemba tables",
+        )
+    }
+
+    /*
+     * Loads the tables of the puzzle type from the tables directory, or builds and writes them
+     * there if they are missing or were built for other moves.
+     */
+    pub fn load_or_build(
+        tables_path: &str,
+        puzzle_type: &PuzzleType,
+        moves: &[Move],
+    ) -> Result<KociembaTables, Box<dyn Error>> {
+        let path = format!("{}/{}.bin", tables_path, puzzle_type);
+        if let Some(tables) = KociembaTables::read_from_file(&path) {
+            if tables.move_names.iter().eq(moves.iter().map(|m| &m.name))
+                && tables.layout == Layout::new(moves)?
+            {
+                info!("Loaded Kociemba tables from {}", path);
+                return Ok(tables);
+            }
+            warn!("Kociemba tables {} do not match, rebuilding them", path);
+        }
+        info!("Building Kociemba tables for {}", puzzle_type);
+        let tables = KociembaTables::build(moves)?;
+        if let Err(e) = tables.write_to_file(&path) {
+            warn!("Could not write Kociemba tables to {}: {}", path, e);
+        }
+        Ok(tables)
+    }
+
+    fn phase1_distance(&self, twist: usize, flip: usize, slice: usize) -> u8 {
+        self.twist_slice_distances[twist * NUM_SLICES + slice]
+            .max(self.flip_slice_distances[flip * NUM_SLICES + slice])
+    }
+
+    fn phase2_distance(&self, corners: usize, ud_edges: usize, slice_edges: usize) -> u8 {
+        self.corner_slice_edge_distances[corners * NUM_PERMUTATIONS_4 + slice_edges]
+            .max(self.ud_edge_slice_edge_distances[ud_edges * NUM_PERMUTATIONS_4 + slice_edges])
+    }
+
+    /*
+     * Solves a cube with the slice turns that bring its centers in place followed by the two
+     * phases. None if its colors do not identify the cubies or no solution was found in time.
+     */
+    pub fn solve(
+        &self,
+        puzzle: &Puzzle,
+        config: &KociembaConfig,
+        deadline: &Deadline,
+    ) -> Option<Vec<usize>> {
+        let mut word = self.center_moves(puzzle)?;
+        let mut state = puzzle.initial_state.clone();
+        for m in &word {
+            state = puzzle.moves[*m].permutation.apply(&state);
+        }
+        let Some(cube) = self.layout.cubies(&state, &puzzle.goal_state) else {
+            debug!("Colors of puzzle {} do not identify its cubies", puzzle.id);
+            return None;
+        };
+        let mut kociemba = Kociemba::new(self, config, cube);
+        word.extend(kociemba.solve(deadline)?);
+        debug!(
+            "Solved puzzle {} after {} phase 1 solutions",
+            puzzle.id, kociemba.probes
+        );
+        Some(word)
+    }
+
+    /*
+     * Solves only the corners of a cube whose edges and centers are at their goal. Two edges are
+     * swapped if the corners have odd parity, so the two phases get a solvable cube.
+     */
+    pub fn solve_corners(
+        &self,
+        puzzle: &Puzzle,
+        config: &KociembaConfig,
+        deadline: &Deadline,
+    ) -> Option<Vec<usize>> {
+        let mut cube = self
+            .layout
+            .cubies(&puzzle.initial_state, &puzzle.goal_state)?;
+        cube.match_edge_parity();
+        Kociemba::new(self, config, cube).solve(deadline)
+    }
+
+    /*
+     * Shortest sequence of slice turns that brings the centers of the state to the ones of the
+     * goal, as the two phases only use face turns.
+     */
+    fn center_moves(&self, puzzle: &Puzzle) -> Option<Vec<usize>> {
+        let project = |state: &Vec<usize>| {
+            self.layout
+                .centers
+                .iter()
+                .map(|x| state[*x])
+                .collect::<Vec<usize>>()
+        };
+        let goal = project(&puzzle.goal_state);
+        let mut parents: HashMap<Vec<usize>, Option<(Vec<usize>, usize)>> = HashMap::new();
+        let mut queue = VecDeque::new();
+        parents.insert(puzzle.initial_state.clone(), None);
+        queue.push_back(puzzle.initial_state.clone());
+        while let Some(state) = queue.pop_front() {
+            if project(&state) == goal {
+                let mut word = Vec::new();
+                let mut current = state;
+                while let Some((parent, m)) = parents[&current].clone() {
+                    word.push(m);
+                    current = parent;
+                }
+                word.reverse();
+                return Some(word);
+            }
+            for m in &self.layout.slice_turns {
+                let next = puzzle.moves[*m].permutation.apply(&state);
+                if !parents.contains_key(&next) {
+                    parents.insert(next.clone(), Some((state.clone(), *m)));
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
 }
 
-// Define functions for the Kociemba algorithm phases
-fn phase1(cube: &mut Cube3x3) {
-    // Implement phase 1 logic
+/*
+ * Whether a move after the previous ones only repeats a shorter sequence: turning the last face
+ * back, a third quarter turn (or second half turn) of the same face, or turning the opposite faces
+ * of an axis in the other order.
+ */
+fn is_redundant(moves: &[PhaseMove], path: &[usize], m: usize) -> bool {
+    let Some(&last) = path.last() else {
+        return false;
+    };
+    let (a, b) = (&moves[m], &moves[last]);
+    if a.face == b.face {
+        let before = path.len().checked_sub(2).map(|i| path[i]);
+        return m != last || a.moves.len() > 1 || before == Some(m);
+    }
+    a.axis == b.axis && a.face < b.face
 }
 
-fn phase2(cube: &mut Cube3x3) {
-    // Implement phase 2 logic
+/*
+ * Two-phase search of a single cube: tries phase 1 solutions of increasing length and completes
+ * each with a shortest phase 2 solution that beats the best solution so far.
+ */
+pub struct Kociemba<'a> {
+    tables: &'a KociembaTables,
+    config: &'a KociembaConfig,
+    cube: CubieCube,
+    best: Option<Vec<usize>>,
+    probes: usize,
+    nodes: usize,
+    aborted: bool,
 }
 
-// Main solving function using Kociemba algorithm
-fn solve(cube: &mut Cube3x3) {
-    phase1(cube);
-    phase2(cube);
+impl<'a> Kociemba<'a> {
+    pub fn new(
+        tables: &'a KociembaTables,
+        config: &'a KociembaConfig,
+        cube: CubieCube,
+    ) -> Kociemba<'a> {
+        Kociemba {
+            tables,
+            config,
+            cube,
+            best: None,
+            probes: 0,
+            nodes: 0,
+            aborted: false,
+        }
+    }
+
+    fn is_aborted(&mut self, deadline: &Deadline) -> bool {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(DEADLINE_INTERVAL) && deadline.is_expired() {
+            self.aborted = true;
+        }
+        self.aborted
+    }
+
+    fn max_length(&self) -> usize {
+        match &self.best {
+            Some(best) => best.len().saturating_sub(1),
+            None => self.config.max_length,
+        }
+    }
+
+    fn phase1(
+        &mut self,
+        (twist, flip, slice): (usize, usize, usize),
+        depth: usize,
+        path: &mut Vec<usize>,
+        deadline: &Deadline,
+    ) {
+        if self.is_aborted(deadline) || self.probes >= self.config.probes {
+            return;
+        }
+        let tables = self.tables;
+        let distance = tables.phase1_distance(twist, flip, slice) as usize;
+        if depth == 0 {
+            // Phase 1 solutions ending in a phase 2 move were already tried one move shorter
+            let ends_in_phase2 = path
+                .last()
+                .is_some_and(|m| tables.phase1_moves[*m].axis == 0);
+            if distance == 0 && !ends_in_phase2 {
+                self.probes += 1;
+                self.phase2_start(path, deadline);
+            }
+            return;
+        }
+        if distance > depth {
+            return;
+        }
+        let num_moves = tables.phase1_moves.len();
+        for m in 0..num_moves {
+            if is_redundant(&tables.phase1_moves, path, m) {
+                continue;
+            }
+            let next = (
+                tables.twist_moves[twist * num_moves + m] as usize,
+                tables.flip_moves[flip * num_moves + m] as usize,
+                tables.slice_moves[slice * num_moves + m] as usize,
+            );
+            path.push(m);
+            self.phase1(next, depth - 1, path, deadline);
+            path.pop();
+            if self.aborted {
+                return;
+            }
+        }
+    }
+
+    fn phase2_start(&mut self, phase1: &Vec<usize>, deadline: &Deadline) {
+        let tables = self.tables;
+        let length1 = phase1
+            .iter()
+            .map(|m| tables.phase1_moves[*m].moves.len())
+            .sum::<usize>();
+        if length1 > self.max_length() {
+            return;
+        }
+        let mut cube = self.cube.clone();
+        for m in phase1 {
+            cube = cube.multiply(&tables.phase1_moves[*m].cubies);
+        }
+        let coordinates = (cube.corners(), cube.ud_edges(), cube.slice_edges());
+        let max_bound = self.max_length() - length1;
+        let mut bound =
+            tables.phase2_distance(coordinates.0, coordinates.1, coordinates.2) as usize;
+        while bound <= max_bound {
+            let mut path = Vec::new();
+            if self.phase2(coordinates, bound, &mut path, deadline) {
+                let mut word = Vec::new();
+                for m in phase1 {
+                    word.extend(&tables.phase1_moves[*m].moves);
+                }
+                for m in &path {
+                    word.extend(&tables.phase2_moves[*m].moves);
+                }
+                debug!(
+                    "Two-phase solution with {} + {} moves",
+                    length1,
+                    word.len() - length1
+                );
+                self.best = Some(word);
+                return;
+            }
+            if self.aborted {
+                return;
+            }
+            bound += 1;
+        }
+    }
+
+    // Whether a phase 2 solution of at most bound quarter turns completes the path
+    fn phase2(
+        &mut self,
+        (corners, ud_edges, slice_edges): (usize, usize, usize),
+        bound: usize,
+        path: &mut Vec<usize>,
+        deadline: &Deadline,
+    ) -> bool {
+        if self.is_aborted(deadline) {
+            return false;
+        }
+        let tables = self.tables;
+        let distance = tables.phase2_distance(corners, ud_edges, slice_edges) as usize;
+        if distance > bound {
+            return false;
+        }
+        if distance == 0 && corners == 0 && ud_edges == 0 && slice_edges == 0 {
+            return true;
+        }
+        let num_moves = tables.phase2_moves.len();
+        for m in 0..num_moves {
+            let cost = tables.phase2_moves[m].moves.len();
+            if cost > bound || is_redundant(&tables.phase2_moves, path, m) {
+                continue;
+            }
+            let next = (
+                tables.corner_moves[corners * num_moves + m] as usize,
+                tables.ud_edge_moves[ud_edges * num_moves + m] as usize,
+                tables.slice_edge_moves[slice_edges * num_moves + m] as usize,
+            );
+            path.push(m);
+            if self.phase2(next, bound - cost, path, deadline) {
+                return true;
+            }
+            path.pop();
+            if self.aborted {
+                return false;
+            }
+        }
+        false
+    }
+
+    /*
+     * Returns the shortest solution (move indices) found until the probes are used up, no shorter
+     * one can exist or the deadline expires.
+     */
+    pub fn solve(&mut self, deadline: &Deadline) -> Option<Vec<usize>> {
+        if self.cube == CubieCube::solved() {
+            return Some(Vec::new());
+        }
+        let tables = self.tables;
+        let start = (self.cube.twist(), self.cube.flip(), self.cube.slice());
+        let mut depth = tables.phase1_distance(start.0, start.1, start.2) as usize;
+        while depth <= self.max_length() && !self.aborted && self.probes < self.config.probes {
+            self.phase1(start, depth, &mut Vec::new(), deadline);
+            depth += 1;
+        }
+        self.best.clone()
+    }
 }
 
-fn main() {
-    // Create an instance of RubiksCube
-    let mut cube = Cube3x3 {
-        // Initialize your cube state
+/*
+ * Solves the 3x3x3 cubes among the puzzles with the two-phase algorithm. The slice turns only
+ * bring the centers in place first, and cubes whose goal colors do not identify every cubie are
+ * left to the other methods.
+ */
+pub fn solve_puzzles(
+    puzzles: &[Puzzle],
+    config: &Config,
+    options: &SolveOptions,
+) -> HashMap<usize, String> {
+    let puzzle_type = PuzzleType::CUBE(3);
+    let cubes = puzzles
+        .iter()
+        .filter(|p| p.puzzle_type == puzzle_type)
+        .cloned()
+        .collect::<Vec<Puzzle>>();
+    if cubes.is_empty() {
+        return HashMap::new();
+    }
+    let tables_path = config.paths.resolve(&config.paths.kociemba_tables);
+    let tables = match KociembaTables::load_or_build(&tables_path, &puzzle_type, &cubes[0].moves) {
+        Ok(tables) => tables,
+        Err(e) => {
+            warn!("No Kociemba tables for {}: {}", puzzle_type, e);
+            return HashMap::new();
+        }
     };
+    parallel::solve_in_parallel(&cubes, options, |puzzle, deadline| {
+        let word = tables.solve(puzzle, &config.kociemba, deadline)?;
+        parallel::log_solved(puzzle, word.len());
+        Some(puzzle::word_to_string(&puzzle.moves, &word))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::puzzle;
+    use crate::validation::validate_solution;
+
+    fn get_cubes() -> Vec<Puzzle> {
+        let puzzle_info = puzzle::load_puzzle_info("./../../data/puzzle_info.csv").unwrap();
+        let puzzles = puzzle::load_puzzles("./../../data/puzzles.csv", &puzzle_info).unwrap();
+        puzzles
+            .into_iter()
+            .filter(|p| p.puzzle_type == PuzzleType::CUBE(3))
+            .collect()
+    }
+
+    #[test]
+    fn test_coordinates() {
+        for rank in [0, 17, 494] {
+            assert_eq!(rank_combination(unrank_combination(rank, 4)), rank);
+        }
+        assert_eq!(rank_combination(0xF00), 494);
+        for rank in [0, 1, 5039, 40319] {
+            assert_eq!(rank_permutation(&unrank_permutation(rank, 8)), rank);
+        }
+        let mut cube = CubieCube::solved();
+        cube.set_twist(1234);
+        cube.set_flip(567);
+        cube.set_slice(89);
+        assert_eq!((cube.twist(), cube.flip(), cube.slice()), (1234, 567, 89));
+        assert_eq!(cube.co.iter().sum::<u8>() % 3, 0);
+        assert_eq!(cube.eo.iter().sum::<u8>() % 2, 0);
+    }
+
+    #[test]
+    fn test_layout_and_cubie_moves() {
+        let cubes = get_cubes();
+        let moves = &cubes[0].moves;
+        let layout = Layout::new(moves).unwrap();
+        assert_eq!(layout.face_turns.len(), 12);
+        assert_eq!(layout.slice_turns.len(), 6);
+        let labels = (0..NUM_FACELETS).collect::<Vec<usize>>();
+        // Applying moves to the facelets and to the cubies gives the same cubies
+        let mut state = labels.clone();
+        let mut cube = CubieCube::solved();
+        for (turn, _) in layout.face_turns.iter().cycle().step_by(5).take(40) {
+            state = moves[*turn].permutation.apply(&state);
+            cube = cube.multiply(&layout.cubie_move(&moves[*turn]));
+            assert_eq!(layout.cubies(&state, &labels), Some(cube.clone()));
+        }
+        assert_eq!(cube.co.iter().sum::<u8>() % 3, 0);
+        assert_eq!(cube.eo.iter().sum::<u8>() % 2, 0);
+    }
+
+    #[test]
+    fn test_kociemba_solves() {
+        let cubes = get_cubes();
+        let tables = KociembaTables::build(&cubes[0].moves).unwrap();
+        let config = KociembaConfig {
+            probes: 10,
+            ..KociembaConfig::default()
+        };
+        let puzzle = cubes
+            .iter()
+            .find(|p| p.goal_string.starts_with("A;A;A") && p.num_wildcards == 0)
+            .unwrap();
+        let word = tables
+            .solve(puzzle, &config, &Deadline::unlimited())
+            .unwrap();
+        let names = puzzle::word_to_string(&puzzle.moves, &word);
+        assert_eq!(validate_solution(puzzle, &names), Ok(word.len()));
+
+        // A single face turn of only the corners leaves them with odd parity
+        let corners = tables
+            .layout
+            .corners
+            .iter()
+            .flatten()
+            .copied()
+            .collect::<Vec<usize>>();
+        let mut corner_puzzle = puzzle.clone();
+        let turned = tables.phase1_moves[0].moves[0];
+        let state = puzzle.moves[turned].permutation.apply(&puzzle.goal_state);
+        corner_puzzle.initial_state = puzzle.goal_state.clone();
+        for x in &corners {
+            corner_puzzle.initial_state[*x] = state[*x];
+        }
+        let word = tables
+            .solve_corners(&corner_puzzle, &config, &Deadline::unlimited())
+            .unwrap();
+        let mut state = corner_puzzle.initial_state.clone();
+        for m in &word {
+            state = puzzle.moves[*m].permutation.apply(&state);
+        }
+        assert!(corners.iter().all(|x| state[*x] == puzzle.goal_state[*x]));
+    }
 
-    // Solve the cube using Kociemba algorithm
-    solve(&mut cube);
+    #[test]
+    fn test_solved_cubies() {
+        let cubes = get_cubes();
+        let tables = KociembaTables::build(&cubes[0].moves).unwrap();
+        let config = KociembaConfig::default();
+        let mut puzzle = cubes
+            .iter()
+            .find(|p| p.goal_string.starts_with("A;A;A") && p.num_wildcards == 0)
+            .unwrap()
+            .clone();
+        puzzle.initial_state = puzzle.goal_state.clone();
+        let word = tables
+            .solve(&puzzle, &config, &Deadline::unlimited())
+            .unwrap();
+        assert!(word.is_empty());
 
-    // Display the solved cube or print the solution steps
-    // ...
+        // A slice turn moves only the centers and the edges of the slice, which the slice turns
+        // of the center search bring back
+        let slice_turn = tables.layout.slice_turns[0];
+        puzzle.initial_state = puzzle.moves[slice_turn]
+            .permutation
+            .apply(&puzzle.goal_state);
+        let word = tables
+            .solve(&puzzle, &config, &Deadline::unlimited())
+            .unwrap();
+        let names = puzzle::word_to_string(&puzzle.moves, &word);
+        assert_eq!(validate_solution(&puzzle, &names), Ok(1));
+    }
 }
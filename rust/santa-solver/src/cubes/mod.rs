@@ -1 +1,2 @@
 pub mod kociemba;
+pub mod reduction;
@@ -0,0 +1,1158 @@
+use crate::config::{Config, KociembaConfig, ReductionConfig};
+use crate::cubes::kociemba::KociembaTables;
+use crate::parallel::{self, Deadline, SolveOptions};
+use crate::permutation::Permutation;
+use crate::puzzle::{self, Move, Puzzle, PuzzleType};
+use log::{debug, warn};
+use rayon::prelude::*;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::iter;
+
+const NUM_FACES: usize = 6;
+// Size of the cube a reduced cube is solved as
+const VIRTUAL_SIZE: usize = 3;
+const VIRTUAL_FACE_SIZE: usize = VIRTUAL_SIZE * VIRTUAL_SIZE;
+// Pieces in an orbit of wings or of centers
+const ORBIT_SIZE: usize = 24;
+// Cycles chained to move one piece into place
+const MAX_CHAIN_LENGTH: usize = 3;
+
+// Stickers a move changes
+fn support(m: &Move) -> Vec<usize> {
+    m.permutation
+        .p
+        .iter()
+        .enumerate()
+        .filter(|(x, y)| **y != x + 1)
+        .map(|(x, _)| x)
+        .collect()
+}
+
+// Sticker whose color ends up at sticker x after the word
+fn pull(moves: &[Move], word: &[usize], x: usize) -> usize {
+    word.iter()
+        .rev()
+        .fold(x, |x, m| moves[*m].permutation.p[x] - 1)
+}
+
+fn apply_word(moves: &[Move], word: &[usize], state: &[usize]) -> Vec<usize> {
+    word.iter().fold(state.to_vec(), |state, m| {
+        moves[*m].permutation.apply(&state)
+    })
+}
+
+fn inverse_word(inverses: &[usize], word: &[usize]) -> Vec<usize> {
+    word.iter().rev().map(|m| inverses[*m]).collect()
+}
+
+// Whether the permutation that sends every i to targets[i] is odd
+fn is_odd(targets: &[usize]) -> bool {
+    let mut visited = vec![false; targets.len()];
+    let mut is_odd = false;
+    for start in 0..targets.len() {
+        let mut i = start;
+        while !visited[i] {
+            visited[i] = true;
+            i = targets[i];
+            if !visited[i] {
+                is_odd = !is_odd;
+            }
+        }
+    }
+    is_odd
+}
+
+/*
+ * The reduced NxN cube, i.e. its corners, together with the middle edges and centers if N is odd,
+ * seen as a 3x3x3 cube. Its moves are the face turns and the turns of all inner layers at once,
+ * which keep the centers of a face together and the edges paired, restricted to these facelets.
+ * They are named like the moves of cube_3/3/3, so the reduced cubes share the Kociemba tables of
+ * the real 3x3x3 cubes.
+ */
+pub struct VirtualCube {
+    size: usize,
+    moves: Vec<Move>,
+    // NxN moves of every move
+    words: Vec<Vec<usize>>,
+    // NxN sticker of every facelet
+    facelets: Vec<usize>,
+}
+
+impl VirtualCube {
+    pub fn new(moves: &[Move], size: usize) -> Result<VirtualCube, Box<dyn Error>> {
+        if size < VIRTUAL_SIZE {
+            return Err(format!("Cube of size {} cannot be reduced", size).into());
+        }
+        let index_of: HashMap<&str, usize> = moves
+            .iter()
+            .enumerate()
+            .map(|(i, m)| (m.name.as_str(), i))
+            .collect();
+        let layers = [vec![0], (1..size - 1).collect(), vec![size - 1]];
+        let rows = [0, size / 2, size - 1];
+        let face_size = size * size;
+        let facelets = (0..NUM_FACES * VIRTUAL_FACE_SIZE)
+            .map(|j| {
+                let (a, b) = ((j % VIRTUAL_FACE_SIZE) / VIRTUAL_SIZE, j % VIRTUAL_SIZE);
+                j / VIRTUAL_FACE_SIZE * face_size + rows[a] * size + rows[b]
+            })
+            .collect::<Vec<usize>>();
+        let project = |v: usize| match v {
+            0 => 0,
+            v if v == size - 1 => 2,
+            _ => 1,
+        };
+        let class_of = |x: usize| {
+            let (row, column) = ((x % face_size) / size, x % size);
+            x / face_size * VIRTUAL_FACE_SIZE + project(row) * VIRTUAL_SIZE + project(column)
+        };
+        let labels = (0..NUM_FACES * face_size).collect::<Vec<usize>>();
+        let mut virtual_moves = Vec::new();
+        let mut words = Vec::new();
+        for axis in ["d", "f", "r"] {
+            for (layer, nxn_layers) in layers.iter().enumerate() {
+                let mut word = Vec::new();
+                let mut inverse = Vec::new();
+                for l in nxn_layers {
+                    let name = format!("{}{}", axis, l);
+                    match (
+                        index_of.get(name.as_str()),
+                        index_of.get(format!("-{}", name).as_str()),
+                    ) {
+                        (Some(m), Some(m_inverse)) => {
+                            word.push(*m);
+                            inverse.push(*m_inverse);
+                        }
+                        _ => return Err(format!("Cube has no move {}", name).into()),
+                    }
+                }
+                let pulled = apply_word(moves, &word, &labels);
+                let p = facelets
+                    .iter()
+                    .map(|x| class_of(pulled[*x]) + 1)
+                    .collect::<Vec<usize>>();
+                let mut sorted = p.clone();
+                sorted.sort_unstable();
+                if !sorted.iter().enumerate().all(|(i, x)| i + 1 == *x) {
+                    return Err(
+                        format!("Move {}{} does not keep the reduced cube", axis, layer).into(),
+                    );
+                }
+                let permutation = Permutation::new(p);
+                virtual_moves.push(Move {
+                    name: format!("{}{}", axis, layer),
+                    permutation: permutation.clone(),
+                });
+                words.push(word);
+                virtual_moves.push(Move {
+                    name: format!("-{}{}", axis, layer),
+                    permutation: permutation.inverse(),
+                });
+                words.push(inverse);
+            }
+        }
+        Ok(VirtualCube {
+            size,
+            moves: virtual_moves,
+            words,
+            facelets,
+        })
+    }
+
+    // Whether a facelet is a sticker of the NxN cube, and not only a stand-in for even N
+    fn is_tracked(&self, j: usize) -> bool {
+        let (a, b) = ((j % VIRTUAL_FACE_SIZE) / VIRTUAL_SIZE, j % VIRTUAL_SIZE);
+        self.size % 2 == 1 || (a != 1 && b != 1)
+    }
+
+    fn tracked_stickers(&self) -> Vec<usize> {
+        (0..self.facelets.len())
+            .filter(|j| self.is_tracked(*j))
+            .map(|j| self.facelets[j])
+            .collect()
+    }
+
+    /*
+     * The 3x3x3 cube of a state, whose facelets that are not tracked are at their goal.
+     */
+    fn puzzle(&self, puzzle: &Puzzle, state: &[usize]) -> Puzzle {
+        let goal_state = self
+            .facelets
+            .iter()
+            .map(|x| puzzle.goal_state[*x])
+            .collect::<Vec<usize>>();
+        let initial_state = self
+            .facelets
+            .iter()
+            .enumerate()
+            .map(|(j, x)| match self.is_tracked(j) {
+                true => state[*x],
+                false => goal_state[j],
+            })
+            .collect();
+        Puzzle {
+            id: puzzle.id,
+            initial_state,
+            goal_state,
+            init_string: String::new(),
+            goal_string: String::new(),
+            moves: self.moves.clone(),
+            num_wildcards: 0,
+            puzzle_type: PuzzleType::CUBE(VIRTUAL_SIZE),
+        }
+    }
+}
+
+/*
+ * Pieces of a cube, i.e. the stickers that are changed by the same moves, and the orbits in which
+ * the moves permute them.
+ */
+struct Pieces {
+    stickers: Vec<Vec<usize>>,
+    piece_of: Vec<usize>,
+    orbits: Vec<Vec<usize>>,
+}
+
+impl Pieces {
+    fn new(moves: &[Move], supports: &[Vec<usize>]) -> Pieces {
+        let n = moves[0].permutation.p.len();
+        let mut signatures: Vec<Vec<usize>> = vec![Vec::new(); n];
+        for (i, support) in supports.iter().enumerate() {
+            for x in support {
+                signatures[*x].push(i);
+            }
+        }
+        let mut index_of: HashMap<&Vec<usize>, usize> = HashMap::new();
+        let mut stickers: Vec<Vec<usize>> = Vec::new();
+        let mut piece_of = vec![0; n];
+        for (x, signature) in signatures.iter().enumerate() {
+            let piece = *index_of.entry(signature).or_insert_with(|| {
+                stickers.push(Vec::new());
+                stickers.len() - 1
+            });
+            stickers[piece].push(x);
+            piece_of[x] = piece;
+        }
+
+        let mut parent = (0..stickers.len()).collect::<Vec<usize>>();
+        fn root(parent: &mut [usize], mut piece: usize) -> usize {
+            while parent[piece] != piece {
+                parent[piece] = parent[parent[piece]];
+                piece = parent[piece];
+            }
+            piece
+        }
+        for (m, support) in moves.iter().zip(supports.iter()) {
+            for x in support {
+                let a = root(&mut parent, piece_of[*x]);
+                let b = root(&mut parent, piece_of[m.permutation.p[*x] - 1]);
+                parent[a.max(b)] = a.min(b);
+            }
+        }
+        let mut orbit_of: HashMap<usize, usize> = HashMap::new();
+        let mut orbits: Vec<Vec<usize>> = Vec::new();
+        for piece in 0..stickers.len() {
+            let r = root(&mut parent, piece);
+            let orbit = *orbit_of.entry(r).or_insert_with(|| {
+                orbits.push(Vec::new());
+                orbits.len() - 1
+            });
+            orbits[orbit].push(piece);
+        }
+        Pieces {
+            stickers,
+            piece_of,
+            orbits,
+        }
+    }
+
+    // Whether the move permutes the pieces of the orbit oddly
+    fn is_odd_move(&self, m: &Move, orbit: &[usize]) -> bool {
+        let position: HashMap<usize, usize> = orbit
+            .iter()
+            .enumerate()
+            .map(|(i, piece)| (*piece, i))
+            .collect();
+        let targets = orbit
+            .iter()
+            .map(|piece| {
+                let x = self.stickers[*piece][0];
+                position[&self.piece_of[m.permutation.p[x] - 1]]
+            })
+            .collect::<Vec<usize>>();
+        is_odd(&targets)
+    }
+}
+
+/*
+ * A word with the stickers it changes, each with the sticker whose color it gets.
+ */
+#[derive(Debug, Clone, PartialEq)]
+struct Cycle {
+    word: Vec<usize>,
+    pulls: Vec<(usize, usize)>,
+}
+
+/*
+ * Searches a commutator [A, B] that 3-cycles pieces of the orbit and fixes every other sticker,
+ * where A is a single move and B a single move, possibly conjugated by another one.
+ */
+fn find_cycle(
+    moves: &[Move],
+    supports: &[Vec<usize>],
+    inverses: &[usize],
+    pieces: &Pieces,
+    orbit: &[usize],
+    relevant: &[usize],
+) -> Option<Cycle> {
+    let mut in_orbit = vec![false; pieces.piece_of.len()];
+    for piece in orbit {
+        for x in &pieces.stickers[*piece] {
+            in_orbit[*x] = true;
+        }
+    }
+    let num_stickers = 3 * pieces.stickers[orbit[0]].len();
+    let conjugators = iter::once(None).chain(relevant.iter().map(|m| Some(*m)));
+    for f in conjugators {
+        for a in relevant {
+            for y in relevant {
+                let b = match f {
+                    Some(f) => vec![f, *y, inverses[f]],
+                    None => vec![*y],
+                };
+                let mut candidates = supports[*a].clone();
+                candidates.extend(supports[*y].iter().map(|x| match f {
+                    Some(f) => moves[f].permutation.p[*x] - 1,
+                    None => *x,
+                }));
+                candidates.sort_unstable();
+                candidates.dedup();
+                if candidates.len() == supports[*a].len() + supports[*y].len() {
+                    // A and B change disjoint stickers and commute
+                    continue;
+                }
+                let mut word = vec![*a];
+                word.extend(&b);
+                word.push(inverses[*a]);
+                word.extend(inverse_word(inverses, &b));
+                let mut pulls = Vec::new();
+                let mut is_pure = true;
+                for x in candidates {
+                    let from = pull(moves, &word, x);
+                    if from != x {
+                        if !in_orbit[x] || pulls.len() == num_stickers {
+                            is_pure = false;
+                            break;
+                        }
+                        pulls.push((x, from));
+                    }
+                }
+                if is_pure
+                    && pulls.len() == num_stickers
+                    && pulls
+                        .iter()
+                        .all(|(x, from)| pieces.piece_of[*x] != pieces.piece_of[*from])
+                {
+                    return Some(Cycle { word, pulls });
+                }
+            }
+        }
+    }
+    None
+}
+
+/*
+ * Conjugates C X C⁻¹ of the cycle and its inverse with all setups C of at most depth moves that
+ * move its stickers differently, shortest first. None if the deadline expires.
+ */
+fn conjugates(
+    moves: &[Move],
+    inverses: &[usize],
+    relevant: &[usize],
+    cycle: &Cycle,
+    depth: usize,
+    deadline: &Deadline,
+) -> Option<Vec<Cycle>> {
+    let domain = cycle.pulls.iter().map(|(x, _)| *x).collect::<Vec<usize>>();
+    let position: HashMap<usize, usize> = domain.iter().enumerate().map(|(i, x)| (*x, i)).collect();
+    let inverse = Cycle {
+        word: inverse_word(inverses, &cycle.word),
+        pulls: cycle.pulls.iter().map(|(x, from)| (*from, *x)).collect(),
+    };
+    // Setups with the stickers they bring the domain of the cycle to
+    let mut seen: HashSet<Vec<usize>> = HashSet::from([domain.clone()]);
+    let mut level: Vec<(Vec<usize>, Vec<usize>)> = vec![(Vec::new(), domain)];
+    let mut setups = level.clone();
+    for _ in 0..depth {
+        if deadline.is_expired() {
+            return None;
+        }
+        let mut next = Vec::new();
+        for (word, images) in &level {
+            for m in relevant {
+                let images = images
+                    .iter()
+                    .map(|x| moves[*m].permutation.p[*x] - 1)
+                    .collect::<Vec<usize>>();
+                if seen.insert(images.clone()) {
+                    let mut setup = vec![*m];
+                    setup.extend(word);
+                    next.push((setup, images));
+                }
+            }
+        }
+        setups.extend(next.iter().cloned());
+        level = next;
+    }
+    let mut cycles = Vec::with_capacity(2 * setups.len());
+    for (setup, images) in &setups {
+        for base in [cycle, &inverse] {
+            let mut word = setup.clone();
+            word.extend(&base.word);
+            word.extend(inverse_word(inverses, setup));
+            let pulls = base
+                .pulls
+                .iter()
+                .map(|(x, from)| (images[position[x]], images[position[from]]))
+                .collect();
+            cycles.push(Cycle { word, pulls });
+        }
+    }
+    Some(cycles)
+}
+
+/*
+ * Applies a cycle to a state, only changing the stickers it moves.
+ */
+fn apply_cycle(state: &mut [usize], cycle: &Cycle) {
+    let pulled = cycle
+        .pulls
+        .iter()
+        .map(|(_, from)| state[*from])
+        .collect::<Vec<usize>>();
+    for ((x, _), color) in cycle.pulls.iter().zip(pulled) {
+        state[*x] = color;
+    }
+}
+
+/*
+ * Cycles of an orbit, indexed by the stickers they move colors from.
+ */
+struct CycleIndex<'a> {
+    cycles: &'a [Cycle],
+    // Cycles with the sticker they move each sticker to
+    from: HashMap<usize, Vec<(usize, usize)>>,
+}
+
+impl<'a> CycleIndex<'a> {
+    fn new(cycles: &'a [Cycle]) -> CycleIndex<'a> {
+        let mut from: HashMap<usize, Vec<(usize, usize)>> = HashMap::new();
+        for (i, cycle) in cycles.iter().enumerate() {
+            for (x, source) in &cycle.pulls {
+                from.entry(*source).or_default().push((i, *x));
+            }
+        }
+        CycleIndex { cycles, from }
+    }
+}
+
+/*
+ * Shortest chain of cycles that moves a piece with the colors of the target into place and keeps
+ * the done pieces. The cycles before the last one only move pieces that are not done. None if
+ * there is no such chain or the deadline expires.
+ */
+fn find_chain(
+    state: &[usize],
+    goal: &[usize],
+    pieces: &Pieces,
+    done: &[bool],
+    target: usize,
+    index: &CycleIndex,
+    deadline: &Deadline,
+) -> Option<Vec<usize>> {
+    let (cycles, cycles_from) = (index.cycles, &index.from);
+    let first = pieces.stickers[target][0];
+    let mut colors = pieces.stickers[target]
+        .iter()
+        .map(|x| goal[*x])
+        .collect::<Vec<usize>>();
+    colors.sort_unstable();
+    let is_source = |x: usize| {
+        let piece = pieces.piece_of[x];
+        let mut piece_colors = pieces.stickers[piece]
+            .iter()
+            .map(|y| state[*y])
+            .collect::<Vec<usize>>();
+        piece_colors.sort_unstable();
+        state[x] == goal[first] && !done[piece] && piece_colors == colors
+    };
+    // Stickers the color of a source sticker is moved to, with the sticker before and the cycle
+    let mut parents: HashMap<(usize, usize), Option<(usize, usize)>> = HashMap::new();
+    let mut level = Vec::new();
+    for x in cycles_from.keys() {
+        if is_source(*x) {
+            parents.insert((*x, *x), None);
+            level.push((*x, *x));
+        }
+    }
+    let chain_to = |parents: &HashMap<(usize, usize), Option<(usize, usize)>>,
+                    (mut x, source): (usize, usize)| {
+        let mut chain = Vec::new();
+        while let Some(Some((from, i))) = parents.get(&(x, source)) {
+            chain.push(*i);
+            x = *from;
+        }
+        chain.reverse();
+        chain
+    };
+    for _ in 0..MAX_CHAIN_LENGTH {
+        if deadline.is_expired() {
+            return None;
+        }
+        let mut next = Vec::new();
+        for (from, source) in level {
+            for (i, x) in cycles_from.get(&from).into_iter().flatten() {
+                let cycle = &cycles[*i];
+                if *x == first {
+                    let mut chain = chain_to(&parents, (from, source));
+                    chain.push(*i);
+                    let mut state = state.to_vec();
+                    for i in &chain {
+                        apply_cycle(&mut state, &cycles[*i]);
+                    }
+                    let is_kept = chain.iter().all(|i| {
+                        cycles[*i].pulls.iter().all(|(x, _)| {
+                            let piece = pieces.piece_of[*x];
+                            !(done[piece] || piece == target) || state[*x] == goal[*x]
+                        })
+                    });
+                    if is_kept {
+                        return Some(chain);
+                    }
+                } else if !parents.contains_key(&(*x, source))
+                    && cycle.pulls.iter().all(|(y, _)| !done[pieces.piece_of[*y]])
+                {
+                    parents.insert((*x, source), Some((from, *i)));
+                    next.push((*x, source));
+                }
+            }
+        }
+        level = next;
+    }
+    None
+}
+
+/*
+ * Solves the pieces of an orbit one after another, each with the shortest chain of cycles that
+ * puts it in place and keeps the pieces before it. None if a piece has no such chain, e.g. because
+ * the last two pieces are swapped, or if the deadline expires.
+ */
+fn solve_orbit(
+    state: &mut [usize],
+    goal: &[usize],
+    pieces: &Pieces,
+    orbit: &[usize],
+    cycles: &[Cycle],
+    deadline: &Deadline,
+) -> Option<Vec<usize>> {
+    let index = CycleIndex::new(cycles);
+    let mut done = vec![false; pieces.stickers.len()];
+    let mut word = Vec::new();
+    for target in orbit {
+        let is_solved = pieces.stickers[*target]
+            .iter()
+            .all(|x| state[*x] == goal[*x]);
+        if !is_solved {
+            let chain = find_chain(state, goal, pieces, &done, *target, &index, deadline)?;
+            for i in chain {
+                apply_cycle(state, &cycles[i]);
+                word.extend(&cycles[i].word);
+            }
+        }
+        done[*target] = true;
+    }
+    Some(word)
+}
+
+/*
+ * Stickers of every piece of the orbit, ordered like the ones of its first piece, i.e. the order
+ * in which the moves carry the stickers of one piece to another. None if a piece is not reached.
+ */
+fn piece_frames(
+    moves: &[Move],
+    pieces: &Pieces,
+    orbit: &[usize],
+    relevant: &[usize],
+) -> Option<HashMap<usize, Vec<usize>>> {
+    let mut frames: HashMap<usize, Vec<usize>> = HashMap::new();
+    frames.insert(orbit[0], pieces.stickers[orbit[0]].clone());
+    let mut queue = vec![orbit[0]];
+    while let Some(piece) = queue.pop() {
+        for m in relevant {
+            let frame = frames[&piece]
+                .iter()
+                .map(|x| moves[*m].permutation.p[*x] - 1)
+                .collect::<Vec<usize>>();
+            let image = pieces.piece_of[frame[0]];
+            if let Entry::Vacant(entry) = frames.entry(image) {
+                entry.insert(frame);
+                queue.push(image);
+            }
+        }
+    }
+    match orbit.iter().all(|piece| frames.contains_key(piece)) {
+        true => Some(frames),
+        false => None,
+    }
+}
+
+/*
+ * Solves a system of linear equations over GF(2) by Gaussian elimination. Every row holds the
+ * coefficients of the unknowns followed by the right hand side. Returns the unknowns that are 1
+ * in a solution, None if there is none.
+ */
+fn solve_gf2(mut rows: Vec<Vec<bool>>, unknowns: usize) -> Option<Vec<usize>> {
+    let mut pivots = Vec::new();
+    for column in 0..unknowns {
+        let r = pivots.len();
+        let Some(p) = (r..rows.len()).find(|i| rows[*i][column]) else {
+            continue;
+        };
+        rows.swap(r, p);
+        let pivot = rows[r].clone();
+        for (i, row) in rows.iter_mut().enumerate() {
+            if i != r && row[column] {
+                for (x, y) in row.iter_mut().zip(&pivot) {
+                    *x ^= *y;
+                }
+            }
+        }
+        pivots.push(column);
+    }
+    if rows[pivots.len()..].iter().any(|row| row[unknowns]) {
+        return None;
+    }
+    Some(
+        pivots
+            .iter()
+            .enumerate()
+            .filter(|(r, _)| rows[*r][unknowns])
+            .map(|(_, column)| *column)
+            .collect(),
+    )
+}
+
+/*
+ * Orbit of centers or wings solved by one stage of the reduction.
+ */
+struct OrbitStage {
+    orbit: Vec<usize>,
+    // Moves that change stickers of the orbit
+    relevant: Vec<usize>,
+    cycle: Cycle,
+    // Stickers of every piece in a common order
+    frames: HashMap<usize, Vec<usize>>,
+}
+
+impl OrbitStage {
+    fn is_wings(&self) -> bool {
+        self.frames[&self.orbit[0]].len() == 2
+    }
+
+    /*
+     * Whether the pieces of the orbit can only be brought to the target by an odd permutation,
+     * which the 3-cycles cannot do. None if the parity does not matter, as two pieces have the
+     * same colors and swapping them fixes the parity, or if the orbit cannot be solved at all.
+     */
+    fn parity(&self, state: &[usize], target: &[usize]) -> Option<bool> {
+        let colors = |piece: &usize, state: &[usize]| {
+            self.frames[piece]
+                .iter()
+                .map(|x| state[*x])
+                .collect::<Vec<usize>>()
+        };
+        let mut slot_of: HashMap<Vec<usize>, usize> = HashMap::new();
+        for (i, piece) in self.orbit.iter().enumerate() {
+            if slot_of.insert(colors(piece, target), i).is_some() {
+                return None;
+            }
+        }
+        let targets = self
+            .orbit
+            .iter()
+            .map(|piece| slot_of.get(&colors(piece, state)).copied())
+            .collect::<Option<Vec<usize>>>()?;
+        Some(is_odd(&targets))
+    }
+}
+
+/*
+ * Reduction method of one cube size: the centers of every face are solved, the wings are paired
+ * with each other and the middle edge (if N is odd) to composite edges, and the reduced cube is
+ * solved as a 3x3x3 cube by the face turns and inner slice turns of VirtualCube.
+ *
+ * The 3x3x3 solution of the hand-off only depends on the corners, the middle edges and the middle
+ * centers, which none of the earlier stages moves, so it is planned first. The stages then bring
+ * the centers and wings to the state the hand-off solves, which pairs the edges in the places
+ * the 3x3x3 solution expects them and keeps the orientation of the centers right for any coloring.
+ * The stages are:
+ * - OLL parity: orbits whose pieces need an odd permutation get inner slice turns first, chosen
+ *   by solving the parities over GF(2). Slice turns also move centers, so this comes first.
+ * - Centers: every orbit of centers with conjugated 3-cycles, which fix all other stickers.
+ * - Edge pairing: every orbit of wings with conjugated 3-cycles.
+ * - Hand-off: the 3x3x3 solution. The PLL parity of an even cube, i.e. a corner permutation the
+ *   paired edges cannot follow, is taken by KociembaTables::solve_corners, which swaps two middle
+ *   edges the cube does not have, and the wings are paired for the edges after that swap.
+ * Every stage is replayed on the state before it and has to keep the stickers solved so far.
+ */
+pub struct ReductionSolver {
+    virtual_cube: VirtualCube,
+    pieces: Pieces,
+    inverses: Vec<usize>,
+    // Orbits of centers first, then orbits of wings
+    stages: Vec<OrbitStage>,
+    // Inner slice turns that keep the reduced cube, with the stages whose orbit they permute oddly
+    parity_moves: Vec<(usize, Vec<bool>)>,
+}
+
+impl ReductionSolver {
+    pub fn new(moves: &[Move], size: usize) -> Result<ReductionSolver, Box<dyn Error>> {
+        let virtual_cube = VirtualCube::new(moves, size)?;
+        let inverses = puzzle::move_inverses(moves)
+            .into_iter()
+            .collect::<Option<Vec<usize>>>()
+            .ok_or("Cube moves are not closed under inverses")?;
+        let supports = moves.iter().map(support).collect::<Vec<Vec<usize>>>();
+        let pieces = Pieces::new(moves, &supports);
+
+        let mut orbits = pieces
+            .orbits
+            .iter()
+            .filter(|orbit| orbit.len() == ORBIT_SIZE)
+            .collect::<Vec<&Vec<usize>>>();
+        orbits.sort_by_key(|orbit| pieces.stickers[orbit[0]].len());
+        let stages = orbits
+            .par_iter()
+            .map(|orbit| {
+                let stickers = orbit
+                    .iter()
+                    .flat_map(|piece| pieces.stickers[*piece].iter().copied())
+                    .collect::<HashSet<usize>>();
+                let relevant = (0..moves.len())
+                    .filter(|m| supports[*m].iter().any(|x| stickers.contains(x)))
+                    .collect::<Vec<usize>>();
+                let cycle = find_cycle(moves, &supports, &inverses, &pieces, orbit, &relevant)
+                    .ok_or("No 3-cycle found for an orbit")?;
+                let frames = piece_frames(moves, &pieces, orbit, &relevant)
+                    .ok_or("Moves do not connect an orbit")?;
+                Ok(OrbitStage {
+                    orbit: (*orbit).clone(),
+                    relevant,
+                    cycle,
+                    frames,
+                })
+            })
+            .collect::<Result<Vec<OrbitStage>, String>>()?;
+
+        // Slice turns of single inner layers that leave the reduced cube alone
+        let tracked = virtual_cube
+            .tracked_stickers()
+            .into_iter()
+            .collect::<HashSet<usize>>();
+        let face_size = size * size;
+        let is_face_turn = |support: &Vec<usize>| {
+            let mut moved = [0; NUM_FACES];
+            for x in support {
+                moved[x / face_size] += 1;
+            }
+            moved.iter().any(|c| *c + 1 >= face_size)
+        };
+        let parity_moves = (0..moves.len())
+            .filter(|m| {
+                !moves[*m].name.starts_with('-')
+                    && !is_face_turn(&supports[*m])
+                    && supports[*m].iter().all(|x| !tracked.contains(x))
+            })
+            .map(|m| {
+                let effects = stages
+                    .iter()
+                    .map(|stage| pieces.is_odd_move(&moves[m], &stage.orbit))
+                    .collect();
+                (m, effects)
+            })
+            .collect();
+        Ok(ReductionSolver {
+            virtual_cube,
+            pieces,
+            inverses,
+            stages,
+            parity_moves,
+        })
+    }
+
+    fn is_solved(target: &[usize], state: &[usize], stickers: &[usize]) -> bool {
+        stickers.iter().all(|x| state[*x] == target[*x])
+    }
+
+    /*
+     * Slice turns after which every orbit whose parity matters has an even permutation to the
+     * target. None if no set of slice turns fixes all of them.
+     */
+    fn parity_word(&self, state: &[usize], target: &[usize]) -> Option<Vec<usize>> {
+        let rows = self
+            .stages
+            .iter()
+            .enumerate()
+            .filter_map(|(i, stage)| {
+                let is_odd = stage.parity(state, target)?;
+                let mut row = self
+                    .parity_moves
+                    .iter()
+                    .map(|(_, effects)| effects[i])
+                    .collect::<Vec<bool>>();
+                row.push(is_odd);
+                Some(row)
+            })
+            .collect::<Vec<Vec<bool>>>();
+        let chosen = solve_gf2(rows, self.parity_moves.len())?;
+        Some(chosen.iter().map(|i| self.parity_moves[*i].0).collect())
+    }
+
+    /*
+     * Solves a cube stage by stage, see ReductionSolver. None if a stage fails or the deadline
+     * expires.
+     */
+    pub fn solve(
+        &self,
+        puzzle: &Puzzle,
+        tables: &KociembaTables,
+        config: &ReductionConfig,
+        kociemba: &KociembaConfig,
+        deadline: &Deadline,
+    ) -> Option<Vec<usize>> {
+        let moves = &puzzle.moves;
+        let reduced = self.virtual_cube.puzzle(puzzle, &puzzle.initial_state);
+        let virtual_word = match self.virtual_cube.size % 2 {
+            1 => tables.solve(&reduced, kociemba, deadline)?,
+            _ => tables.solve_corners(&reduced, kociemba, deadline)?,
+        };
+        let hand_off = virtual_word
+            .iter()
+            .flat_map(|m| self.virtual_cube.words[*m].iter().copied())
+            .collect::<Vec<usize>>();
+        // The state the hand-off solves, i.e. the reduced cube with its centers and edges
+        let target = apply_word(
+            moves,
+            &inverse_word(&self.inverses, &hand_off),
+            &puzzle.goal_state,
+        );
+        let tracked = self.virtual_cube.tracked_stickers();
+
+        let Some(mut word) = self.parity_word(&puzzle.initial_state, &target) else {
+            debug!("No slice turns fix the parity of puzzle {}", puzzle.id);
+            return None;
+        };
+        let mut state = apply_word(moves, &word, &puzzle.initial_state);
+        if tracked
+            .iter()
+            .any(|x| state[*x] != puzzle.initial_state[*x])
+        {
+            warn!(
+                "Parity slice turns of puzzle {} move the reduced cube",
+                puzzle.id
+            );
+            return None;
+        }
+        debug!(
+            "Fixed the parity of puzzle {} with {} slice turns",
+            puzzle.id,
+            word.len()
+        );
+
+        let mut solved = Vec::new();
+        for stage in &self.stages {
+            let cycles = conjugates(
+                moves,
+                &self.inverses,
+                &stage.relevant,
+                &stage.cycle,
+                config.setup_depth,
+                deadline,
+            )?;
+            let mut stage_state = state.clone();
+            let stage_word = solve_orbit(
+                &mut stage_state,
+                &target,
+                &self.pieces,
+                &stage.orbit,
+                &cycles,
+                deadline,
+            );
+            if deadline.is_expired() {
+                return None;
+            }
+            let Some(stage_word) = stage_word else {
+                debug!(
+                    "Could not solve an orbit of {} of puzzle {}",
+                    if stage.is_wings() { "wings" } else { "centers" },
+                    puzzle.id
+                );
+                return None;
+            };
+            let replayed = apply_word(moves, &stage_word, &state);
+            solved.extend(
+                stage
+                    .orbit
+                    .iter()
+                    .flat_map(|piece| self.pieces.stickers[*piece].iter().copied()),
+            );
+            if replayed != stage_state || !ReductionSolver::is_solved(&target, &replayed, &solved) {
+                warn!(
+                    "Replaying a stage of puzzle {} does not solve it",
+                    puzzle.id
+                );
+                return None;
+            }
+            word.extend(stage_word);
+            state = replayed;
+        }
+        debug!(
+            "Reduced puzzle {} to a 3x3x3 cube with {} moves",
+            puzzle.id,
+            word.len()
+        );
+
+        state = apply_word(moves, &hand_off, &state);
+        word.extend(hand_off);
+        if state != puzzle.goal_state {
+            warn!("Hand-off of puzzle {} leaves stickers unsolved", puzzle.id);
+            return None;
+        }
+        Some(word)
+    }
+}
+
+/*
+ * Solves the cubes of size 4 and larger by reduction. The reduced cubes are solved with the
+ * Kociemba tables of the 3x3x3 cube, which all cube sizes share.
+ */
+pub fn solve_puzzles(
+    puzzles: &[Puzzle],
+    config: &Config,
+    options: &SolveOptions,
+) -> HashMap<usize, String> {
+    let cubes = puzzles
+        .iter()
+        .filter(|p| matches!(p.puzzle_type, PuzzleType::CUBE(size) if size > VIRTUAL_SIZE))
+        .cloned()
+        .collect::<Vec<Puzzle>>();
+    let mut representatives: HashMap<PuzzleType, &Puzzle> = HashMap::new();
+    for puzzle in &cubes {
+        representatives
+            .entry(puzzle.puzzle_type.clone())
+            .or_insert(puzzle);
+    }
+    let solvers: HashMap<PuzzleType, ReductionSolver> = representatives
+        .into_iter()
+        .filter_map(|(puzzle_type, puzzle)| {
+            let PuzzleType::CUBE(size) = puzzle_type else {
+                return None;
+            };
+            match ReductionSolver::new(&puzzle.moves, size) {
+                Ok(solver) => Some((puzzle_type, solver)),
+                Err(e) => {
+                    warn!(
+                        "No reduction solver for puzzle type {:?}: {}",
+                        puzzle_type, e
+                    );
+                    None
+                }
+            }
+        })
+        .collect();
+    let Some(solver) = solvers.values().next() else {
+        return HashMap::new();
+    };
+    let tables_path = config.paths.resolve(&config.paths.kociemba_tables);
+    let tables = match KociembaTables::load_or_build(
+        &tables_path,
+        &PuzzleType::CUBE(VIRTUAL_SIZE),
+        &solver.virtual_cube.moves,
+    ) {
+        Ok(tables) => tables,
+        Err(e) => {
+            warn!("No Kociemba tables for the reduced cubes: {}", e);
+            return HashMap::new();
+        }
+    };
+    parallel::solve_in_parallel(&cubes, options, |puzzle, deadline| {
+        let solver = solvers.get(&puzzle.puzzle_type)?;
+        let word = solver.solve(
+            puzzle,
+            &tables,
+            &config.reduction,
+            &config.kociemba,
+            deadline,
+        )?;
+        parallel::log_solved(puzzle, word.len());
+        Some(puzzle::word_to_string(&puzzle.moves, &word))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::puzzle;
+    use crate::validation::validate_solution;
+    use std::time::Duration;
+
+    fn get_puzzles() -> (HashMap<PuzzleType, Vec<Move>>, Vec<Puzzle>) {
+        let puzzle_info = puzzle::load_puzzle_info("./../../data/puzzle_info.csv").unwrap();
+        let puzzles = puzzle::load_puzzles("./../../data/puzzles.csv", &puzzle_info).unwrap();
+        (puzzle_info, puzzles)
+    }
+
+    fn assert_same_moves(a: &[Move], b: &[Move]) {
+        assert_eq!(a.len(), b.len());
+        for (x, y) in a.iter().zip(b.iter()) {
+            assert_eq!((&x.name, &x.permutation), (&y.name, &y.permutation));
+        }
+    }
+
+    #[test]
+    fn test_virtual_cube_moves() {
+        let (puzzle_info, _) = get_puzzles();
+        let cube_moves = &puzzle_info[&PuzzleType::CUBE(3)];
+        // The reduced 3x3x3 cube is the cube itself
+        let virtual_cube = VirtualCube::new(cube_moves, 3).unwrap();
+        assert_same_moves(&virtual_cube.moves, cube_moves);
+        for size in [4, 5] {
+            let virtual_cube =
+                VirtualCube::new(&puzzle_info[&PuzzleType::CUBE(size)], size).unwrap();
+            assert_same_moves(&virtual_cube.moves, cube_moves);
+        }
+    }
+
+    #[test]
+    fn test_cycles_are_pure() {
+        let (puzzle_info, _) = get_puzzles();
+        let moves = &puzzle_info[&PuzzleType::CUBE(5)];
+        let solver = ReductionSolver::new(moves, 5).unwrap();
+        // Two orbits of centers, then one of wings
+        let kinds = solver
+            .stages
+            .iter()
+            .map(|stage| stage.is_wings())
+            .collect::<Vec<bool>>();
+        assert_eq!(kinds, vec![false, false, true]);
+        let labels = (0..moves[0].permutation.p.len()).collect::<Vec<usize>>();
+        for stage in &solver.stages {
+            let cycles = conjugates(
+                moves,
+                &solver.inverses,
+                &stage.relevant,
+                &stage.cycle,
+                1,
+                &Deadline::unlimited(),
+            )
+            .unwrap();
+            for cycle in cycles {
+                let state = apply_word(moves, &cycle.word, &labels);
+                let changed = labels
+                    .iter()
+                    .filter(|x| state[**x] != **x)
+                    .map(|x| (*x, state[*x]))
+                    .collect::<HashSet<(usize, usize)>>();
+                assert_eq!(changed, cycle.pulls.iter().copied().collect());
+            }
+        }
+        let stage = &solver.stages[0];
+        let expired = Deadline::after(Some(Duration::ZERO));
+        assert!(conjugates(
+            moves,
+            &solver.inverses,
+            &stage.relevant,
+            &stage.cycle,
+            1,
+            &expired
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_oll_parity() {
+        let (_, puzzles) = get_puzzles();
+        let puzzle = puzzles
+            .iter()
+            .find(|p| p.puzzle_type == PuzzleType::CUBE(4) && p.goal_string.starts_with("A;A"))
+            .unwrap();
+        let solver = ReductionSolver::new(&puzzle.moves, 4).unwrap();
+        let wings = solver.stages.iter().position(|s| s.is_wings()).unwrap();
+        let stage = &solver.stages[wings];
+        let goal = &puzzle.goal_state;
+        assert_eq!(stage.parity(goal, goal), Some(false));
+        // Centers of one color are interchangeable
+        assert_eq!(solver.stages[0].parity(goal, goal), None);
+        // A slice turn 4-cycles the wings, a face turn moves them in two 4-cycles
+        let (slice, effects) = &solver.parity_moves[0];
+        assert!(effects[wings]);
+        let state = puzzle.moves[*slice].permutation.apply(goal);
+        assert_eq!(stage.parity(&state, goal), Some(true));
+        assert_eq!(solver.parity_word(&state, goal).unwrap().len(), 1);
+        let face = stage
+            .relevant
+            .iter()
+            .find(|m| support(&puzzle.moves[**m]).len() > support(&puzzle.moves[*slice]).len())
+            .unwrap();
+        let state = puzzle.moves[*face].permutation.apply(goal);
+        assert_eq!(stage.parity(&state, goal), Some(false));
+    }
+
+    #[test]
+    fn test_solve_gf2() {
+        // x0 + x1 = 1, x1 = 1, x0 + x2 = 0
+        let rows = vec![
+            vec![true, true, false, true],
+            vec![false, true, false, true],
+            vec![true, false, true, false],
+        ];
+        assert_eq!(solve_gf2(rows, 3), Some(vec![1]));
+        // x0 = 1 and x0 = 0
+        let rows = vec![vec![true, true], vec![true, false]];
+        assert_eq!(solve_gf2(rows, 1), None);
+    }
+
+    #[test]
+    fn test_reduction_solves() {
+        let (puzzle_info, puzzles) = get_puzzles();
+        let tables = KociembaTables::build(&puzzle_info[&PuzzleType::CUBE(3)]).unwrap();
+        let kociemba = KociembaConfig {
+            probes: 1,
+            ..KociembaConfig::default()
+        };
+        for (size, goal_prefix) in [(4, "A;A"), (5, "A;A"), (4, "N0;N1")] {
+            let puzzle = puzzles
+                .iter()
+                .find(|p| {
+                    p.puzzle_type == PuzzleType::CUBE(size)
+                        && p.goal_string.starts_with(goal_prefix)
+                        && p.num_wildcards == 0
+                })
+                .unwrap();
+            let solver = ReductionSolver::new(&puzzle.moves, size).unwrap();
+            let word = solver
+                .solve(
+                    puzzle,
+                    &tables,
+                    &ReductionConfig::default(),
+                    &kociemba,
+                    &Deadline::unlimited(),
+                )
+                .unwrap();
+            let names = puzzle::word_to_string(&puzzle.moves, &word);
+            assert_eq!(validate_solution(puzzle, &names), Ok(word.len()));
+        }
+    }
+}
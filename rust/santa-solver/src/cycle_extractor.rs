@@ -1,60 +1,29 @@
+use crate::commutators::CommutatorSearch;
+use crate::config::Config;
+use crate::kalka_teicher_tsaban as kalka;
+use crate::permutation::{Permutation, PermutationPath};
+use crate::puzzle::{Move, PuzzleFilter, PuzzleType, PuzzleTypeWrapper};
+use crate::testing_utils::TestingUtils;
 use log::{debug, info, warn};
-use santa_solver_lib::kalka_teicher_tsaban as kalka;
-use santa_solver_lib::puzzle;
-use santa_solver_lib::puzzle::PuzzleType;
-use santa_solver_lib::puzzle::PuzzleTypeWrapper;
-use santa_solver_lib::testing_utils::TestingUtils;
 use std::collections::HashMap;
 
-fn main() {
-    env_logger::init();
-
-    let args: Vec<String> = std::env::args().collect();
-    let puzzle_info_path = if args.len() > 1 {
-        &args[1]
-    } else {
-        "./../../data/puzzle_info.csv"
-    };
-    let cycles_path = if args.len() > 2 {
-        &args[3]
-    } else {
-        "./../../data/cycles" // The directory where the generated permutations should be stored
-    };
-
-    // Load the puzzles
-    debug!("Loading puzzle data...");
-    let puzzles_info = puzzle::load_puzzle_info(puzzle_info_path).unwrap();
-
+/*
+ * Searches c-cycles (c = 2, ..., 10) for every puzzle type selected by the filter and writes them
+ * to <cycles>/<puzzle_type>_mus.csv. The c-cycles that are not among the short commutators are
+ * searched with kalka_teicher_tsaban::find_c_cycles.
+ */
+pub fn extract_cycles(
+    puzzles_info: &HashMap<PuzzleType, Vec<Move>>,
+    filter: &PuzzleFilter,
+    config: &Config,
+) {
+    let cycles_path = config.paths.resolve(&config.paths.cycles);
     // Generate transpositions for each puzzle type
     for (puzzle_type, moves) in puzzles_info.iter() {
-        match puzzle_type {
-            PuzzleType::GLOBE(_, _) => {
-                // Skip the globe for now
-                continue;
-            }
-            PuzzleType::WREATH(_) => {
-                // Skip the wreath for now
-                continue;
-            }
-            _ => {}
+        if !filter.matches_type(puzzle_type) {
+            continue;
         }
-        let max_depth = match puzzle_type {
-            PuzzleType::CUBE(_) => 100000,
-            PuzzleType::GLOBE(n, m) => {
-                if *n < 25 && *m < 25 {
-                    1000000
-                } else {
-                    500000
-                }
-            }
-            PuzzleType::WREATH(n) => {
-                if *n < 30 {
-                    4000000
-                } else {
-                    1000000
-                }
-            }
-        };
+        let max_depth = config.search.extract_limit(puzzle_type);
         let mut index_to_gen_name = Vec::new();
         let mut gen_perm_to_index = HashMap::new();
         let mut index_to_gen_perm = Vec::new(); // todo: remove after testin
@@ -75,16 +44,41 @@ fn main() {
         let n = moves[0].permutation.len();
         debug!("Generating c-cycles for puzzle type {:?}", puzzle_type);
         let c_cycles = vec![2_usize, 3, 4, 5, 6, 7, 8, 9, 10];
-        let mus = kalka::find_c_cycles(
-            &puzzle_type_wrapper.gen_perm_to_index,
-            &c_cycles,
-            n,
-            max_depth,
+        // Short commutators give the odd c-cycles without searching the group
+        let mut commutators = CommutatorSearch::new(
+            moves,
+            *c_cycles.iter().max().unwrap(),
+            config.search.commutator_limit,
         );
-        if mus.is_none() {
+        commutators.add_commutators(config.search.commutator_length);
+        let mut mus: HashMap<usize, (PermutationPath, Permutation)> = HashMap::new();
+        for c in &c_cycles {
+            if let Some(entry) = commutators.with_cycles(&vec![*c]).first() {
+                let path = entry
+                    .word
+                    .iter()
+                    .map(|i| puzzle_type_wrapper.gen_perm_to_index[&moves[*i].permutation])
+                    .collect();
+                mus.insert(*c, (PermutationPath::new(path), entry.permutation.clone()));
+            }
+        }
+        let missing = c_cycles
+            .iter()
+            .filter(|c| !mus.contains_key(c))
+            .copied()
+            .collect::<Vec<usize>>();
+        if !missing.is_empty() {
+            let found = kalka::find_c_cycles(
+                &puzzle_type_wrapper.gen_perm_to_index,
+                &missing,
+                n,
+                max_depth,
+            );
+            mus.extend(found.into_iter().flatten());
+        }
+        if mus.is_empty() {
             warn!("Failed to find c-cycles for puzzle type {:?}", puzzle_type);
         } else {
-            let mus = mus.unwrap();
             // Print the orders found
             let mut mu_orders = mus.keys().collect::<Vec<_>>();
             mu_orders.sort();
@@ -186,7 +180,8 @@ fn main() {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use santa_solver_lib::{permutation::Permutation, testing_utils::TestingUtils};
+    use crate::permutation::Permutation;
+    use crate::puzzle;
 
     #[test]
     fn test_cycle_manually() {
@@ -0,0 +1,488 @@
+use crate::commutators::CommutatorSearch;
+use crate::config::SearchConfig;
+use crate::groups::PermutationGroupIterator;
+use crate::kalka_teicher_tsaban::{to_2_cycle, to_3_cycle};
+use crate::parallel::Deadline;
+use crate::permutation::{
+    find_parity_word, get_orbits, orbit_parities, Permutation, PermutationIndex,
+};
+use crate::puzzle::{Move, PuzzleType};
+use crate::versioned_file;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+const CYCLE_LIBRARY_MAGIC: [u8; 4] = *b"SCCL";
+// Increase whenever the layout of CycleLibrary or the meaning of its words changes
+pub const CYCLE_LIBRARY_VERSION: u32 = 1;
+
+/*
+ * Library of 2-cycles and 3-cycles of a puzzle type with short words in its moves. For every orbit
+ * of the group, the shortest c-cycle found by a breadth first search over the group elements is
+ * closed under conjugation with the moves, again breadth first. A cycle is stored with 0-indexed
+ * points, rotated such that the smallest point comes first. Its word contains indices into
+ * move_names in the order the moves are composed, i.e. the permutation of the word
+ * w_1, ..., w_k is w_k * ... * w_1 (see PermutationPath).
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CycleLibrary {
+    pub puzzle_type: String,
+    pub move_names: Vec<String>,
+    pub orbit_of: Vec<usize>,
+    // Orbits without 2-cycles in the library, on which the parity has to be fixed with moves
+    pub parity_orbits: Vec<usize>,
+    pub cycles: HashMap<Vec<usize>, Vec<u16>>,
+}
+
+pub(crate) fn canonical_cycle(mut cycle: Vec<usize>) -> Vec<usize> {
+    let min_pos = (0..cycle.len()).min_by_key(|i| cycle[*i]).unwrap();
+    cycle.rotate_left(min_pos);
+    cycle
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+impl CycleLibrary {
+    pub fn build(puzzle_type: &PuzzleType, moves: &[Move], limits: &SearchConfig) -> CycleLibrary {
+        let n = moves[0].permutation.len();
+        let generators = moves
+            .iter()
+            .map(|m| m.permutation.clone())
+            .collect::<Vec<Permutation>>();
+        let mut library = CycleLibrary {
+            puzzle_type: puzzle_type.to_string(),
+            move_names: moves.iter().map(|m| m.name.clone()).collect(),
+            orbit_of: get_orbits(&generators),
+            parity_orbits: Vec::new(),
+            cycles: HashMap::new(),
+        };
+        let seeds = library.find_seeds(&generators, limits);
+        info!(
+            "Found {} seed cycles for {}, closing them under conjugation...",
+            seeds.len(),
+            puzzle_type
+        );
+        for (cycle, word) in seeds {
+            library.add_conjugates(&generators, cycle, word, limits.conjugate_limit);
+        }
+        let mut odd_orbits = library
+            .cycles
+            .keys()
+            .filter(|cycle| cycle.len() == 2)
+            .map(|cycle| library.orbit_of[cycle[0]])
+            .collect::<Vec<usize>>();
+        odd_orbits.sort();
+        odd_orbits.dedup();
+        library.parity_orbits = (0..n)
+            .filter(|x| library.orbit_of[*x] == *x && odd_orbits.binary_search(x).is_err())
+            .collect();
+        info!(
+            "Cycle library for {} contains {} cycles",
+            puzzle_type,
+            library.cycles.len()
+        );
+        library
+    }
+
+    /*
+     * Searches short words tau such that a power tau^m is a single c-cycle for c in {2, 3} and
+     * returns every c-cycle found with its shortest word, shortest words first. The 3-cycles among
+     * short commutators are taken as seeds first, as they are usually much shorter than the powers
+     * found by the search. The c-cycles of an orbit are not necessarily all conjugate, so each of
+     * them may seed another conjugacy class.
+     * As c is prime, tau^m is a c-cycle iff tau has exactly one cycle of length c, no other cycle
+     * length is divisible by c and m is a multiple of all other cycle lengths.
+     */
+    fn find_seeds(
+        &self,
+        generators: &[Permutation],
+        limits: &SearchConfig,
+    ) -> Vec<(Vec<usize>, Vec<u16>)> {
+        let n = generators[0].len();
+        let mut gen_to_idx: HashMap<Permutation, PermutationIndex> = HashMap::new();
+        for (i, gen) in generators.iter().enumerate() {
+            gen_to_idx.entry(gen.clone()).or_insert(i);
+        }
+
+        let mut seeds: HashMap<Vec<usize>, Vec<u16>> = HashMap::new();
+        let moves = generators
+            .iter()
+            .enumerate()
+            .map(|(i, gen)| Move {
+                name: i.to_string(),
+                permutation: gen.clone(),
+            })
+            .collect::<Vec<Move>>();
+        let mut commutators = CommutatorSearch::new(&moves, 3, limits.commutator_limit);
+        commutators.add_commutators(limits.commutator_length);
+        for entry in commutators.with_cycles(&vec![3]) {
+            let cycle = entry
+                .permutation
+                .compute_info()
+                .cycles
+                .into_iter()
+                .find(|cycle| cycle.len() == 3)
+                .unwrap();
+            let points = canonical_cycle(cycle.iter().map(|x| x - 1).collect());
+            seeds
+                .entry(points)
+                .or_insert_with(|| entry.word.iter().map(|x| *x as u16).collect());
+        }
+        debug!("Found {} 3-cycles among the commutators", seeds.len());
+        let limit = limits.seed_search_limit.min(limits.seed_search_entries / n);
+        for (tau_path, tau) in PermutationGroupIterator::new(&gen_to_idx).take(limit) {
+            let cycles = tau.compute_info().cycles;
+            for c in [2, 3] {
+                let mut c_cycles = cycles.iter().filter(|cycle| cycle.len() == c);
+                let c_cycle = match (c_cycles.next(), c_cycles.next()) {
+                    (Some(c_cycle), None) => c_cycle,
+                    _ => continue,
+                };
+                let mut m = 1;
+                for cycle in cycles.iter().filter(|cycle| cycle.len() != c) {
+                    if cycle.len() % c == 0 {
+                        m = n + 1;
+                    }
+                    m = m / gcd(m, cycle.len()) * cycle.len();
+                    if m > n {
+                        break;
+                    }
+                }
+                if m > n {
+                    continue;
+                }
+                // tau^m maps c_cycle[j] to c_cycle[j + m]
+                let points = canonical_cycle(
+                    (0..c)
+                        .map(|j| c_cycle[(j * m) % c] - 1)
+                        .collect::<Vec<usize>>(),
+                );
+                let word_len = tau_path.arr.len() * m;
+                if seeds.get(&points).is_none_or(|w| w.len() > word_len) {
+                    let mut word = Vec::with_capacity(word_len);
+                    for _ in 0..m {
+                        word.extend(tau_path.arr.iter().map(|x| *x as u16));
+                    }
+                    debug!("Found {}-cycle {:?} of word length {}", c, points, word_len);
+                    seeds.insert(points, word);
+                }
+            }
+        }
+        let mut covered = seeds
+            .keys()
+            .map(|cycle| self.orbit_of[cycle[0]])
+            .collect::<Vec<usize>>();
+        covered.sort();
+        covered.dedup();
+        let mut orbit_sizes = vec![0; n];
+        for orbit in &self.orbit_of {
+            orbit_sizes[*orbit] += 1;
+        }
+        let orbits = orbit_sizes.iter().filter(|size| **size > 1).count();
+        if covered.len() < orbits {
+            warn!(
+                "Found c-cycles in only {} of {} orbits",
+                covered.len(),
+                orbits
+            );
+        }
+        let mut seeds = seeds.into_iter().collect::<Vec<_>>();
+        seeds.sort_by(|(a, w_a), (b, w_b)| w_a.len().cmp(&w_b.len()).then(a.cmp(b)));
+        seeds
+    }
+
+    /*
+     * Conjugating a cycle (x_1 ... x_c) with a move s gives s^-1 (x_1 ... x_c) s, which is the
+     * cycle (s^-1(x_1) ... s^-1(x_c)) with the word s, w, s^-1.
+     */
+    fn add_conjugates(
+        &mut self,
+        generators: &[Permutation],
+        cycle: Vec<usize>,
+        word: Vec<u16>,
+        conjugate_limit: usize,
+    ) {
+        let mut first_index: HashMap<&Permutation, usize> = HashMap::new();
+        for (i, gen) in generators.iter().enumerate() {
+            first_index.entry(gen).or_insert(i);
+        }
+        let inverses = generators
+            .iter()
+            .map(|gen| first_index.get(&gen.inverse()).cloned())
+            .collect::<Vec<Option<usize>>>();
+        let mut added = 0;
+        let mut queue: VecDeque<Vec<usize>> = VecDeque::new();
+        if !self.cycles.contains_key(&cycle) {
+            self.cycles.insert(cycle.clone(), word);
+            queue.push_back(cycle);
+        }
+        while let Some(cycle) = queue.pop_front() {
+            for (i, gen) in generators.iter().enumerate() {
+                let inverse = match inverses[i] {
+                    Some(inverse) if first_index[gen] == i => inverse,
+                    _ => continue,
+                };
+                let conjugate = canonical_cycle(
+                    cycle
+                        .iter()
+                        .map(|x| generators[inverse].p[*x] - 1)
+                        .collect(),
+                );
+                if self.cycles.contains_key(&conjugate) {
+                    continue;
+                }
+                let word = &self.cycles[&cycle];
+                let mut conjugate_word = Vec::with_capacity(word.len() + 2);
+                conjugate_word.push(i as u16);
+                conjugate_word.extend(word);
+                conjugate_word.push(inverse as u16);
+                self.cycles.insert(conjugate.clone(), conjugate_word);
+                queue.push_back(conjugate);
+                added += 1;
+                if added >= conjugate_limit {
+                    warn!("Stopping after {} conjugates", added);
+                    return;
+                }
+            }
+        }
+    }
+
+    /*
+     * Shortest word in the moves that has the given parities on the parity orbits.
+     */
+    fn find_parity_word(
+        &self,
+        moves: &[Move],
+        parities: &[bool],
+        deadline: &Deadline,
+    ) -> Option<Vec<usize>> {
+        let move_parities = moves
+            .iter()
+            .map(|m| orbit_parities(&m.permutation, &self.orbit_of, &self.parity_orbits))
+            .collect::<Vec<Vec<bool>>>();
+        find_parity_word(&move_parities, parities, deadline)
+    }
+
+    /*
+     * Writes the target as a product of library cycles, orbit by orbit: restrictions of odd
+     * parity as 2-cycles, even ones as 3-cycles. If moves change the parity of several orbits at
+     * once, odd orbits without 2-cycles are made even by a short prefix of moves first. Returns
+     * the word in move indices or None if a cycle is missing from the library or the deadline
+     * expired.
+     */
+    pub fn factorize(
+        &self,
+        moves: &[Move],
+        target: &Permutation,
+        deadline: &Deadline,
+    ) -> Option<Vec<usize>> {
+        self.factorize_with(moves, target, deadline, |cycle| self.word(cycle))
+    }
+
+    /*
+     * Word of a cycle of 0-indexed points in canonical rotation, if it is in the library.
+     */
+    pub fn word(&self, cycle: &[usize]) -> Option<Vec<usize>> {
+        let word = self.cycles.get(cycle)?;
+        Some(word.iter().map(|x| *x as usize).collect())
+    }
+
+    /*
+     * Same as factorize, but the word of every cycle (0-indexed, in canonical rotation) is given
+     * by cycle_word, e.g. to reach cycles that are missing from the library.
+     */
+    pub fn factorize_with<F>(
+        &self,
+        moves: &[Move],
+        target: &Permutation,
+        deadline: &Deadline,
+        mut cycle_word: F,
+    ) -> Option<Vec<usize>>
+    where
+        F: FnMut(&[usize]) -> Option<Vec<usize>>,
+    {
+        let n = target.len();
+        let mut result = Vec::new();
+        let mut target = target.clone();
+        let parities = orbit_parities(&target, &self.orbit_of, &self.parity_orbits);
+        if parities.iter().any(|odd| *odd) {
+            result = self.find_parity_word(moves, &parities, deadline)?;
+            // target = rest * prefix, so the rest is target * prefix^-1
+            let mut prefix = Permutation::identity(n);
+            for i in &result {
+                prefix = moves[*i].permutation.compose(&prefix);
+            }
+            target = target.compose(&prefix.inverse());
+        }
+        let mut orbits = (0..n)
+            .filter(|x| target.p[*x] - 1 != *x)
+            .map(|x| self.orbit_of[x])
+            .collect::<Vec<usize>>();
+        orbits.sort();
+        orbits.dedup();
+        for orbit in orbits {
+            if deadline.is_expired() {
+                return None;
+            }
+            let mut p = (1..=n).collect::<Vec<usize>>();
+            for x in (0..n).filter(|x| self.orbit_of[*x] == orbit) {
+                p[x] = target.p[x];
+            }
+            let restriction = Permutation::new(p);
+            let info = restriction.compute_info();
+            let c_group = match info.signum {
+                false => to_2_cycle(&info),
+                true => to_3_cycle(&info),
+            };
+            for cycle in c_group {
+                let key = canonical_cycle(cycle.iter().map(|x| x - 1).collect());
+                match cycle_word(&key) {
+                    Some(word) => result.extend(word),
+                    None => {
+                        debug!("No word for cycle {:?}", key);
+                        return None;
+                    }
+                }
+            }
+        }
+        Some(result)
+    }
+
+    pub fn write_to_file(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        versioned_file::write_versioned(path, CYCLE_LIBRARY_MAGIC, CYCLE_LIBRARY_VERSION, self)
+    }
+
+    /*
+     * Returns None if the file does not exist, was written by another version or is broken.
+     */
+    pub fn read_from_file(path: &str) -> Option<CycleLibrary> {
+        versioned_file::read_versioned(
+            path,
+            CYCLE_LIBRARY_MAGIC,
+            CYCLE_LIBRARY_VERSION,
+            "Cycle library",
+        )
+    }
+
+    /*
+     * Loads the library of the puzzle type from the directory, or builds and stores it if it does
+     * not exist or does not match the moves.
+     */
+    pub fn load_or_build(
+        library_path: &str,
+        puzzle_type: &PuzzleType,
+        moves: &[Move],
+        limits: &SearchConfig,
+    ) -> CycleLibrary {
+        let path = format!("{}/{}.bin", library_path, puzzle_type);
+        if let Some(library) = CycleLibrary::read_from_file(&path) {
+            if library.move_names.iter().eq(moves.iter().map(|m| &m.name)) {
+                info!("Loaded cycle library for {} from {}", puzzle_type, path);
+                return library;
+            }
+            warn!(
+                "Moves of cycle library {} do not match, rebuilding it",
+                path
+            );
+        }
+        let library = CycleLibrary::build(puzzle_type, moves, limits);
+        if let Err(e) = library.write_to_file(&path) {
+            warn!("Could not write cycle library to {}: {}", path, e);
+        }
+        library
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing_utils::TestingUtils;
+    use std::fs;
+
+    fn get_s_n_moves(n: usize) -> Vec<Move> {
+        let mut moves = Vec::new();
+        for (i, gen) in TestingUtils::get_s_n_generators(n).iter().enumerate() {
+            moves.push(Move {
+                name: format!("gen{}", i),
+                permutation: gen.clone(),
+            });
+            moves.push(Move {
+                name: format!("-gen{}", i),
+                permutation: gen.inverse(),
+            });
+        }
+        moves
+    }
+
+    #[test]
+    fn test_cycle_library_s_n() {
+        let moves = get_s_n_moves(6);
+        let library = CycleLibrary::build(&PuzzleType::WREATH(6), &moves, &SearchConfig::default());
+        // 15 transpositions and 40 3-cycles
+        assert_eq!(library.cycles.len(), 15 + 40);
+        for target_str in vec!["(1,2)", "(1,2,3)", "(1,6)(2,5)(3,4)", "(1,3,5,2,4)"] {
+            let target = Permutation::parse_permutation_from_cycle(target_str, 6);
+            let word = library
+                .factorize(&moves, &target, &Deadline::unlimited())
+                .unwrap();
+            TestingUtils::assert_index_path_equals_permutation(
+                &word,
+                &target,
+                &moves.iter().map(|m| m.permutation.clone()).collect(),
+            );
+        }
+    }
+
+    #[test]
+    fn test_cycle_library_coupled_parity() {
+        // Both orbits are odd only together, so there is no 2-cycle in the group
+        let mut moves = Vec::new();
+        for (name, cycle_str) in vec![("a", "(1,2)(4,5)"), ("b", "(1,2,3)"), ("c", "(4,5,6)")] {
+            let perm = Permutation::parse_permutation_from_cycle(cycle_str, 6);
+            moves.push(Move {
+                name: name.to_string(),
+                permutation: perm.clone(),
+            });
+            moves.push(Move {
+                name: format!("-{}", name),
+                permutation: perm.inverse(),
+            });
+        }
+        let library =
+            CycleLibrary::build(&PuzzleType::GLOBE(1, 3), &moves, &SearchConfig::default());
+        assert_eq!(library.parity_orbits, vec![0, 3]);
+        let target = Permutation::parse_permutation_from_cycle("(1,3)(4,6)", 6);
+        let word = library
+            .factorize(&moves, &target, &Deadline::unlimited())
+            .unwrap();
+        TestingUtils::assert_index_path_equals_permutation(
+            &word,
+            &target,
+            &moves.iter().map(|m| m.permutation.clone()).collect(),
+        );
+    }
+
+    #[test]
+    fn test_cycle_library_file() {
+        let moves = get_s_n_moves(5);
+        let library = CycleLibrary::build(&PuzzleType::WREATH(5), &moves, &SearchConfig::default());
+        let dir = std::env::temp_dir().join("santa_cycle_library_test");
+        let path = dir.join("wreath_5_5.bin");
+        library.write_to_file(path.to_str().unwrap()).unwrap();
+        let loaded = CycleLibrary::read_from_file(path.to_str().unwrap()).unwrap();
+        assert_eq!(loaded.cycles, library.cycles);
+        assert_eq!(loaded.move_names, library.move_names);
+
+        // A library of another version is ignored
+        let file = fs::File::create(&path).unwrap();
+        bincode::serialize_into(file, &(CYCLE_LIBRARY_MAGIC, CYCLE_LIBRARY_VERSION + 1)).unwrap();
+        assert!(CycleLibrary::read_from_file(path.to_str().unwrap()).is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
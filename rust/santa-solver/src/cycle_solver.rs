@@ -0,0 +1,212 @@
+use crate::config::Config;
+use crate::cycle_library::{canonical_cycle, CycleLibrary};
+use crate::ktt_solver;
+use crate::parallel::{self, Deadline, SolveOptions};
+use crate::permutation::Permutation;
+use crate::puzzle::{self, Move, Puzzle};
+use crate::validation::validate_solution;
+use log::{debug, info, warn};
+use std::collections::{HashMap, HashSet};
+
+/*
+ * Word of the cycle (x_1 ... x_c) of 0-indexed points in canonical rotation. Cycles of the library
+ * are used as they are. A missing cycle is reached by conjugating a library cycle (y_1 ... y_c)
+ * with the word w: the word S, w, S^-1 of a setup word S gives the cycle
+ * (S^-1(y_1) ... S^-1(y_c)), so the setups are searched breadth first for one with
+ * S(x_1 ... x_c) in the library. None if there is none within setup_depth moves or the deadline
+ * expired.
+ */
+fn cycle_word(
+    cycle: &[usize],
+    library: &CycleLibrary,
+    moves: &[Move],
+    inverses: &[Option<usize>],
+    setup_depth: usize,
+    deadline: &Deadline,
+) -> Option<Vec<usize>> {
+    if let Some(word) = library.word(cycle) {
+        return Some(word);
+    }
+    let mut seen: HashSet<Vec<usize>> = HashSet::from([cycle.to_vec()]);
+    let mut level: Vec<(Vec<usize>, Vec<usize>)> = vec![(Vec::new(), cycle.to_vec())];
+    for _ in 0..setup_depth {
+        let mut next = Vec::new();
+        for (setup, images) in &level {
+            if deadline.is_expired() {
+                return None;
+            }
+            for (m, move_elm) in moves.iter().enumerate() {
+                if inverses[m].is_none() {
+                    continue;
+                }
+                let images = images
+                    .iter()
+                    .map(|x| move_elm.permutation.p[*x] - 1)
+                    .collect::<Vec<usize>>();
+                if !seen.insert(images.clone()) {
+                    continue;
+                }
+                let mut setup = setup.clone();
+                setup.push(m);
+                if let Some(word) = library.word(&canonical_cycle(images.clone())) {
+                    let mut result = setup.clone();
+                    result.extend(word);
+                    result.extend(setup.iter().rev().map(|m| inverses[*m].unwrap()));
+                    return Some(result);
+                }
+                next.push((setup, images));
+            }
+        }
+        level = next;
+    }
+    None
+}
+
+/*
+ * Writes the target as a product of cycles as the ktt solver does, but reaches the cycles that
+ * are missing from the library with setup moves. The solution is replayed before it is returned.
+ */
+fn solve_puzzle(
+    puzzle: &Puzzle,
+    library: &CycleLibrary,
+    target: &Permutation,
+    setup_depth: usize,
+    deadline: &Deadline,
+) -> Option<String> {
+    info!(
+        "Solving puzzle {} of type {:?} with {} cycles",
+        puzzle.id,
+        puzzle.puzzle_type,
+        library.cycles.len()
+    );
+    let inverses = puzzle::move_inverses(&puzzle.moves);
+    let word = library.factorize_with(&puzzle.moves, target, deadline, |cycle| {
+        cycle_word(
+            cycle,
+            library,
+            &puzzle.moves,
+            &inverses,
+            setup_depth,
+            deadline,
+        )
+    });
+    let Some(word) = word else {
+        debug!(
+            "Failed to find a solution for puzzle {} of type {:?}",
+            puzzle.id, puzzle.puzzle_type
+        );
+        return None;
+    };
+    let solution = ktt_solver::solution_string(library, &word);
+    if let Err(e) = validate_solution(puzzle, &solution) {
+        warn!("Solution of puzzle {} is invalid: {}", puzzle.id, e);
+        return None;
+    }
+    parallel::log_solved(puzzle, word.len());
+    Some(solution)
+}
+
+/*
+ * Solves puzzles of any type with the cycle libraries of the ktt method, conjugating library
+ * cycles with up to cycle_setup_depth setup moves where a cycle of the target is missing.
+ */
+pub fn solve_puzzles(
+    puzzles: &Vec<Puzzle>,
+    config: &Config,
+    options: &SolveOptions,
+) -> HashMap<usize, String> {
+    let libraries = ktt_solver::load_libraries(puzzles, config);
+    let chains = ktt_solver::build_search_chains(puzzles, &libraries);
+    parallel::solve_in_parallel(puzzles, options, |puzzle, deadline| {
+        let library = &libraries[&puzzle.puzzle_type];
+        let target = ktt_solver::choose_target(&chains, puzzle, config, deadline)?;
+        solve_puzzle(
+            puzzle,
+            library,
+            &target,
+            config.search.cycle_setup_depth,
+            deadline,
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SearchConfig;
+    use crate::permutation;
+    use crate::puzzle::PuzzleType;
+    use crate::testing_utils::TestingUtils;
+
+    fn get_scrambled_wreath() -> Puzzle {
+        let moves = puzzle::load_puzzle_info("./../../data/puzzle_info.csv")
+            .unwrap()
+            .remove(&PuzzleType::WREATH(6))
+            .unwrap();
+        let goal_state = (0..moves[0].permutation.len()).collect::<Vec<usize>>();
+        let initial_state = (0..40).fold(goal_state.clone(), |state, i| {
+            moves[(i * 7 + i / 3) % moves.len()]
+                .permutation
+                .apply(&state)
+        });
+        Puzzle {
+            puzzle_type: PuzzleType::WREATH(6),
+            ..TestingUtils::puzzle_from_moves(moves, initial_state, goal_state, 0)
+        }
+    }
+
+    #[test]
+    fn test_solve_with_library_cycles() {
+        let puzzle = get_scrambled_wreath();
+        let library =
+            CycleLibrary::build(&puzzle.puzzle_type, &puzzle.moves, &SearchConfig::default());
+        let target = permutation::get_permutation(&puzzle.initial_state, &puzzle.goal_state);
+        let solution = solve_puzzle(&puzzle, &library, &target, 0, &Deadline::unlimited()).unwrap();
+        assert!(validate_solution(&puzzle, &solution).is_ok());
+    }
+
+    #[test]
+    fn test_setup_moves_for_missing_cycles() {
+        let puzzle = get_scrambled_wreath();
+        let mut library =
+            CycleLibrary::build(&puzzle.puzzle_type, &puzzle.moves, &SearchConfig::default());
+        // Keep every other 3-cycle, the others are reached by conjugation
+        let mut three_cycles = library
+            .cycles
+            .keys()
+            .filter(|cycle| cycle.len() == 3)
+            .cloned()
+            .collect::<Vec<Vec<usize>>>();
+        three_cycles.sort();
+        for cycle in three_cycles.iter().step_by(2) {
+            library.cycles.remove(cycle);
+        }
+        let inverses = puzzle::move_inverses(&puzzle.moves);
+        let word = cycle_word(
+            &three_cycles[0],
+            &library,
+            &puzzle.moves,
+            &inverses,
+            2,
+            &Deadline::unlimited(),
+        )
+        .unwrap();
+        let names = puzzle::word_to_string(&puzzle.moves, &word);
+        let str_to_gen = puzzle
+            .moves
+            .iter()
+            .map(|m| (m.name.clone(), m.permutation.clone()))
+            .collect();
+        let removed = three_cycles[0]
+            .iter()
+            .map(|x| x + 1)
+            .collect::<Vec<usize>>();
+        assert_eq!(
+            TestingUtils::get_permutation_from_operation_string(names, str_to_gen),
+            Permutation::from_cycles_fixed_per_size(&vec![removed], puzzle.goal_state.len())
+        );
+        let target = permutation::get_permutation(&puzzle.initial_state, &puzzle.goal_state);
+        let solution = solve_puzzle(&puzzle, &library, &target, 2, &Deadline::unlimited()).unwrap();
+        assert!(validate_solution(&puzzle, &solution).is_ok());
+    }
+}
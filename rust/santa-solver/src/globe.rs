@@ -0,0 +1,777 @@
+use crate::config::{Config, GlobeConfig};
+use crate::parallel::{self, Deadline, SolveOptions};
+use crate::puzzle::{self, Move, Puzzle, PuzzleType};
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::error::Error;
+use std::iter;
+
+// Move of a pair of rows: a rotation of the upper (0) or lower (1) row, possibly inverted, or a flip
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Turn {
+    Rotate(usize, bool),
+    Flip(usize),
+}
+
+impl Turn {
+    fn inverse(self) -> Turn {
+        match self {
+            Turn::Rotate(side, inverse) => Turn::Rotate(side, !inverse),
+            Turn::Flip(column) => Turn::Flip(column),
+        }
+    }
+}
+
+fn inverse_word(word: &[Turn]) -> Vec<Turn> {
+    word.iter().rev().map(|turn| turn.inverse()).collect()
+}
+
+// Sticker whose color a sparse permutation puts on sticker x
+fn lookup(pulls: &[(usize, usize)], x: usize) -> usize {
+    pulls
+        .iter()
+        .find(|(to, _)| *to == x)
+        .map_or(x, |(_, from)| *from)
+}
+
+fn mismatches(state: &[usize], goal: &[usize]) -> usize {
+    state
+        .iter()
+        .zip(goal.iter())
+        .filter(|(a, b)| a != b)
+        .count()
+}
+
+/*
+ * A word of a pair of rows with the stickers it changes, each with the sticker whose color it gets.
+ */
+#[derive(Debug, Clone)]
+struct Shape {
+    word: Vec<Turn>,
+    pulls: Vec<(usize, usize)>,
+}
+
+/*
+ * Conjugate of a shape that puts the right color on a sticker: the shape is shifted by shift
+ * columns on both rows and by offset more columns on the upper row, and then conjugated with a flip
+ * if setup is given.
+ */
+#[derive(Debug, Clone, Copy)]
+struct Placement {
+    shape: usize,
+    shift: usize,
+    offset: usize,
+    setup: Option<usize>,
+    cost: usize,
+}
+
+/*
+ * A row of a globe and its mirror row, which the flips exchange, seen on their own. Their stickers
+ * are indexed by side * columns + column, side 0 being the upper row. The moves restricted to the
+ * pair are given as pulls, i.e. the sticker whose color ends up at each sticker.
+ */
+#[derive(Debug, Clone, PartialEq)]
+struct RowPair {
+    columns: usize,
+    // Rotation of each side, forward and inverse
+    rotations: Vec<Vec<Vec<usize>>>,
+    flips: Vec<Vec<usize>>,
+}
+
+impl RowPair {
+    fn new(
+        moves: &[Move],
+        rotations: &[[usize; 2]],
+        flips: &[usize],
+        rows: [usize; 2],
+    ) -> Result<RowPair, Box<dyn Error>> {
+        let columns = flips.len();
+        let restrict = |m: usize| -> Result<Vec<usize>, Box<dyn Error>> {
+            (0..2 * columns)
+                .map(|x| {
+                    let sticker = rows[x / columns] * columns + x % columns;
+                    let from = moves[m].permutation.p[sticker] - 1;
+                    let side =
+                        rows.iter()
+                            .position(|row| *row == from / columns)
+                            .ok_or(format!(
+                                "Move {} mixes rows {:?} with others",
+                                moves[m].name, rows
+                            ))?;
+                    Ok(side * columns + from % columns)
+                })
+                .collect()
+        };
+        Ok(RowPair {
+            columns,
+            rotations: rows
+                .iter()
+                .map(|row| rotations[*row].iter().map(|m| restrict(*m)).collect())
+                .collect::<Result<_, _>>()?,
+            flips: flips
+                .iter()
+                .map(|m| restrict(*m))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn pull(&self, turn: Turn, x: usize) -> usize {
+        match turn {
+            Turn::Rotate(side, inverse) => self.rotations[side][inverse as usize][x],
+            Turn::Flip(column) => self.flips[column][x],
+        }
+    }
+
+    fn word_pulls(&self, word: &[Turn]) -> Vec<(usize, usize)> {
+        (0..2 * self.columns)
+            .map(|x| (x, word.iter().rev().fold(x, |x, turn| self.pull(*turn, x))))
+            .filter(|(x, from)| x != from)
+            .collect()
+    }
+
+    fn apply(&self, state: &mut [usize], word: &[Turn]) {
+        let pulls = self.word_pulls(word);
+        let colors = pulls
+            .iter()
+            .map(|(_, from)| state[*from])
+            .collect::<Vec<usize>>();
+        for ((x, _), color) in pulls.iter().zip(colors) {
+            state[*x] = color;
+        }
+    }
+
+    fn rotation_length(&self, k: usize) -> usize {
+        (k % self.columns).min(self.columns - k % self.columns)
+    }
+
+    // Shortest word rotating a side by k columns
+    fn rotation(&self, side: usize, k: usize) -> Vec<Turn> {
+        let k = k % self.columns;
+        match k <= self.columns / 2 {
+            true => vec![Turn::Rotate(side, false); k],
+            false => vec![Turn::Rotate(side, true); self.columns - k],
+        }
+    }
+
+    fn translate(&self, x: usize, shift: usize, offset: usize) -> usize {
+        let (side, column) = (x / self.columns, x % self.columns);
+        let column = column + shift + if side == 0 { offset } else { 0 };
+        side * self.columns + column % self.columns
+    }
+
+    /*
+     * Word with the pulls of the word translated by shift and offset: the flips are shifted, which
+     * conjugates the word with a rotation of both rows, and the upper row is rotated around it.
+     */
+    fn translate_word(&self, word: &[Turn], shift: usize, offset: usize) -> Vec<Turn> {
+        let setup = self.rotation(0, offset);
+        let shifted = word.iter().map(|turn| match turn {
+            Turn::Flip(column) => Turn::Flip((column + shift) % self.columns),
+            turn => *turn,
+        });
+        let mut result = setup.clone();
+        result.extend(shifted);
+        result.extend(inverse_word(&setup));
+        result
+    }
+
+    fn translate_pulls(
+        &self,
+        pulls: &[(usize, usize)],
+        shift: usize,
+        offset: usize,
+    ) -> Vec<(usize, usize)> {
+        let mut result = pulls
+            .iter()
+            .map(|(to, from)| {
+                (
+                    self.translate(*to, shift, offset),
+                    self.translate(*from, shift, offset),
+                )
+            })
+            .collect::<Vec<(usize, usize)>>();
+        result.sort();
+        result
+    }
+
+    /*
+     * Flip-rotate-flip commutators [r_x^±1, f_0 r_y^±1 f_0] of the two rows, which swap two pairs
+     * of stickers next to the ends of the flip.
+     */
+    fn double_transpositions(&self) -> Vec<Shape> {
+        let mut shapes = Vec::new();
+        for (x, y) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            for (i, j) in [(false, false), (false, true), (true, false), (true, true)] {
+                let word = vec![
+                    Turn::Rotate(x, i),
+                    Turn::Flip(0),
+                    Turn::Rotate(y, j),
+                    Turn::Flip(0),
+                    Turn::Rotate(x, !i),
+                    Turn::Flip(0),
+                    Turn::Rotate(y, !j),
+                    Turn::Flip(0),
+                ];
+                let pulls = self.word_pulls(&word);
+                if !pulls.is_empty() && pulls.len() <= 4 {
+                    shapes.push(Shape { word, pulls });
+                }
+            }
+        }
+        shapes
+    }
+
+    /*
+     * 3-cycles as products of two double transpositions that share a transposition, one shortest
+     * word for each 3-cycle up to translation.
+     */
+    fn three_cycles(&self, double_transpositions: &[Shape]) -> Vec<Shape> {
+        let canonical = |pulls: &[(usize, usize)]| {
+            (0..self.columns)
+                .flat_map(|shift| (0..self.columns).map(move |offset| (shift, offset)))
+                .map(|(shift, offset)| self.translate_pulls(pulls, shift, offset))
+                .min()
+                .unwrap()
+        };
+        let mut shapes: HashMap<Vec<(usize, usize)>, Shape> = HashMap::new();
+        for a in double_transpositions {
+            for b in double_transpositions {
+                for shift in 0..self.columns {
+                    for offset in 0..self.columns {
+                        let b_pulls = self.translate_pulls(&b.pulls, shift, offset);
+                        let mut stickers = a
+                            .pulls
+                            .iter()
+                            .chain(b_pulls.iter())
+                            .map(|(to, _)| *to)
+                            .collect::<Vec<usize>>();
+                        stickers.sort();
+                        stickers.dedup();
+                        let pulls = stickers
+                            .iter()
+                            .map(|x| (*x, lookup(&a.pulls, lookup(&b_pulls, *x))))
+                            .filter(|(x, from)| x != from)
+                            .collect::<Vec<(usize, usize)>>();
+                        if pulls.len() != 3 {
+                            continue;
+                        }
+                        let mut word = a.word.clone();
+                        word.extend(self.translate_word(&b.word, shift, offset));
+                        let mut inverse_pulls = pulls
+                            .iter()
+                            .map(|(to, from)| (*from, *to))
+                            .collect::<Vec<(usize, usize)>>();
+                        inverse_pulls.sort();
+                        let inverse = Shape {
+                            word: inverse_word(&word),
+                            pulls: inverse_pulls,
+                        };
+                        for shape in [Shape { word, pulls }, inverse] {
+                            let key = canonical(&shape.pulls);
+                            match shapes.get(&key) {
+                                Some(known) if known.word.len() <= shape.word.len() => {}
+                                _ => {
+                                    shapes.insert(key, shape);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        shapes.into_values().collect()
+    }
+
+    // Translations moving sticker j of a shape to x and sticker i to y, cheapest offset first
+    fn translations(&self, j: usize, x: usize, i: usize, y: usize) -> Vec<(usize, usize)> {
+        let columns = self.columns;
+        let diff = |a: usize, b: usize| (a % columns + columns - b % columns) % columns;
+        let by_cost = (0..columns).map(|k| match k % 2 {
+            0 => (columns - k / 2) % columns,
+            _ => k / 2 + 1,
+        });
+        if j / columns != x / columns || i / columns != y / columns {
+            return Vec::new();
+        }
+        match (j / columns, i / columns) {
+            (1, 1) if diff(x, j) == diff(y, i) => {
+                by_cost.map(|offset| (diff(x, j), offset)).collect()
+            }
+            (1, 0) => vec![(diff(x, j), diff(diff(y, i), diff(x, j)))],
+            (0, 1) => vec![(diff(y, i), diff(diff(x, j), diff(y, i)))],
+            (0, 0) if diff(x, j) == diff(y, i) => by_cost
+                .map(|offset| (diff(diff(x, j), offset), offset))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // Stickers changed by a placement with the stickers whose colors they get
+    fn placement_pulls(&self, shapes: &[Shape], placement: &Placement) -> Vec<(usize, usize)> {
+        let setup = |x: usize| placement.setup.map_or(x, |column| self.flips[column][x]);
+        shapes[placement.shape]
+            .pulls
+            .iter()
+            .map(|(to, from)| {
+                (
+                    setup(self.translate(*to, placement.shift, placement.offset)),
+                    setup(self.translate(*from, placement.shift, placement.offset)),
+                )
+            })
+            .collect()
+    }
+
+    fn placement_word(&self, shapes: &[Shape], placement: &Placement) -> Vec<Turn> {
+        let word = self.translate_word(
+            &shapes[placement.shape].word,
+            placement.shift,
+            placement.offset,
+        );
+        match placement.setup {
+            Some(column) => iter::once(Turn::Flip(column))
+                .chain(word)
+                .chain(iter::once(Turn::Flip(column)))
+                .collect(),
+            None => word,
+        }
+    }
+
+    /*
+     * Cheapest placement that gives sticker x its goal color from a sticker that is not done and
+     * keeps the colors of the done stickers, within the limits of the config.
+     */
+    fn find_placement(
+        &self,
+        shapes: &[Shape],
+        state: &[usize],
+        goal: &[usize],
+        done: &[bool],
+        x: usize,
+        config: &GlobeConfig,
+    ) -> Option<Placement> {
+        let sources = (0..state.len())
+            .filter(|y| *y != x && !done[*y] && state[*y] == goal[x])
+            .collect::<Vec<usize>>();
+        let mut best: Option<Placement> = None;
+        let setups = (0..self.columns).filter(|_| config.setup_flips).map(Some);
+        for setup in iter::once(None).chain(setups) {
+            let map = |z: usize| setup.map_or(z, |column| self.flips[column][z]);
+            let setup_cost = if setup.is_some() { 2 } else { 0 };
+            for (k, shape) in shapes.iter().enumerate() {
+                for (j, i) in &shape.pulls {
+                    for y in &sources {
+                        for (shift, offset) in self.translations(*j, map(x), *i, map(*y)) {
+                            if self.rotation_length(offset) > config.max_offset {
+                                break;
+                            }
+                            let rotation_cost = 2 * self.rotation_length(offset);
+                            let cost = shape.word.len() + setup_cost + rotation_cost;
+                            if best.is_some_and(|best| best.cost <= cost) {
+                                break;
+                            }
+                            let placement = Placement {
+                                shape: k,
+                                shift,
+                                offset,
+                                setup,
+                                cost,
+                            };
+                            let keeps_done = self
+                                .placement_pulls(shapes, &placement)
+                                .iter()
+                                .all(|(to, from)| !done[*to] || state[*from] == goal[*to]);
+                            if keeps_done {
+                                best = Some(placement);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    /*
+     * Order in which the stickers are put in place, such that the last three are one sticker of
+     * the upper row and two neighbours of the lower row, which a translated 3-cycle can reach.
+     */
+    fn order(&self) -> Vec<usize> {
+        let columns = self.columns;
+        let last = [columns - 1, 2 * columns - 2, 2 * columns - 1];
+        (0..2 * columns)
+            .filter(|x| !last.contains(x))
+            .chain(last)
+            .collect()
+    }
+
+    /*
+     * Puts the stickers in place one after another with the cheapest placement each, until at most
+     * allowed stickers are wrong. None if a sticker has no placement, e.g. because the permutation
+     * left is odd.
+     */
+    fn place_all(
+        &self,
+        shapes: &[Shape],
+        state: &mut [usize],
+        goal: &[usize],
+        allowed: usize,
+        config: &GlobeConfig,
+        deadline: &Deadline,
+    ) -> Option<Vec<Turn>> {
+        let mut word = Vec::new();
+        let mut done = vec![false; state.len()];
+        for x in self.order() {
+            if mismatches(state, goal) <= allowed {
+                break;
+            }
+            if deadline.is_expired() {
+                return None;
+            }
+            if state[x] != goal[x] {
+                let placement = self.find_placement(shapes, state, goal, &done, x, config)?;
+                let placement_word = self.placement_word(shapes, &placement);
+                self.apply(state, &placement_word);
+                word.extend(placement_word);
+            }
+            done[x] = true;
+        }
+        Some(word)
+    }
+
+    /*
+     * Solves the pair: both rows are rotated to the position with the most stickers in place, then
+     * the stickers are placed. If that fails, it is retried with the upper row rotated by one more
+     * column, which changes the parity of the permutation.
+     */
+    fn solve(
+        &self,
+        shapes: &[Shape],
+        state: &[usize],
+        goal: &[usize],
+        allowed: usize,
+        config: &GlobeConfig,
+        deadline: &Deadline,
+    ) -> Option<Vec<Turn>> {
+        let mut word = Vec::new();
+        let mut aligned = state.to_vec();
+        for side in 0..2 {
+            let rotation = (0..self.columns)
+                .map(|k| self.rotation(side, k))
+                .min_by_key(|rotation| {
+                    let mut rotated = aligned.clone();
+                    self.apply(&mut rotated, rotation);
+                    (mismatches(&rotated, goal), rotation.len())
+                })
+                .unwrap();
+            self.apply(&mut aligned, &rotation);
+            word.extend(rotation);
+        }
+        for parity in [Vec::new(), vec![Turn::Rotate(0, false)]] {
+            let mut state = aligned.clone();
+            self.apply(&mut state, &parity);
+            if let Some(placed) =
+                self.place_all(shapes, &mut state, goal, allowed, config, deadline)
+            {
+                word.extend(parity);
+                word.extend(placed);
+                return Some(word);
+            }
+        }
+        None
+    }
+}
+
+/*
+ * Solver of one globe size. The rows are rings of 2n stickers that the rotations r_i turn and the
+ * flips f_a turn upside down: f_a swaps the n stickers of a row from column a on with those of its
+ * mirror row, reversed. No move mixes a pair of mirror rows with the other rows, and a word whose
+ * flips cancel on the other rows, like the flip-rotate-flip commutators, only changes one pair, so
+ * the pairs are solved one after another. The middle row of an odd number of rows is not changed
+ * by the flips and is only rotated.
+ */
+pub struct GlobeSolver {
+    columns: usize,
+    // Indices of r_i and -r_i of every row
+    rotations: Vec<[usize; 2]>,
+    // Index of f_a of every column
+    flips: Vec<usize>,
+    pairs: Vec<[usize; 2]>,
+    middle_rows: Vec<usize>,
+    row_pair: RowPair,
+    shapes: Vec<Shape>,
+}
+
+impl GlobeSolver {
+    /*
+     * The size is taken from the moves rather than the puzzle type, as the moves of globe_33/3 are
+     * those of globe_3/33.
+     */
+    pub fn new(moves: &[Move]) -> Result<GlobeSolver, Box<dyn Error>> {
+        let count = |prefix: &str| {
+            moves
+                .iter()
+                .filter(|mov| {
+                    mov.name
+                        .strip_prefix(prefix)
+                        .is_some_and(|i| i.parse::<usize>().is_ok())
+                })
+                .count()
+        };
+        let (rows, columns) = (count("r"), count("f"));
+        if rows == 0 || moves[0].permutation.len() != rows * columns {
+            return Err(format!(
+                "Moves do not fit a globe of {} rows and {} columns",
+                rows, columns
+            )
+            .into());
+        }
+        let (m, n) = (rows - 1, columns / 2);
+        let index_of = moves
+            .iter()
+            .enumerate()
+            .map(|(i, mov)| (mov.name.as_str(), i))
+            .collect::<HashMap<&str, usize>>();
+        let find = |name: String| {
+            index_of
+                .get(name.as_str())
+                .copied()
+                .ok_or(format!("Missing move {}", name))
+        };
+        let rotations = (0..rows)
+            .map(|row| Ok([find(format!("r{}", row))?, find(format!("-r{}", row))?]))
+            .collect::<Result<Vec<[usize; 2]>, Box<dyn Error>>>()?;
+        let flips = (0..columns)
+            .map(|column| find(format!("f{}", column)))
+            .collect::<Result<Vec<usize>, _>>()?;
+
+        let mut pairs = Vec::new();
+        let mut middle_rows = Vec::new();
+        for row in 0..rows {
+            let mirror = (moves[flips[0]].permutation.p[row * columns] - 1) / columns;
+            match row.cmp(&mirror) {
+                std::cmp::Ordering::Less => pairs.push([row, mirror]),
+                std::cmp::Ordering::Equal => middle_rows.push(row),
+                std::cmp::Ordering::Greater => {}
+            }
+        }
+        let Some(first) = pairs.first() else {
+            return Err(format!("Globe {}/{} has no pair of rows", m, n).into());
+        };
+        let row_pair = RowPair::new(moves, &rotations, &flips, *first)?;
+        for pair in &pairs {
+            if RowPair::new(moves, &rotations, &flips, *pair)? != row_pair {
+                return Err(format!("Rows {:?} do not move like rows {:?}", pair, first).into());
+            }
+        }
+        let mut shapes = row_pair.double_transpositions();
+        if shapes.is_empty() {
+            return Err(format!("Globe {}/{} has no flip-rotate-flip commutators", m, n).into());
+        }
+        shapes.extend(row_pair.three_cycles(&shapes));
+        shapes.sort_by_key(|shape| shape.word.len());
+        debug!(
+            "Globe {}/{} has {} pairs of rows and {} commutator shapes",
+            m,
+            n,
+            pairs.len(),
+            shapes.len()
+        );
+        Ok(GlobeSolver {
+            columns,
+            rotations,
+            flips,
+            pairs,
+            middle_rows,
+            row_pair,
+            shapes,
+        })
+    }
+
+    fn stickers(&self, rows: &[usize]) -> Vec<usize> {
+        rows.iter()
+            .flat_map(|row| row * self.columns..(row + 1) * self.columns)
+            .collect()
+    }
+
+    fn apply_word(moves: &[Move], word: &[usize], state: &[usize]) -> Vec<usize> {
+        word.iter().fold(state.to_vec(), |state, m| {
+            moves[*m].permutation.apply(&state)
+        })
+    }
+
+    /*
+     * Solves a globe ring by ring: the middle rows with a rotation, then each pair of rows. The
+     * wildcards are used up by the rings solved first. Returns the move indices of the solution,
+     * which is replayed before it is returned.
+     */
+    pub fn solve(
+        &self,
+        puzzle: &Puzzle,
+        config: &GlobeConfig,
+        deadline: &Deadline,
+    ) -> Option<Vec<usize>> {
+        let moves = &puzzle.moves;
+        let goal = &puzzle.goal_state;
+        let mut allowed = puzzle.num_wildcards;
+        let mut state = puzzle.initial_state.clone();
+        let mut word = Vec::new();
+
+        for row in &self.middle_rows {
+            let stickers = self.stickers(&[*row]);
+            let (k, wrong) = (0..self.columns)
+                .map(|k| {
+                    let wrong = (0..self.columns)
+                        .filter(|c| state[stickers[(c + k) % self.columns]] != goal[stickers[*c]])
+                        .count();
+                    (k, wrong)
+                })
+                .min_by_key(|(k, wrong)| (*wrong, (*k).min(self.columns - k)))
+                .unwrap();
+            if wrong > allowed {
+                debug!(
+                    "Middle row {} of puzzle {} cannot be solved",
+                    row, puzzle.id
+                );
+                return None;
+            }
+            allowed -= wrong;
+            let rotation = match k <= self.columns / 2 {
+                true => vec![self.rotations[*row][0]; k],
+                false => vec![self.rotations[*row][1]; self.columns - k],
+            };
+            state = GlobeSolver::apply_word(moves, &rotation, &state);
+            word.extend(rotation);
+        }
+
+        for pair in &self.pairs {
+            let stickers = self.stickers(pair);
+            let pair_state = stickers.iter().map(|x| state[*x]).collect::<Vec<usize>>();
+            let pair_goal = stickers.iter().map(|x| goal[*x]).collect::<Vec<usize>>();
+            let Some(turns) = self.row_pair.solve(
+                &self.shapes,
+                &pair_state,
+                &pair_goal,
+                allowed,
+                config,
+                deadline,
+            ) else {
+                debug!(
+                    "Rows {:?} of puzzle {} could not be solved",
+                    pair, puzzle.id
+                );
+                return None;
+            };
+            let pair_word = turns
+                .iter()
+                .map(|turn| match turn {
+                    Turn::Rotate(side, inverse) => self.rotations[pair[*side]][*inverse as usize],
+                    Turn::Flip(column) => self.flips[*column],
+                })
+                .collect::<Vec<usize>>();
+            state = GlobeSolver::apply_word(moves, &pair_word, &state);
+            let wrong = stickers.iter().filter(|x| state[**x] != goal[**x]).count();
+            allowed = allowed.saturating_sub(wrong);
+            word.extend(pair_word);
+        }
+
+        if mismatches(&state, goal) > puzzle.num_wildcards {
+            warn!("Solution of puzzle {} does not solve it", puzzle.id);
+            return None;
+        }
+        Some(word)
+    }
+}
+
+/*
+ * Solves the globes ring by ring with flip-rotate-flip commutators.
+ */
+pub fn solve_puzzles(
+    puzzles: &[Puzzle],
+    config: &Config,
+    options: &SolveOptions,
+) -> HashMap<usize, String> {
+    let globes = puzzles
+        .iter()
+        .filter(|p| matches!(p.puzzle_type, PuzzleType::GLOBE(_, _)))
+        .cloned()
+        .collect::<Vec<Puzzle>>();
+    let mut solvers: HashMap<PuzzleType, GlobeSolver> = HashMap::new();
+    for puzzle in &globes {
+        if solvers.contains_key(&puzzle.puzzle_type) {
+            continue;
+        }
+        match GlobeSolver::new(&puzzle.moves) {
+            Ok(solver) => {
+                solvers.insert(puzzle.puzzle_type.clone(), solver);
+            }
+            Err(e) => warn!(
+                "No globe solver for puzzle type {:?}: {}",
+                puzzle.puzzle_type, e
+            ),
+        }
+    }
+    parallel::solve_in_parallel(&globes, options, |puzzle, deadline| {
+        let solver = solvers.get(&puzzle.puzzle_type)?;
+        let word = solver.solve(puzzle, &config.globe, deadline)?;
+        parallel::log_solved(puzzle, word.len());
+        Some(puzzle::word_to_string(&puzzle.moves, &word))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::puzzle;
+    use crate::validation::validate_solution;
+
+    fn get_puzzles() -> (HashMap<PuzzleType, Vec<Move>>, Vec<Puzzle>) {
+        let puzzle_info = puzzle::load_puzzle_info("./../../data/puzzle_info.csv").unwrap();
+        let puzzles = puzzle::load_puzzles("./../../data/puzzles.csv", &puzzle_info).unwrap();
+        (puzzle_info, puzzles)
+    }
+
+    #[test]
+    fn test_translated_shapes() {
+        let (puzzle_info, _) = get_puzzles();
+        let solver = GlobeSolver::new(&puzzle_info[&PuzzleType::GLOBE(1, 8)]).unwrap();
+        let row_pair = &solver.row_pair;
+        assert!(solver.shapes.iter().any(|shape| shape.pulls.len() == 3));
+        for shape in &solver.shapes {
+            assert_eq!(row_pair.word_pulls(&shape.word), shape.pulls);
+            for (shift, offset) in [(0, 3), (5, 0), (7, 12)] {
+                let word = row_pair.translate_word(&shape.word, shift, offset);
+                assert_eq!(
+                    row_pair.word_pulls(&word),
+                    row_pair.translate_pulls(&shape.pulls, shift, offset)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_size_from_moves() {
+        let (puzzle_info, _) = get_puzzles();
+        let solver = GlobeSolver::new(&puzzle_info[&PuzzleType::GLOBE(33, 3)]).unwrap();
+        assert_eq!(solver.columns, 66);
+        assert_eq!(solver.pairs, vec![[0, 3], [1, 2]]);
+        assert!(solver.middle_rows.is_empty());
+        let solver = GlobeSolver::new(&puzzle_info[&PuzzleType::GLOBE(2, 6)]).unwrap();
+        assert_eq!(solver.pairs, vec![[0, 2]]);
+        assert_eq!(solver.middle_rows, vec![1]);
+    }
+
+    #[test]
+    fn test_solve_globes() {
+        let (puzzle_info, puzzles) = get_puzzles();
+        // Unique colors, repeated colors with wildcards and a middle row
+        for id in [343, 358, 353] {
+            let puzzle = puzzles.iter().find(|p| p.id == id).unwrap();
+            let solver = GlobeSolver::new(&puzzle_info[&puzzle.puzzle_type]).unwrap();
+            let word = solver
+                .solve(puzzle, &GlobeConfig::default(), &Deadline::unlimited())
+                .unwrap();
+            let names = puzzle::word_to_string(&puzzle.moves, &word);
+            assert_eq!(validate_solution(puzzle, &names), Ok(word.len()));
+        }
+    }
+}
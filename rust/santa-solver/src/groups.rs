@@ -1,5 +1,8 @@
+use crate::compact_permutation::{CompactPermutation, Point};
+use crate::config::VisitedConfig;
 use crate::minkwitz::PermAndWord;
 use crate::permutation::{CompressedPermutation, Permutation, PermutationIndex, PermutationPath};
+use crate::visited::VisitedSet;
 use log::error;
 use log::info;
 use std::collections::HashMap;
@@ -21,10 +24,10 @@ pub struct PermutationGroupPermAndWordIterator<'a> {
 
 pub struct DepthLimitedPermutationGroupIterator<'a> {
     frontier: VecDeque<(Permutation, Vec<usize>)>,
-    visited: bloomfilter::Bloom<Permutation>,
+    visited: VisitedSet,
     items_inserted: usize,
     queue: VecDeque<(Permutation, Vec<usize>)>,
-    generators: &'a Vec<Permutation>,
+    generators: &'a [Permutation],
     current_depth: usize,
     max_depth: usize,
 }
@@ -133,14 +136,22 @@ impl<'a> Iterator for PermutationGroupPermAndWordIterator<'a> {
 }
 
 impl<'a> DepthLimitedPermutationGroupIterator<'a> {
-    pub fn new(generators: &'a Vec<Permutation>, max_depth: usize) -> Self {
+    pub fn new(generators: &'a [Permutation], max_depth: usize) -> Self {
+        Self::with_visited(generators, max_depth, &VisitedConfig::default())
+    }
+
+    pub fn with_visited(
+        generators: &'a [Permutation],
+        max_depth: usize,
+        visited: &VisitedConfig,
+    ) -> Self {
         let mut queue = VecDeque::new();
         let identity = Permutation::identity(generators[0].len());
         queue.push_back((identity, Vec::<usize>::new()));
 
         Self {
             frontier: VecDeque::new(),
-            visited: bloomfilter::Bloom::new_for_fp_rate(1_000_000, 0.0000001), //TODO adjust
+            visited: VisitedSet::new(visited),
             queue,
             generators,
             current_depth: 0,
@@ -155,7 +166,10 @@ impl<'a> Iterator for DepthLimitedPermutationGroupIterator<'a> {
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.current_depth == self.max_depth {
-            info!("Reached max depth of {} in Group Iterator", self.max_depth);
+            info!(
+                "Reached max depth of {} in Group Iterator, visited set: {}",
+                self.max_depth, self.visited
+            );
             return None;
         }
         if self.frontier.is_empty() {
@@ -177,7 +191,10 @@ impl<'a> Iterator for DepthLimitedPermutationGroupIterator<'a> {
         }
         let result = self.frontier.pop_front();
         if self.queue.is_empty() && result.is_none() {
-            info!("Group Iterator Frontier is now empty, proceeding ...");
+            info!(
+                "Group Iterator Frontier is now empty, visited set: {}",
+                self.visited
+            );
             return None;
         } else if result.is_none() {
             return self.next();
@@ -203,7 +220,7 @@ impl<'a> Iterator for DepthLimitedPermutationGroupIterator<'a> {
 
 pub struct DepthLimitedPermutationGroupIteratorCompressed<'a> {
     frontier: VecDeque<(CompressedPermutation, Vec<usize>)>,
-    visited: bloomfilter::Bloom<CompressedPermutation>,
+    visited: VisitedSet,
     items_inserted: usize,
     queue: VecDeque<(CompressedPermutation, Vec<usize>)>,
     generators: &'a Vec<CompressedPermutation>,
@@ -213,13 +230,21 @@ pub struct DepthLimitedPermutationGroupIteratorCompressed<'a> {
 
 impl<'a> DepthLimitedPermutationGroupIteratorCompressed<'a> {
     pub fn new(generators: &'a Vec<CompressedPermutation>, max_depth: usize) -> Self {
+        Self::with_visited(generators, max_depth, &VisitedConfig::default())
+    }
+
+    pub fn with_visited(
+        generators: &'a Vec<CompressedPermutation>,
+        max_depth: usize,
+        visited: &VisitedConfig,
+    ) -> Self {
         let mut queue = VecDeque::new();
         let identity = CompressedPermutation::identity(generators[0].len());
         queue.push_back((identity, Vec::<usize>::new()));
 
         Self {
             frontier: VecDeque::new(),
-            visited: bloomfilter::Bloom::new_for_fp_rate(1_000_000, 0.0000001), //TODO adjust
+            visited: VisitedSet::new(visited),
             queue,
             generators,
             current_depth: 0,
@@ -234,7 +259,10 @@ impl<'a> Iterator for DepthLimitedPermutationGroupIteratorCompressed<'a> {
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.current_depth == self.max_depth {
-            info!("Reached max depth of {} in Group Iterator", self.max_depth);
+            info!(
+                "Reached max depth of {} in Group Iterator, visited set: {}",
+                self.max_depth, self.visited
+            );
             return None;
         }
         if self.frontier.is_empty() {
@@ -256,7 +284,10 @@ impl<'a> Iterator for DepthLimitedPermutationGroupIteratorCompressed<'a> {
         }
         let result = self.frontier.pop_front();
         if self.queue.is_empty() && result.is_none() {
-            info!("Group Iterator Frontier is now empty, proceeding ...");
+            info!(
+                "Group Iterator Frontier is now empty, visited set: {}",
+                self.visited
+            );
             return None;
         } else if result.is_none() {
             return self.next();
@@ -280,9 +311,176 @@ impl<'a> Iterator for DepthLimitedPermutationGroupIteratorCompressed<'a> {
     }
 }
 
+/*
+ * Same as DepthLimitedPermutationGroupIterator for compact permutations, so the queues of large
+ * puzzles take a quarter of the memory. Products that were already visited are computed in a
+ * reused buffer, so only new elements allocate.
+ */
+pub struct DepthLimitedCompactGroupIterator<'a, T: Point> {
+    frontier: VecDeque<(CompactPermutation<T>, Vec<usize>)>,
+    visited: VisitedSet,
+    items_inserted: usize,
+    queue: VecDeque<(CompactPermutation<T>, Vec<usize>)>,
+    generators: &'a [CompactPermutation<T>],
+    product: CompactPermutation<T>,
+    current_depth: usize,
+    max_depth: usize,
+}
+
+impl<'a, T: Point> DepthLimitedCompactGroupIterator<'a, T> {
+    pub fn new(generators: &'a [CompactPermutation<T>], max_depth: usize) -> Self {
+        Self::with_visited(generators, max_depth, &VisitedConfig::default())
+    }
+
+    pub fn with_visited(
+        generators: &'a [CompactPermutation<T>],
+        max_depth: usize,
+        visited: &VisitedConfig,
+    ) -> Self {
+        let mut queue = VecDeque::new();
+        let identity = CompactPermutation::identity(generators[0].len());
+        queue.push_back((identity.clone(), Vec::<usize>::new()));
+
+        Self {
+            frontier: VecDeque::new(),
+            visited: VisitedSet::new(visited),
+            queue,
+            generators,
+            product: identity,
+            current_depth: 0,
+            items_inserted: 0,
+            max_depth,
+        }
+    }
+}
+
+impl<T: Point> Iterator for DepthLimitedCompactGroupIterator<'_, T> {
+    type Item = (CompactPermutation<T>, Vec<usize>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.current_depth == self.max_depth {
+                info!(
+                    "Reached max depth of {} in Group Iterator, visited set: {}",
+                    self.max_depth, self.visited
+                );
+                return None;
+            }
+            if let Some((element_perm, path)) = self.frontier.pop_front() {
+                self.visited.set(&element_perm);
+                self.items_inserted += 1;
+                if self.items_inserted.is_multiple_of(10000) {
+                    info!(
+                        "Visited {} elements in DepthLimitedCompactGroupIterator",
+                        self.items_inserted
+                    );
+                }
+                self.queue.push_back((element_perm.clone(), path.clone()));
+                if path.len() > self.current_depth {
+                    self.current_depth = path.len();
+                }
+                return Some((element_perm, path));
+            }
+            let Some((element_perm, element_path)) = self.queue.pop_front() else {
+                info!(
+                    "Group Iterator Frontier is now empty, visited set: {}",
+                    self.visited
+                );
+                return None;
+            };
+            for (i, generator) in self.generators.iter().enumerate() {
+                generator.compose_into(&element_perm, &mut self.product);
+                if !self.visited.check(&self.product) {
+                    let mut new_path = element_path.clone();
+                    new_path.push(i);
+                    self.frontier.push_back((self.product.clone(), new_path));
+                }
+            }
+        }
+    }
+}
+
+/*
+ * Result of a search for many targets at once. Words are generator indices, applied in order, i.e.
+ * the word i_1..i_k stands for g_{i_k}.compose(...).compose(g_{i_1}).
+ */
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MultiTargetResult {
+    // Shortest word of every target that was found
+    pub words: HashMap<Permutation, Vec<usize>>,
+    // Targets not reached within the depth, in the order they were given
+    pub missing: Vec<Permutation>,
+}
+
+impl MultiTargetResult {
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+
+    /*
+     * Concatenation of the words of all found targets, ordered by target so that the result does
+     * not depend on the iteration order of the map. Only a word for the product of the targets if
+     * they commute, e.g. if they are disjoint cycles.
+     */
+    pub fn concatenated_words(&self) -> Vec<usize> {
+        let mut entries = self.words.iter().collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.0.p.cmp(&b.0.p));
+        entries
+            .into_iter()
+            .flat_map(|(_, word)| word.iter().copied())
+            .collect()
+    }
+}
+
+/*
+ * Breadth first search from the identity for all targets at once, which stops as soon as every
+ * target is found or all words of max_depth generators have been enumerated.
+ */
+pub fn search_targets(
+    generators: &[Permutation],
+    targets: &[Permutation],
+    max_depth: usize,
+    visited: &VisitedConfig,
+) -> MultiTargetResult {
+    let mut remaining: HashSet<&Permutation> = targets.iter().collect();
+    let mut words = HashMap::new();
+    if let Some(identity) = targets.iter().find(|t| t.is_identity()) {
+        remaining.remove(identity);
+        words.insert(identity.clone(), Vec::new());
+    }
+    // The iterator stops after the first element of its max depth, so it gets one level more
+    if !remaining.is_empty() {
+        let iterator =
+            DepthLimitedPermutationGroupIterator::with_visited(generators, max_depth + 1, visited);
+        for (element, path) in iterator {
+            if path.len() > max_depth {
+                break;
+            }
+            if remaining.remove(&element) {
+                words.insert(element, path);
+                if remaining.is_empty() {
+                    break;
+                }
+            }
+        }
+    }
+    info!(
+        "Found {} of {} targets within depth {}",
+        words.len(),
+        targets.len(),
+        max_depth
+    );
+    let missing = targets
+        .iter()
+        .filter(|t| remaining.contains(t))
+        .cloned()
+        .collect();
+    MultiTargetResult { words, missing }
+}
+
 pub struct IterativeDeepeningGroupGenerator<'a> {
     frontier: VecDeque<(Permutation, Vec<usize>)>,
-    visited: bloomfilter::Bloom<Permutation>,
+    visited: VisitedSet,
     stack: Vec<(Permutation, Vec<usize>, usize)>,
     generators: &'a Vec<Permutation>,
     current_depth: usize,
@@ -299,7 +497,7 @@ impl<'a> IterativeDeepeningGroupGenerator<'a> {
 
         Self {
             frontier: VecDeque::new(),
-            visited: bloomfilter::Bloom::new_for_fp_rate(1_000_000, 0.0000001), // TODO adjust
+            visited: VisitedSet::new(&VisitedConfig::default()),
             stack: Vec::new(),
             generators,
             current_depth: 0,
@@ -360,7 +558,10 @@ impl<'a> Iterator for IterativeDeepeningGroupGenerator<'a> {
             return Some((element_perm, path));
         }
 
-        info!("Reached max depth of {} in Group Iterator", self.max_depth);
+        info!(
+            "Reached max depth of {} in Group Iterator, visited set: {}",
+            self.max_depth, self.visited
+        );
         None
     }
 }
@@ -371,7 +572,7 @@ impl<'a> Iterator for IterativeDeepeningGroupGenerator<'a> {
 
 pub struct IterativeDeepeningCompressed<'a> {
     frontier: VecDeque<(CompressedPermutation, Vec<usize>)>,
-    visited: bloomfilter::Bloom<CompressedPermutation>,
+    visited: VisitedSet,
     stack: Vec<(CompressedPermutation, Vec<usize>, usize)>,
     generators: &'a Vec<CompressedPermutation>,
     current_depth: usize,
@@ -381,13 +582,21 @@ pub struct IterativeDeepeningCompressed<'a> {
 
 impl<'a> IterativeDeepeningCompressed<'a> {
     pub fn new(generators: &'a Vec<CompressedPermutation>, max_depth: usize) -> Self {
+        Self::with_visited(generators, max_depth, &VisitedConfig::default())
+    }
+
+    pub fn with_visited(
+        generators: &'a Vec<CompressedPermutation>,
+        max_depth: usize,
+        visited: &VisitedConfig,
+    ) -> Self {
         let mut queue = VecDeque::new();
         let identity = CompressedPermutation::identity(generators[0].len());
         queue.push_back((identity, Vec::<usize>::new()));
 
         Self {
             frontier: VecDeque::new(),
-            visited: bloomfilter::Bloom::new_for_fp_rate(1_000_000, 0.0000001), // TODO adjust
+            visited: VisitedSet::new(visited),
             stack: Vec::new(),
             generators,
             current_depth: 0,
@@ -448,7 +657,10 @@ impl<'a> Iterator for IterativeDeepeningCompressed<'a> {
             return Some((element_perm, path));
         }
 
-        info!("Reached max depth of {} in Group Iterator", self.max_depth);
+        info!(
+            "Reached max depth of {} in Group Iterator, visited set: {}",
+            self.max_depth, self.visited
+        );
         None
     }
 }
@@ -571,6 +783,49 @@ mod depth_limited_permutation_group_iterator_tests {
         assert_eq!(iterator.next(), None);
     }
 
+    #[test]
+    fn test_depth_limited_compact_group_iterator() {
+        let generators = TestingUtils::get_s_n_generators(5);
+        let compact_generators = generators
+            .iter()
+            .map(|g| CompactPermutation::<u8>::try_from(g).unwrap())
+            .collect::<Vec<CompactPermutation<u8>>>();
+        let expected = DepthLimitedPermutationGroupIterator::new(&generators, 100);
+        let iterator = DepthLimitedCompactGroupIterator::new(&compact_generators, 100);
+        let mut counter = 0;
+        for ((perm, path), (compact_perm, compact_path)) in expected.zip(iterator) {
+            assert_eq!(Permutation::from(&compact_perm), perm);
+            assert_eq!(compact_path, path);
+            counter += 1;
+        }
+        assert_eq!(counter, 120);
+    }
+
+    #[test]
+    fn test_search_targets() {
+        let generators = TestingUtils::get_s_n_generators(5);
+        let targets = vec![
+            Permutation::new(vec![3, 2, 1, 4, 5]),
+            Permutation::identity(5),
+            Permutation::new(vec![5, 4, 3, 2, 1]),
+            Permutation::new(vec![2, 1, 3, 4, 5]),
+        ];
+        let result = search_targets(&generators, &targets, 3, &VisitedConfig::default());
+        assert!(!result.is_complete());
+        // Reversing 5 elements takes 10 adjacent transpositions
+        assert_eq!(result.missing, vec![targets[2].clone()]);
+        assert_eq!(result.words[&targets[0]].len(), 3);
+        assert_eq!(result.words[&targets[1]], Vec::<usize>::new());
+        assert_eq!(result.words[&targets[3]], vec![0]);
+        for (target, word) in result.words.iter() {
+            TestingUtils::assert_index_path_equals_permutation(word, target, &generators);
+        }
+
+        let result = search_targets(&generators, &targets, 10, &VisitedConfig::default());
+        assert!(result.is_complete());
+        assert_eq!(result.words[&targets[2]].len(), 10);
+    }
+
     #[test]
     fn test_depth_limited_permutation_group_iterator_larger() {
         let generators = TestingUtils::get_s_n_generators(5);
@@ -0,0 +1,399 @@
+use crate::config::Config;
+use crate::coordinate_calc::get_coords::get_pattern_facelets;
+use crate::parallel::{self, Deadline, SolveOptions};
+use crate::pattern_database::PatternDatabase;
+use crate::puzzle::{self, Puzzle, PuzzleType};
+use log::{debug, warn};
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+/*
+ * Lower bound on the number of moves that bring a state within the wildcards of the goal state.
+ * IDA* only finds shortest solutions with admissible heuristics, i.e. ones that never overestimate.
+ */
+pub trait Heuristic: Send + Sync {
+    fn estimate(&self, state: &[usize]) -> usize;
+}
+
+/*
+ * Number of facelets not showing the color of the goal state, beyond the wildcards, divided by the
+ * largest number of facelets a single move changes.
+ */
+pub struct MisplacedFacelets {
+    goal: Vec<usize>,
+    num_wildcards: usize,
+    max_support: usize,
+}
+
+impl MisplacedFacelets {
+    pub fn new(puzzle: &Puzzle) -> MisplacedFacelets {
+        let max_support = puzzle
+            .moves
+            .iter()
+            .map(|m| m.permutation.compress().support_size())
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        MisplacedFacelets {
+            goal: puzzle.goal_state.clone(),
+            num_wildcards: puzzle.num_wildcards,
+            max_support,
+        }
+    }
+}
+
+impl Heuristic for MisplacedFacelets {
+    fn estimate(&self, state: &[usize]) -> usize {
+        let wrong = state
+            .iter()
+            .zip(self.goal.iter())
+            .filter(|(a, b)| a != b)
+            .count();
+        wrong
+            .saturating_sub(self.num_wildcards)
+            .div_ceil(self.max_support)
+    }
+}
+
+/*
+ * Like MisplacedFacelets, but for each color of the goal state on its own: the facelets of the
+ * color not showing it, beyond the wildcards, divided by the most facelets of that color a single
+ * move changes. The largest of these bounds is much stronger than MisplacedFacelets when the moves
+ * change many facelets in total but few of each color.
+ */
+pub struct MisplacedColors {
+    // Color, its facelets in the goal state and the most of them a single move changes
+    colors: Vec<(usize, Vec<usize>, usize)>,
+    num_wildcards: usize,
+}
+
+impl MisplacedColors {
+    pub fn new(puzzle: &Puzzle) -> MisplacedColors {
+        let mut facelets_of: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (i, color) in puzzle.goal_state.iter().enumerate() {
+            facelets_of.entry(*color).or_default().push(i);
+        }
+        let mut colors = facelets_of
+            .into_iter()
+            .map(|(color, facelets)| {
+                let max_changed = puzzle
+                    .moves
+                    .iter()
+                    .map(|m| {
+                        facelets
+                            .iter()
+                            .filter(|i| m.permutation.p[**i] != **i + 1)
+                            .count()
+                    })
+                    .max()
+                    .unwrap_or(0)
+                    .max(1);
+                (color, facelets, max_changed)
+            })
+            .collect::<Vec<(usize, Vec<usize>, usize)>>();
+        colors.sort();
+        MisplacedColors {
+            colors,
+            num_wildcards: puzzle.num_wildcards,
+        }
+    }
+}
+
+impl Heuristic for MisplacedColors {
+    fn estimate(&self, state: &[usize]) -> usize {
+        self.colors
+            .iter()
+            .map(|(color, facelets, max_changed)| {
+                let wrong = facelets.iter().filter(|i| state[**i] != *color).count();
+                wrong
+                    .saturating_sub(self.num_wildcards)
+                    .div_ceil(*max_changed)
+            })
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+enum Outcome {
+    Found,
+    // Smallest estimated solution length above the bound, usize::MAX if there is none
+    Exceeded(usize),
+    Aborted,
+}
+
+/*
+ * Iterative deepening A* over the moves of a puzzle, which uses the largest estimate of its
+ * heuristics. Sequences are pruned if a move undoes the previous one, or if it commutes with the
+ * previous one and has a smaller index, as the sequence with the two moves swapped is tried anyway.
+ */
+pub struct IdaStar<'a> {
+    puzzle: &'a Puzzle,
+    heuristics: Vec<&'a dyn Heuristic>,
+    // Index of the inverse of every move, if it is a move
+    inverses: Vec<Option<usize>>,
+    commutes: Vec<Vec<bool>>,
+    nodes: usize,
+}
+
+impl<'a> IdaStar<'a> {
+    pub fn new(puzzle: &'a Puzzle, heuristics: Vec<&'a dyn Heuristic>) -> IdaStar<'a> {
+        let inverses = puzzle::move_inverses(&puzzle.moves);
+        let commutes = puzzle
+            .moves
+            .iter()
+            .map(|a| {
+                puzzle
+                    .moves
+                    .iter()
+                    .map(|b| {
+                        a.permutation.compose(&b.permutation)
+                            == b.permutation.compose(&a.permutation)
+                    })
+                    .collect()
+            })
+            .collect();
+        IdaStar {
+            puzzle,
+            heuristics,
+            inverses,
+            commutes,
+            nodes: 0,
+        }
+    }
+
+    // Whether the state is within the wildcards of the goal state
+    pub fn is_goal(&self, state: &[usize]) -> bool {
+        let wrong = state
+            .iter()
+            .zip(self.puzzle.goal_state.iter())
+            .filter(|(a, b)| a != b)
+            .count();
+        wrong <= self.puzzle.num_wildcards
+    }
+
+    fn estimate(&self, state: &[usize]) -> usize {
+        self.heuristics
+            .iter()
+            .map(|h| h.estimate(state))
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn is_pruned(&self, last: Option<usize>, m: usize) -> bool {
+        match last {
+            Some(last) => self.inverses[last] == Some(m) || (m < last && self.commutes[last][m]),
+            None => false,
+        }
+    }
+
+    fn search(
+        &mut self,
+        state: &[usize],
+        bound: usize,
+        path: &mut Vec<usize>,
+        deadline: &Deadline,
+    ) -> Outcome {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(1_000_000) && deadline.is_expired() {
+            return Outcome::Aborted;
+        }
+        let f = path.len() + self.estimate(state);
+        if f > bound {
+            return Outcome::Exceeded(f);
+        }
+        if self.is_goal(state) {
+            return Outcome::Found;
+        }
+        let mut min = usize::MAX;
+        for m in 0..self.puzzle.moves.len() {
+            if self.is_pruned(path.last().copied(), m) {
+                continue;
+            }
+            let new_state = self.puzzle.moves[m].permutation.apply(state);
+            path.push(m);
+            match self.search(&new_state, bound, path, deadline) {
+                Outcome::Found => return Outcome::Found,
+                Outcome::Aborted => return Outcome::Aborted,
+                Outcome::Exceeded(f) => min = min.min(f),
+            }
+            path.pop();
+        }
+        Outcome::Exceeded(min)
+    }
+
+    /*
+     * Returns a shortest solution (move indices) with at most max_bound moves, if the heuristics
+     * are admissible. Gives up once the deadline has expired.
+     */
+    pub fn solve(&mut self, max_bound: usize, deadline: &Deadline) -> Option<Vec<usize>> {
+        let state = self.puzzle.initial_state.clone();
+        let mut bound = self.estimate(&state);
+        while bound <= max_bound {
+            let mut path = Vec::new();
+            match self.search(&state, bound, &mut path, deadline) {
+                Outcome::Found => return Some(path),
+                Outcome::Aborted => return None,
+                Outcome::Exceeded(usize::MAX) => return None,
+                Outcome::Exceeded(f) => bound = f,
+            }
+            debug!(
+                "IDA* of puzzle {} raised its bound to {} after {} nodes",
+                self.puzzle.id, bound, self.nodes
+            );
+        }
+        None
+    }
+}
+
+/*
+ * Pattern databases of every puzzle type and goal state among the puzzles without wildcards,
+ * projected onto the facelets chosen by coordinate_calc. They are cached in the pattern databases
+ * directory and only built if they are not there yet.
+ */
+pub fn load_pattern_databases(
+    puzzles: &[Puzzle],
+    config: &Config,
+) -> HashMap<(PuzzleType, Vec<usize>), PatternDatabase> {
+    if config.ida.pattern_size == 0 {
+        return HashMap::new();
+    }
+    let databases_path = config.paths.resolve(&config.paths.pattern_databases);
+    let mut representatives: HashMap<(PuzzleType, Vec<usize>), &Puzzle> = HashMap::new();
+    for puzzle in puzzles.iter().filter(|p| p.num_wildcards == 0) {
+        representatives
+            .entry((puzzle.puzzle_type.clone(), puzzle.goal_state.clone()))
+            .or_insert(puzzle);
+    }
+    representatives
+        .into_par_iter()
+        .filter_map(|(key, puzzle)| {
+            let facelets = get_pattern_facelets(&puzzle.puzzle_type);
+            match PatternDatabase::load_or_build(
+                &databases_path,
+                puzzle,
+                &facelets,
+                config.ida.pattern_size,
+            ) {
+                Ok(database) => Some((key, database)),
+                Err(e) => {
+                    warn!(
+                        "No pattern database for puzzle type {:?}: {}",
+                        puzzle.puzzle_type, e
+                    );
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/*
+ * Solves puzzles of every type with IDA*, using the misplaced facelets in total and per color and,
+ * for puzzles without wildcards, a pattern database of their goal state.
+ */
+pub fn solve_puzzles(
+    puzzles: &[Puzzle],
+    config: &Config,
+    options: &SolveOptions,
+) -> HashMap<usize, String> {
+    let databases = load_pattern_databases(puzzles, config);
+    parallel::solve_in_parallel(puzzles, options, |puzzle, deadline| {
+        let misplaced = MisplacedFacelets::new(puzzle);
+        let misplaced_colors = MisplacedColors::new(puzzle);
+        let mut heuristics: Vec<&dyn Heuristic> = vec![&misplaced, &misplaced_colors];
+        if puzzle.num_wildcards == 0 {
+            let key = (puzzle.puzzle_type.clone(), puzzle.goal_state.clone());
+            if let Some(database) = databases.get(&key) {
+                heuristics.push(database);
+            }
+        }
+        let mut ida = IdaStar::new(puzzle, heuristics);
+        let result = ida.solve(config.ida.max_bound, deadline)?;
+        parallel::log_solved(puzzle, result.len());
+        debug!("Searched {} nodes for puzzle {}", ida.nodes, puzzle.id);
+        Some(puzzle::word_to_string(&puzzle.moves, &result))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing_utils::TestingUtils;
+    use crate::validation::validate_solution;
+
+    fn get_puzzle(initial_state: Vec<usize>, num_wildcards: usize) -> Puzzle {
+        let moves = TestingUtils::moves_from_cycles(
+            &[("a", "(1,2,3,4)"), ("b", "(3,4,5,6)"), ("c", "(7,8)")],
+            8,
+        );
+        TestingUtils::puzzle_from_moves(
+            moves,
+            initial_state,
+            vec![1, 2, 3, 4, 5, 6, 7, 8],
+            num_wildcards,
+        )
+    }
+
+    fn scramble(word: &[usize]) -> Puzzle {
+        let mut puzzle = get_puzzle(vec![1, 2, 3, 4, 5, 6, 7, 8], 0);
+        for m in word {
+            puzzle.initial_state = puzzle.moves[*m].permutation.apply(&puzzle.initial_state);
+        }
+        puzzle
+    }
+
+    #[test]
+    fn test_ida_star_finds_shortest_solutions() {
+        for word in [vec![], vec![1], vec![1, 3, 5], vec![1, 1, 3, 4, 3, 0]] {
+            let puzzle = scramble(&word);
+            let misplaced = MisplacedFacelets::new(&puzzle);
+            let misplaced_colors = MisplacedColors::new(&puzzle);
+            let database = PatternDatabase::build(&puzzle, &vec![0, 1, 2, 3], 2000).unwrap();
+            assert_eq!(database.num_values(), 4);
+            let mut ida = IdaStar::new(&puzzle, vec![&misplaced, &misplaced_colors, &database]);
+            let solution = ida.solve(10, &Deadline::unlimited()).unwrap();
+            // The same solver without heuristics is a plain iterative deepening search
+            let mut plain = IdaStar::new(&puzzle, vec![]);
+            let shortest = plain.solve(10, &Deadline::unlimited()).unwrap();
+            assert_eq!(solution.len(), shortest.len());
+            let names = puzzle::word_to_string(&puzzle.moves, &solution);
+            assert_eq!(validate_solution(&puzzle, &names), Ok(solution.len()));
+        }
+    }
+
+    #[test]
+    fn test_ida_star_with_wildcards() {
+        let puzzle = get_puzzle(vec![2, 1, 3, 4, 5, 6, 8, 7], 2);
+        let misplaced = MisplacedFacelets::new(&puzzle);
+        assert_eq!(misplaced.estimate(&puzzle.initial_state), 1);
+        let mut ida = IdaStar::new(&puzzle, vec![&misplaced]);
+        // Swapping 7 and 8 leaves the two wildcards for 1 and 2
+        let solution = ida.solve(5, &Deadline::unlimited()).unwrap();
+        assert_eq!(solution.len(), 1);
+        let name = puzzle.moves[solution[0]].name.as_str();
+        assert_eq!(validate_solution(&puzzle, name), Ok(1));
+        assert!(ida.solve(0, &Deadline::unlimited()).is_none());
+    }
+
+    #[test]
+    fn test_misplaced_colors() {
+        // The long cycle changes six facelets, but only ones of the second color
+        let moves = TestingUtils::moves_from_cycles(
+            &[("a", "(1,3)"), ("b", "(3,4,5,6,7,8)"), ("c", "(2,4)")],
+            8,
+        );
+        let goal = vec![1, 1, 2, 2, 2, 2, 2, 2];
+        let initial = vec![2, 2, 1, 1, 2, 2, 2, 2];
+        let puzzle = TestingUtils::puzzle_from_moves(moves, initial, goal, 0);
+        let misplaced = MisplacedFacelets::new(&puzzle);
+        let misplaced_colors = MisplacedColors::new(&puzzle);
+        assert_eq!(misplaced.estimate(&puzzle.initial_state), 1);
+        assert_eq!(misplaced_colors.estimate(&puzzle.initial_state), 2);
+        assert_eq!(misplaced_colors.estimate(&puzzle.goal_state), 0);
+        let mut ida = IdaStar::new(&puzzle, vec![&misplaced_colors]);
+        let solution = ida.solve(5, &Deadline::unlimited()).unwrap();
+        assert_eq!(solution.len(), 2);
+        let names = puzzle::word_to_string(&puzzle.moves, &solution);
+        assert_eq!(validate_solution(&puzzle, &names), Ok(2));
+    }
+}
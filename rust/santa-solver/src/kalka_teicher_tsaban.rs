@@ -1,10 +1,13 @@
 use crate::groups::PermutationGroupIterator;
-use crate::permutation::{Permutation, PermutationIndex, PermutationInfo, PermutationPath};
-use crate::testing_utils::TestingUtils;
+use crate::parallel::Deadline;
+use crate::permutation::{
+    find_parity_word, get_orbits, orbit_parities, Permutation, PermutationIndex, PermutationInfo,
+    PermutationPath,
+};
 use log::{debug, info, warn};
 use std::collections::{HashMap, HashSet};
 
-fn to_2_cycle(p: &PermutationInfo) -> Vec<Vec<usize>> {
+pub fn to_2_cycle(p: &PermutationInfo) -> Vec<Vec<usize>> {
     let cycles = &p.cycles; // disjoint cycles of arbitrary length
                             // Express p as a product of disjoint 2-cycles and fixed points
     let mut result = Vec::new();
@@ -23,14 +26,33 @@ fn to_2_cycle(p: &PermutationInfo) -> Vec<Vec<usize>> {
     result
 }
 
-fn to_3_cycle(p: &PermutationInfo) -> Vec<Vec<usize>> {
+pub fn to_3_cycle(p: &PermutationInfo) -> Vec<Vec<usize>> {
     if !p.signum {
         panic!("Permutation must be even");
     }
-    let _cycles = &p.cycles; // disjoint cycles of arbitrary length
-                             // Express p as a product of disjoint 3-cycles and fixed points
-                             // TODO: implement for better results
-    to_2_cycle(p)
+    // Express p as a product of 3-cycles (in the order they are applied) by pairing up the
+    // transpositions of the 2-cycle decomposition. A pair (x a), (x b) sharing the point x is
+    // the 3-cycle (x a b). A disjoint pair (a b), (c d) is first rewritten as
+    // (a b), (b c), (b c), (c d), which gives the 3-cycles (b a c) and (c b d).
+    let transpositions = to_2_cycle(p);
+    let mut result = Vec::new();
+    for pair in transpositions.chunks(2) {
+        let (first, second) = (&pair[0], &pair[1]);
+        if let Some(x) = first.iter().find(|x| second.contains(x)) {
+            let a = if first[0] == *x { first[1] } else { first[0] };
+            let b = if second[0] == *x {
+                second[1]
+            } else {
+                second[0]
+            };
+            result.push(vec![*x, a, b]);
+        } else {
+            let (a, b, c, d) = (first[0], first[1], second[0], second[1]);
+            result.push(vec![b, a, c]);
+            result.push(vec![c, b, d]);
+        }
+    }
+    result
 }
 
 pub fn find_c_cycle(
@@ -38,7 +60,20 @@ pub fn find_c_cycle(
     c: usize,
     n: usize,
 ) -> Option<(PermutationPath, Permutation)> {
-    let generator = PermutationGroupIterator::new(&gen_to_str);
+    find_c_cycle_in_orbit(gen_to_str, c, n, None)
+}
+
+/*
+ * Finds a c-cycle whose points (1-indexed) all lie in the given orbit. Conjugation never leaves
+ * an orbit, so every orbit the target moves needs its own c-cycle.
+ */
+pub fn find_c_cycle_in_orbit(
+    gen_to_str: &HashMap<Permutation, PermutationIndex>,
+    c: usize,
+    n: usize,
+    orbit: Option<&HashSet<usize>>,
+) -> Option<(PermutationPath, Permutation)> {
+    let generator = PermutationGroupIterator::new(gen_to_str);
     let mut i = 0;
     for (mut tau_path, tau) in generator {
         i += 1;
@@ -53,7 +88,9 @@ pub fn find_c_cycle(
                 let mut found = false;
                 for cycle in tau_cycles {
                     if cycle.len() > 1 {
-                        if cycle.len() == c {
+                        if cycle.len() == c
+                            && orbit.is_none_or(|o| cycle.iter().all(|x| o.contains(x)))
+                        {
                             if found {
                                 continue 'inner;
                             } else {
@@ -149,18 +186,39 @@ pub fn generate_cycles(
     mu: &Permutation,
     mu_path: &PermutationPath,
     n: usize,
+) -> HashMap<Permutation, PermutationPath> {
+    generate_cycles_until(
+        gen_to_index,
+        mu,
+        mu_path,
+        n,
+        &HashSet::new(),
+        &Deadline::unlimited(),
+    )
+}
+
+/*
+ * Like generate_cycles, but stops as soon as all permutations in required have been found.
+ * The conjugates are generated breadth first, so each path is a shortest conjugation of mu.
+ * Also stops with what was found so far once the deadline expires.
+ */
+pub fn generate_cycles_until(
+    gen_to_index: &HashMap<Permutation, PermutationIndex>,
+    mu: &Permutation,
+    mu_path: &PermutationPath,
+    n: usize,
+    required: &HashSet<Permutation>,
+    deadline: &Deadline,
 ) -> HashMap<Permutation, PermutationPath> {
     let mut a_0: HashMap<Permutation, PermutationPath> = HashMap::new(); // A_{l-1}, previous iteration
     let mut a_l: HashMap<Permutation, PermutationPath> = HashMap::new(); // A_l, current iteration
     let mut a_union: HashMap<Permutation, PermutationPath> = HashMap::new(); // a_0 union A_1 union ... union A_l
     a_0.insert(mu.clone(), mu_path.clone());
-    let generators = &gen_to_index
-        .keys()
-        .map(|x| x.clone())
-        .collect::<Vec<Permutation>>();
+    a_union.insert(mu.clone(), mu_path.clone());
+    let generators = gen_to_index.keys().cloned().collect::<Vec<Permutation>>();
     loop {
         a_l.clear();
-        for gen in generators {
+        for gen in &generators {
             let s_i = gen;
             let s_i_inv = &s_i.inverse();
             let s_i_path = gen_to_index.get(s_i).unwrap();
@@ -238,11 +296,6 @@ pub fn generate_cycles(
                     al_path.push(*s_i_inv_path);
                     al_path.merge(a_path);
                     al_path.push(*s_i_path);
-                    TestingUtils::assert_index_path_equals_permutation_using_hashmap(
-                        &al_path.arr,
-                        &perm_eps_neg,
-                        gen_to_index,
-                    );
                     a_l.insert(perm_eps_neg, al_path);
                 }
             }
@@ -256,6 +309,13 @@ pub fn generate_cycles(
         // Move items out of a_l into a_union
         a_union.extend(a_l.clone());
         std::mem::swap(&mut a_0, &mut a_l);
+        if !required.is_empty() && required.iter().all(|c| a_union.contains_key(c)) {
+            return a_union;
+        }
+        if deadline.is_expired() {
+            info!("Deadline expired after finding {} elements", a_union.len());
+            return a_union;
+        }
         if a_union.len() > n {
             info!("Aborting after finding {} elements", a_union.len());
             // for (perm, path) in a_union.iter() {
@@ -270,30 +330,120 @@ pub fn generate_cycles(
     }
 }
 
+/*
+ * Factorizes the target into the generators. At most conjugate_limit conjugates of a c-cycle are
+ * generated per orbit, and the search gives up once the deadline expires.
+ */
 pub fn factorize(
     gen_to_idx: &HashMap<Permutation, PermutationIndex>,
     gen_to_str: Vec<String>,
     target: &Permutation,
+    conjugate_limit: usize,
+    deadline: &Deadline,
 ) -> Option<String> {
+    let n = target.len();
+    let generators = gen_to_idx.keys().cloned().collect::<Vec<Permutation>>();
+    let orbit_of = get_orbits(&generators);
+    let mut all_orbits = orbit_of.clone();
+    all_orbits.sort();
+    all_orbits.dedup();
+
+    // Conjugates of a cycle never leave its orbit, and odd restrictions often only come together
+    // (e.g. a corner swap together with an edge swap of a cube). So a short parity word makes
+    // every restriction even first, as the cycle library does, and the rest
+    // target * prefix^-1 is factorized orbit by orbit with 3-cycles of that orbit.
+    let index_to_gen = gen_to_idx
+        .iter()
+        .map(|(perm, index)| (*index, perm))
+        .collect::<HashMap<PermutationIndex, &Permutation>>();
+    let mut sorted_gens = index_to_gen
+        .iter()
+        .map(|(index, perm)| (*index, *perm))
+        .collect::<Vec<(PermutationIndex, &Permutation)>>();
+    sorted_gens.sort_by_key(|(index, _)| *index);
+    let mut result = PermutationPath::default();
+    let mut rest = target.clone();
+    let parities = orbit_parities(target, &orbit_of, &all_orbits);
+    if parities.iter().any(|odd| *odd) {
+        let gen_parities = sorted_gens
+            .iter()
+            .map(|(_, perm)| orbit_parities(perm, &orbit_of, &all_orbits))
+            .collect::<Vec<Vec<bool>>>();
+        let Some(word) = find_parity_word(&gen_parities, &parities, deadline) else {
+            warn!("No word with the parities of the target found");
+            return None;
+        };
+        let mut prefix = Permutation::identity(n);
+        for position in &word {
+            let (index, perm) = sorted_gens[*position];
+            prefix = perm.compose(&prefix);
+            result.push(index);
+        }
+        rest = target.compose(&prefix.inverse());
+    }
+
+    let mut orbits = (0..n)
+        .filter(|x| rest.p[*x] - 1 != *x)
+        .map(|x| orbit_of[x])
+        .collect::<Vec<usize>>();
+    orbits.sort();
+    orbits.dedup();
+    for orbit in &orbits {
+        let mut p = (1..=n).collect::<Vec<usize>>();
+        for x in (0..n).filter(|x| orbit_of[*x] == *orbit) {
+            p[x] = rest.p[x];
+        }
+        let restriction = Permutation::new(p);
+        let orbit_points = (0..n)
+            .filter(|x| orbit_of[*x] == *orbit)
+            .map(|x| x + 1)
+            .collect::<HashSet<usize>>();
+        let path = factorize_in_orbit(
+            gen_to_idx,
+            &restriction,
+            &orbit_points,
+            conjugate_limit,
+            deadline,
+        )?;
+        result.merge(&path);
+    }
+
+    // Replay the word, a wrong one is reported instead of returned
+    let mut product = Permutation::identity(n);
+    for index in &result.arr {
+        let Some(perm) = index_to_gen.get(index) else {
+            warn!("Factorization uses the unknown generator {}", index);
+            return None;
+        };
+        product = perm.compose(&product);
+    }
+    if product != *target {
+        warn!("Factorization does not give the target");
+        return None;
+    }
+    Some(result.to_string(&gen_to_str))
+}
+
+fn factorize_in_orbit(
+    gen_to_idx: &HashMap<Permutation, PermutationIndex>,
+    target: &Permutation,
+    orbit: &HashSet<usize>,
+    conjugate_limit: usize,
+    deadline: &Deadline,
+) -> Option<PermutationPath> {
     let permutation_info = target.compute_info();
-    let generators = &gen_to_idx
-        .keys()
-        .map(|x| x.clone())
-        .collect::<Vec<Permutation>>();
     let n = target.len();
+    // Odd permutations are products of 2-cycles, even permutations products of 3-cycles
     let c = match permutation_info.signum {
         false => 2,
         true => 3,
     };
-    if c != 2 {
-        panic!("3-cycles are not implemented yet.");
-    }
 
     // Step 1: Find a short c-cycle in the group generated by generators
-    let (mu_path, mu) = match find_c_cycle(&gen_to_idx, c, n) {
+    let (mu_path, mu) = match find_c_cycle_in_orbit(gen_to_idx, c, n, Some(orbit)) {
         Some((mu_path, mu)) => (mu_path, mu),
         None => {
-            eprintln!("No short c-cycle found");
+            warn!("No short {}-cycle found", c);
             return None;
         }
     };
@@ -302,103 +452,43 @@ pub fn factorize(
         mu, mu_path
     );
 
-    // TestingUtils::assert_index_path_equals_permutation_using_hashmap(
-    //     &mu_path.arr,
-    //     &mu,
-    //     &gen_to_idx,
-    // );
-    info!("We have asserted that the path results in this mu");
-
-    // disjoint cycles of length c
+    // c-cycles whose product is the target, in the order they are applied
     let c_group = match permutation_info.signum {
         false => to_2_cycle(&permutation_info),
         true => to_3_cycle(&permutation_info),
     };
+    let c_perms = c_group
+        .iter()
+        .map(|cycle| Permutation::from_cycles_fixed_per_size(&vec![cycle.clone()], n))
+        .collect::<Vec<Permutation>>();
+    let c_set = c_perms.iter().cloned().collect::<HashSet<Permutation>>();
 
-    // convert C to a set of permutations
-    let mut c_set = HashSet::new();
-    for cycle in c_group {
-        c_set.insert(Permutation::from_cycles_fixed_per_size(&vec![cycle], n));
+    // Step 2: Find short expressions for additional c-cycles by conjugating mu
+    let a_union =
+        generate_cycles_until(gen_to_idx, &mu, &mu_path, conjugate_limit, &c_set, deadline);
+    let missing = c_set.iter().filter(|c| !a_union.contains_key(c)).count();
+    if missing > 0 {
+        warn!(
+            "{} of {} {}-cycles are not conjugate to mu",
+            missing,
+            c_set.len(),
+            c
+        );
+        debug!("a_union length: {:?}", a_union.len());
+        return None;
     }
 
-    // Step 2: Find short expressions for additional c-cycles
-    let mut a_0: HashMap<Permutation, PermutationPath> = HashMap::new(); // A_{l-1}, previous iteration
-    let mut a_l: HashMap<Permutation, PermutationPath> = HashMap::new(); // A_l, current iteration
-    let mut a_union: HashMap<Permutation, PermutationPath> = HashMap::new(); // a_0 union A_1 union ... union A_l
-    a_0.insert(mu, mu_path);
-    loop {
-        a_l.clear();
-        for gen in generators {
-            let s_i = gen;
-            let s_i_inv = &s_i.inverse();
-            let s_i_path = gen_to_idx.get(s_i).unwrap();
-            let s_i_inv_path = gen_to_idx.get(s_i_inv).unwrap();
-            for (a, a_path) in &a_0 {
-                // calculate s_i^eps * a * s_i^-eps and check membership
-                let perm_eps_pos = s_i_inv.compose(&a.compose(s_i));
-                let perm_eps_neg = s_i.compose(&a.compose(s_i_inv));
-
-                // A_union = (a_0 union a_1 union ... union A_{l-1}) at this point
-                if !a_union.contains_key(&perm_eps_pos) && !a_l.contains_key(&perm_eps_pos) {
-                    // Is the a_l check necessary?
-                    let mut al_path = PermutationPath::default();
-                    al_path.push(*s_i_inv_path);
-                    al_path.merge(a_path);
-                    al_path.push(*s_i_path);
-                    a_l.insert(perm_eps_pos, al_path);
-                }
-                if !a_union.contains_key(&perm_eps_neg) && !a_l.contains_key(&perm_eps_neg) {
-                    let mut al_path = PermutationPath::default();
-                    al_path.push(*s_i_path);
-                    al_path.merge(a_path);
-                    al_path.push(*s_i_inv_path);
-                    a_l.insert(perm_eps_neg, al_path);
-                }
-            }
-        }
-        // check if we could find elements in next iteration
-        if a_l.is_empty() {
-            warn!("Error: A_l is empty");
-            debug!("a_union length: {:?}", a_union.len());
-            debug!("c_set length: {:?}", c_set.len());
-            // Print the number of elements in c_set that are not in a_union
-            let mut missing = 0;
-            for c in &c_set {
-                if !a_union.contains_key(c) {
-                    missing += 1;
-                }
-            }
-            debug!("missing: {:?}", missing);
-            return None;
-        }
-        // add A_{l} to A_union by extending A_union
-        // reassign s.t. we can reach A_{l} in next iteration A_{l+1}
-        // Move items out of a_l into a_union
-        a_union.extend(a_l.clone());
-        std::mem::swap(&mut a_0, &mut a_l);
-        // Count the elements from c_set missing in a_union
-        let mut missing = 0;
-        for c in &c_set {
-            if !a_union.contains_key(c) {
-                missing += 1;
-            }
-        }
-        if missing == 0 {
-            // We have found a short expression for all c-cycles
-            // Return a_union
-            let result = a_union.get(target).unwrap();
-            TestingUtils::assert_index_path_equals_permutation_using_hashmap(
-                &result.arr,
-                &target,
-                &gen_to_idx,
-            );
-            return Some(result.to_string(&gen_to_str));
-        }
+    // Step 3: Concatenate the paths of the c-cycles
+    let mut result = PermutationPath::default();
+    for c_perm in &c_perms {
+        result.merge(a_union.get(c_perm).unwrap());
     }
+    Some(result)
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::config::SearchConfig;
     use crate::testing_utils::TestingUtils;
 
     use super::*;
@@ -475,7 +565,14 @@ mod tests {
 
         let gen_to_idx = TestingUtils::get_generator_to_perm_index_map_s_n(3);
         let index_to_gen_name = vec!["gen1".to_string(), "gen2".to_string()];
-        let result = factorize(&gen_to_idx, index_to_gen_name, &target).unwrap();
+        let result = factorize(
+            &gen_to_idx,
+            index_to_gen_name,
+            &target,
+            SearchConfig::default().conjugate_limit,
+            &Deadline::unlimited(),
+        )
+        .unwrap();
         debug!("result: {:?}", result);
         // check if result is correct (factorization results in target permutation)
         let mut result_perm = Permutation::identity(3);
@@ -522,8 +619,117 @@ mod tests {
 
         let target = Permutation::new(vec![1, 2, 3, 4, 5, 6, 7, 9, 8, 10]);
 
-        let result = factorize(&gen_to_idx, index_to_gen_name, &target).unwrap();
+        let result = factorize(
+            &gen_to_idx,
+            index_to_gen_name,
+            &target,
+            SearchConfig::default().conjugate_limit,
+            &Deadline::unlimited(),
+        )
+        .unwrap();
 
         TestingUtils::assert_permutation_equals_operation_string(&target, result, str_to_gen);
     }
+
+    #[test]
+    fn test_to_3_cycle() {
+        let targets = vec![
+            "(1,2,3)",
+            "(1,2)(3,4)",
+            "(1,2,3,4,5)",
+            "(1,2)(3,4,5,6)",
+            "(1,4)(2,7)(3,5,8)",
+            "(1,4)(2,7)(3,5)(6,8)",
+        ];
+        for target_str in targets {
+            let target = Permutation::parse_permutation_from_cycle(target_str, 8);
+            let mut result = Permutation::identity(8);
+            for cycle in to_3_cycle(&target.compute_info()) {
+                assert_eq!(cycle.len(), 3);
+                let cycle_perm = Permutation::from_cycles_fixed_per_size(&vec![cycle], 8);
+                result = cycle_perm.compose(&result);
+            }
+            assert_eq!(result, target);
+        }
+    }
+
+    #[test]
+    fn factorize_even() {
+        let generators = TestingUtils::get_s_n_generators(6);
+        let mut str_to_gen: HashMap<String, Permutation> = HashMap::new();
+        let mut index_to_gen_name = vec![];
+        for (i, generator) in generators.iter().enumerate() {
+            str_to_gen.insert(format!("gen{}", i), generator.clone());
+            index_to_gen_name.push(format!("gen{}", i));
+        }
+        let gen_to_idx = TestingUtils::get_generator_to_perm_index_map_s_n(6);
+        for target_str in vec!["(1,2,3)", "(1,2)(5,6)", "(1,3,5,2,4)"] {
+            let target = Permutation::parse_permutation_from_cycle(target_str, 6);
+            let result = factorize(
+                &gen_to_idx,
+                index_to_gen_name.clone(),
+                &target,
+                SearchConfig::default().conjugate_limit,
+                &Deadline::unlimited(),
+            )
+            .unwrap();
+            TestingUtils::assert_permutation_equals_operation_string(
+                &target,
+                result,
+                str_to_gen.clone(),
+            );
+        }
+    }
+
+    #[test]
+    fn factorize_orbits() {
+        // S_3 on each of the orbits {1, 2, 3} and {4, 5, 6}
+        let names = ["s", "c", "-c", "t", "d", "-d"];
+        let cycles = ["(1,2)", "(1,2,3)", "(1,3,2)", "(4,5)", "(4,5,6)", "(4,6,5)"];
+        let mut str_to_gen: HashMap<String, Permutation> = HashMap::new();
+        let mut gen_to_idx: HashMap<Permutation, usize> = HashMap::new();
+        for (i, (name, cycle)) in names.iter().zip(cycles.iter()).enumerate() {
+            let perm = Permutation::parse_permutation_from_cycle(cycle, 6);
+            str_to_gen.insert(name.to_string(), perm.clone());
+            gen_to_idx.insert(perm, i);
+        }
+        let index_to_gen_name = names.iter().map(|name| name.to_string()).collect();
+        // Both restrictions are even, so each orbit is factorized with its own 3-cycles
+        let target = Permutation::parse_permutation_from_cycle("(1,2,3)(4,6,5)", 6);
+        let result = factorize(
+            &gen_to_idx,
+            index_to_gen_name,
+            &target,
+            SearchConfig::default().conjugate_limit,
+            &Deadline::unlimited(),
+        )
+        .unwrap();
+        TestingUtils::assert_permutation_equals_operation_string(&target, result, str_to_gen);
+    }
+
+    #[test]
+    fn factorize_coupled_parity() {
+        // The swap is the only odd generator and swaps in both orbits at once
+        let names = ["w", "c", "-c", "d", "-d"];
+        let cycles = ["(1,2)(4,5)", "(1,2,3)", "(1,3,2)", "(4,5,6)", "(4,6,5)"];
+        let mut str_to_gen: HashMap<String, Permutation> = HashMap::new();
+        let mut gen_to_idx: HashMap<Permutation, usize> = HashMap::new();
+        for (i, (name, cycle)) in names.iter().zip(cycles.iter()).enumerate() {
+            let perm = Permutation::parse_permutation_from_cycle(cycle, 6);
+            str_to_gen.insert(name.to_string(), perm.clone());
+            gen_to_idx.insert(perm, i);
+        }
+        let index_to_gen_name = names.iter().map(|name| name.to_string()).collect();
+        // Both restrictions are odd, so the swap has to fix their parity first
+        let target = Permutation::parse_permutation_from_cycle("(1,3)(5,6)", 6);
+        let result = factorize(
+            &gen_to_idx,
+            index_to_gen_name,
+            &target,
+            SearchConfig::default().conjugate_limit,
+            &Deadline::unlimited(),
+        )
+        .unwrap();
+        TestingUtils::assert_permutation_equals_operation_string(&target, result, str_to_gen);
+    }
 }
@@ -1,78 +1,139 @@
-use crate::kalka_teicher_tsaban::factorize;
-use crate::permutation::{get_permutation, Permutation, PermutationIndex};
+use crate::config::Config;
+use crate::cycle_library::CycleLibrary;
+use crate::parallel::{self, Deadline, SolveOptions};
+use crate::permutation::Permutation;
 use crate::puzzle::{Puzzle, PuzzleType};
+use crate::schreier::StabilizerChain;
+use crate::target_chooser;
 use log::{info, warn};
+use rayon::prelude::*;
 use std::collections::HashMap;
 
-pub fn solve_puzzles(puzzles: &Vec<Puzzle>) -> HashMap<usize, String> {
-    let mut results = HashMap::new();
+/*
+ * Cycle library of every puzzle type, loaded from (or stored to) the cycle_libraries path of the
+ * config. Libraries of different types are built in parallel.
+ */
+pub(crate) fn load_libraries(
+    puzzles: &Vec<Puzzle>,
+    config: &Config,
+) -> HashMap<PuzzleType, CycleLibrary> {
+    let library_path = config.paths.resolve(&config.paths.cycle_libraries);
+    let mut moves_by_type: HashMap<&PuzzleType, &Puzzle> = HashMap::new();
     for puzzle in puzzles {
-        // Determine whether the puzzle only has unique elements and no wildcards
-        /*if puzzle.num_wildcards > 0 {
-            continue;
-        } */
-        if puzzle.puzzle_type != PuzzleType::GLOBE(3, 4) {
-            continue;
-        }
-        if puzzle
-            .initial_state
-            .iter()
-            .collect::<std::collections::HashSet<_>>()
-            .len()
-            != puzzle.initial_state.len()
-        {
-            continue;
-        }
-        let target = get_permutation(&puzzle.initial_state, &puzzle.goal_state);
-        let target_info = target.compute_info();
-        if target_info.signum {
-            continue;
+        moves_by_type.entry(&puzzle.puzzle_type).or_insert(puzzle);
+    }
+    moves_by_type
+        .into_par_iter()
+        .map(|(puzzle_type, puzzle)| {
+            let library = CycleLibrary::load_or_build(
+                &library_path,
+                puzzle_type,
+                &puzzle.moves,
+                &config.search,
+            );
+            (puzzle_type.clone(), library)
+        })
+        .collect()
+}
+
+/*
+ * Stabilizer chains to choose the targets with, for every puzzle type with a non-empty library.
+ * The search chain depends on the goal colors, which are shared by most puzzles of a type.
+ */
+pub(crate) fn build_search_chains(
+    puzzles: &Vec<Puzzle>,
+    libraries: &HashMap<PuzzleType, CycleLibrary>,
+) -> HashMap<(PuzzleType, String), StabilizerChain> {
+    let mut chain_puzzles: HashMap<(&PuzzleType, &String), &Puzzle> = HashMap::new();
+    for puzzle in puzzles {
+        if !libraries[&puzzle.puzzle_type].cycles.is_empty() {
+            chain_puzzles
+                .entry((&puzzle.puzzle_type, &puzzle.goal_string))
+                .or_insert(puzzle);
         }
+    }
+    chain_puzzles
+        .into_par_iter()
+        .map(|((puzzle_type, goal_string), puzzle)| {
+            info!("Building stabilizer chain for {}...", puzzle_type);
+            let chain = target_chooser::build_search_chain(&puzzle.moves, goal_string);
+            ((puzzle_type.clone(), goal_string.clone()), chain)
+        })
+        .collect()
+}
+
+/*
+ * Target of the puzzle in the group generated by its moves, None if its type has no chain or the
+ * target is not in the group.
+ */
+pub(crate) fn choose_target(
+    chains: &HashMap<(PuzzleType, String), StabilizerChain>,
+    puzzle: &Puzzle,
+    config: &Config,
+    deadline: &Deadline,
+) -> Option<Permutation> {
+    let chain = chains.get(&(puzzle.puzzle_type.clone(), puzzle.goal_string.clone()))?;
+    let target =
+        target_chooser::choose_target(chain, puzzle, config.search.target_node_limit, deadline);
+    if target.is_none() {
+        warn!(
+            "Target of puzzle {} is not an element of the group generated by its moves, skipping",
+            puzzle.id
+        );
+    }
+    target
+}
+
+/*
+ * A word for the target describes the inverse of the state change of its moves, so the solution
+ * applies the inverse of every move in the same order.
+ */
+pub(crate) fn solution_string(library: &CycleLibrary, word: &[usize]) -> String {
+    word.iter()
+        .map(|i| {
+            let name = &library.move_names[*i];
+            match name.strip_prefix('-') {
+                Some(name) => name.to_string(),
+                None => format!("-{}", name),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(".")
+}
+
+/*
+ * Solves puzzles of any type by writing their target as a product of 2-cycles and 3-cycles from
+ * the cycle library of the puzzle type. Libraries are loaded from (or stored to) the
+ * cycle_libraries path of the config.
+ * The libraries and stabilizer chains are built once per type before the puzzles are solved in
+ * parallel.
+ */
+pub fn solve_puzzles(
+    puzzles: &Vec<Puzzle>,
+    config: &Config,
+    options: &SolveOptions,
+) -> HashMap<usize, String> {
+    let libraries = load_libraries(puzzles, config);
+    let chains = build_search_chains(puzzles, &libraries);
+
+    parallel::solve_in_parallel(puzzles, options, |puzzle, deadline| {
+        let library = &libraries[&puzzle.puzzle_type];
+        let target = choose_target(&chains, puzzle, config, deadline)?;
         info!(
             "Solving puzzle {} of type {:?}",
             puzzle.id, puzzle.puzzle_type
         );
-        // TODO: Move map to puzzle types
-        let mut generator_names: HashMap<Permutation, String> = HashMap::new();
-        for move_ in puzzle.moves.iter() {
-            if generator_names.contains_key(&move_.permutation) {
+        let word = match library.factorize(&puzzle.moves, &target, deadline) {
+            Some(word) => word,
+            None => {
                 warn!(
-                    "Duplicate generator for permutation {:?} and {:?}",
-                    move_.name,
-                    generator_names.get(&move_.permutation).unwrap()
+                    "Could not factorize the target of puzzle {} with the cycle library",
+                    puzzle.id
                 );
-                warn!(
-                    "Permutation {:?} will be ignored",
-                    move_.permutation.compute_info().cycles
-                );
-                continue;
+                return None;
             }
-            generator_names.insert(move_.permutation.clone(), move_.name.clone());
-        }
-
-        let gen_to_str_vec = generator_names
-            .into_iter()
-            .collect::<Vec<(Permutation, String)>>();
-        let gen_to_idx = gen_to_str_vec
-            .iter()
-            .enumerate()
-            .map(|(i, (p, _))| (p.clone(), i))
-            .collect::<HashMap<Permutation, PermutationIndex>>();
-        let gen_to_str_vec = gen_to_str_vec
-            .iter()
-            .map(|(_, s)| s.clone())
-            .collect::<Vec<String>>();
-
-        let factorization = factorize(&gen_to_idx, gen_to_str_vec, &target);
-        if let None = factorization {
-            continue;
-        }
-        let score = factorization.clone().unwrap().split('.').count();
-        info!(
-            "Solved puzzle {} of type {:?} with score {}",
-            puzzle.id, puzzle.puzzle_type, score
-        );
-        results.insert(puzzle.id, factorization.unwrap());
-    }
-    results
+        };
+        parallel::log_solved(puzzle, word.len());
+        Some(solution_string(library, &word))
+    })
 }
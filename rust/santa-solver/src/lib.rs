@@ -1,15 +1,36 @@
+pub mod beam_search;
+pub mod bidirectional;
+pub mod commutators;
+pub mod compact_permutation;
 pub mod conjugated_search;
+pub mod config;
 pub mod coordinate_calc;
 pub mod cubes;
+pub mod cycle_extractor;
+pub mod cycle_library;
+pub mod cycle_solver;
+pub mod globe;
 pub mod groups;
+pub mod ida_star;
 pub mod iterators;
 pub mod kalka_teicher_tsaban;
 pub mod ktt_solver;
 pub mod minkwitz;
 pub mod minkwitz_search;
+pub mod minkwitz_solver;
+pub mod parallel;
+pub mod pattern_database;
+pub mod peephole;
 pub mod permutation;
 pub mod puzzle;
 pub mod schreier;
 mod split_cycles;
+pub mod submission;
+pub mod target_chooser;
 pub mod testing_utils;
+pub mod validation;
+pub mod versioned_file;
+pub mod visited;
+pub mod wildcards;
+pub mod window_optimizer;
 pub mod wreath;
@@ -1,7 +1,10 @@
-use crate::groups::{DepthLimitedPermutationGroupIterator, PermutationGroupIterator};
+use crate::compact_permutation::{CompactPermutation, Point};
+use crate::config::VisitedConfig;
+use crate::groups::{DepthLimitedCompactGroupIterator, DepthLimitedPermutationGroupIterator};
 use log::debug;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::error::Error;
 use std::fs;
 use std::hash::{Hash, Hasher};
 use std::io::{self, BufRead};
@@ -37,7 +40,7 @@ pub struct GroupGens {
     pub elements: Vec<GroupGen>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransTable {
     pub table: HashMap<(usize, usize), PermAndWord>,
     pub group_elements_processed: usize,
@@ -150,6 +153,10 @@ impl PermAndWord {
     pub fn set_news(&mut self, news: bool) {
         self.news = news;
     }
+
+    pub fn compact_perm<T: Point>(&self) -> Result<CompactPermutation<T>, Box<dyn Error>> {
+        CompactPermutation::try_from(&self.perm)
+    }
 }
 
 impl Hash for PermAndWord {
@@ -190,7 +197,22 @@ impl PartialOrd for PermAndWord {
     }
 }
 
+// Table entries as compact permutations, see TransTable::compact_perms
+pub type CompactTransTable<T> = HashMap<(usize, usize), CompactPermutation<T>>;
+
 impl TransTable {
+    /*
+     * Permutations of the table entries as compact permutations, e.g. to keep the tables of large
+     * puzzles in memory while solving.
+     */
+    pub fn compact_perms<T: Point>(&self) -> Result<CompactTransTable<T>, Box<dyn Error>> {
+        let mut perms = HashMap::with_capacity(self.table.len());
+        for (key, entry) in self.table.iter() {
+            perms.insert(*key, entry.compact_perm()?);
+        }
+        Ok(perms)
+    }
+
     pub fn new() -> Self {
         let table: HashMap<(usize, usize), PermAndWord> = HashMap::new();
         TransTable {
@@ -286,6 +308,7 @@ impl MinkwitzTable {
         s: usize,
         w: usize,
         current_mu_table: Option<TransTable>,
+        visited: &VisitedConfig,
     ) -> TransTable {
         // if the current mu_table is not defined, create a new one, otherwise improve from already
         // processed count of group elements onward
@@ -310,8 +333,25 @@ impl MinkwitzTable {
             gen_perm_to_index.insert(gens.elements[i].perm.clone(), i);
             index_to_perm_and_word.push(gens.elements[i].perm.clone());
         }
-        let group_iterator =
-            DepthLimitedPermutationGroupIterator::new(&index_to_perm_and_word, 1000);
+        // The queues of the group iterator hold most of the memory, so they keep compact
+        // permutations whenever the degree fits into u16 points
+        let compact_generators = index_to_perm_and_word
+            .iter()
+            .map(CompactPermutation::<u16>::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .ok();
+        let group_iterator: Box<dyn Iterator<Item = (Permutation, Vec<usize>)>> =
+            match &compact_generators {
+                Some(generators) => Box::new(
+                    DepthLimitedCompactGroupIterator::with_visited(generators, 1000, visited)
+                        .map(|(perm, path)| (Permutation::from(&perm), path)),
+                ),
+                None => Box::new(DepthLimitedPermutationGroupIterator::with_visited(
+                    &index_to_perm_and_word,
+                    1000,
+                    visited,
+                )),
+            };
         for (perm, perm_path) in group_iterator {
             if count < start_index_from {
                 count += 1;
@@ -549,6 +589,18 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_trans_table_compact_perms() {
+        let perm = super::Permutation::parse_permutation_from_cycle("(1,5,7)(2,6,8)", 8);
+        let mut table = super::TransTable::new();
+        table.insert((0, 4), super::PermAndWord::new(perm.clone(), vec![1]));
+        let compact = table.compact_perms::<u8>().unwrap();
+        assert_eq!(compact.len(), 1);
+        assert_eq!(super::Permutation::from(&compact[&(0, 4)]), perm);
+        let entry = table.get(&(0, 4)).unwrap();
+        assert_eq!(entry.compact_perm::<u16>().unwrap().len(), 8);
+    }
+
     #[allow(dead_code)]
     #[test]
     fn test_group_gens() {
@@ -588,7 +640,15 @@ mod test {
         let base = super::GroupBase {
             elements: vec![0, 1, 2, 3, 4, 5, 6, 7],
         };
-        let tt = super::MinkwitzTable::build_short_word_sgs(&gens, &base, 100, 10, 1000, None);
+        let tt = super::MinkwitzTable::build_short_word_sgs(
+            &gens,
+            &base,
+            100,
+            10,
+            1000,
+            None,
+            &super::VisitedConfig::default(),
+        );
         for i in 0..base.elements.len() {
             for j in 0..base.elements.len() {
                 if i == j {
@@ -625,7 +685,15 @@ mod test {
         let base = super::GroupBase {
             elements: vec![0, 1, 2, 3, 4, 5, 6, 7],
         };
-        let tt = super::MinkwitzTable::build_short_word_sgs(&gens, &base, 100, 10, 1000, None);
+        let tt = super::MinkwitzTable::build_short_word_sgs(
+            &gens,
+            &base,
+            100,
+            10,
+            1000,
+            None,
+            &super::VisitedConfig::default(),
+        );
         super::is_valid_sgs(&tt, &base);
         for elm in &tt.table {
             println!("Table entry {:?} is {:?}", elm.0, elm.1);
@@ -713,7 +781,15 @@ mod test {
         let base = super::GroupBase {
             elements: vec![0, 1, 2, 3, 20, 21, 22, 23],
         };
-        let tt = super::MinkwitzTable::build_short_word_sgs(&gens, &base, 100, 10, 1000, None);
+        let tt = super::MinkwitzTable::build_short_word_sgs(
+            &gens,
+            &base,
+            100,
+            10,
+            1000,
+            None,
+            &super::VisitedConfig::default(),
+        );
         let target = perm_f.compose(&perm_b).compose(&perm_u).compose(&perm_d);
         let valid_indices = crate::schreier::SchreierSims::get_stabilizing_color_gens(
             &"1;2;3;4;5;6;7;8".to_string(),
@@ -210,6 +210,7 @@ mod test {
     use std::collections::HashSet;
 
     use crate::{
+        config::VisitedConfig,
         minkwitz::{self, GroupBase, GroupGen, GroupGens, PermAndWord},
         permutation::Permutation,
         schreier,
@@ -229,8 +230,15 @@ mod test {
         let genset = GroupGens::new(vec![gen1_inv, gen1, gen2_inv, gen2]);
         let base = GroupBase::new(vec![0, 1, 2]);
         // build the table
-        let sgs_table =
-            minkwitz::MinkwitzTable::build_short_word_sgs(&genset, &base, 100, 10, 100, None);
+        let sgs_table = minkwitz::MinkwitzTable::build_short_word_sgs(
+            &genset,
+            &base,
+            100,
+            10,
+            100,
+            None,
+            &VisitedConfig::default(),
+        );
         // valid indices is vector of three sets each containing one index
         let valid_indices = vec![
             vec![0].into_iter().collect::<HashSet<usize>>(),
@@ -263,7 +271,15 @@ mod test {
         let base = GroupBase {
             elements: vec![0, 1, 2, 3, 4, 5, 6, 7],
         };
-        let tt = minkwitz::MinkwitzTable::build_short_word_sgs(&gens, &base, 1000, 20, 10, None);
+        let tt = minkwitz::MinkwitzTable::build_short_word_sgs(
+            &gens,
+            &base,
+            1000,
+            20,
+            10,
+            None,
+            &VisitedConfig::default(),
+        );
         for elm in &tt.table {
             println!("Table entry {:?} is {:?}", elm.0, elm.1);
         }
@@ -0,0 +1,327 @@
+use crate::config::{BaseKind, Config};
+use crate::minkwitz::{self, PermAndWord, TransTable};
+use crate::minkwitz_search;
+use crate::parallel::{self, SolveOptions};
+use crate::permutation::{Permutation, PermutationPath};
+use crate::puzzle::{Move, Puzzle, PuzzleType};
+use crate::schreier::{self, SchreierSims, StabilizerChain};
+use crate::testing_utils::TestingUtils;
+use log::{debug, error, info};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+// The tables of a puzzle type, shared by all puzzles of the type
+pub struct TypeTable {
+    pub gens: minkwitz::GroupGens,
+    pub base: minkwitz::GroupBase,
+    pub sgs_table: TransTable,
+    pub index_to_gen_name: Vec<String>,
+}
+
+fn create_sgs_table_wrapper(
+    puzzle: &Puzzle,
+    gens: &minkwitz::GroupGens,
+    base: &minkwitz::GroupBase,
+    config: &Config,
+) -> TransTable {
+    info!(
+        "Creating new SGS table for puzzle_type {:?}",
+        puzzle.puzzle_type,
+    );
+    let n = config.minkwitz.n;
+    let s = config.minkwitz.s;
+    let w = config.minkwitz.w;
+    let improve_steps = config.minkwitz.improve_steps;
+
+    let minkwitz_table_path = config.paths.resolve(&config.paths.minkwitz_tables);
+    let sgs_table_path = format!("{}/based_{}.bin", minkwitz_table_path, puzzle.puzzle_type);
+    if Path::new(&sgs_table_path).exists() {
+        let sgs_table = minkwitz::TransTable::read_from_file(&sgs_table_path);
+        // sgs_table.group_elements_processed = 0;
+        info!(
+            "We found an existing SGS table of length {:?} for this puzzle of type {:?}. Loading it...",
+            sgs_table.table.len(),
+            puzzle.puzzle_type,
+        );
+        info!("The base has a length of {:?}", base.elements.len());
+        if improve_steps > 0 {
+            info!("Improving the SGS table by {:?} steps...", improve_steps);
+            let sgs_table = minkwitz::MinkwitzTable::build_short_word_sgs(
+                gens,
+                base,
+                improve_steps,
+                s,
+                w,
+                Some(sgs_table),
+                &config.visited,
+            );
+            let improvement = sgs_table.num_changes;
+            if improvement > 0 {
+                info!(
+                    "The SGS table was improved by {:?} steps. Writing to file...",
+                    improvement
+                );
+                sgs_table.write_to_file(&sgs_table_path);
+            } else {
+                error!("The SGS table was not improved. Suggest lowering improvement_steps to 0 to avoid unnecessary computation.");
+            }
+            sgs_table.write_to_file(&sgs_table_path);
+            return sgs_table;
+        }
+        sgs_table
+    } else {
+        info!(
+            "We did not find an existing SGS table for this puzzle of type {:?}. Creating it...",
+            puzzle.puzzle_type
+        );
+        let sgs_table = minkwitz::MinkwitzTable::build_short_word_sgs(
+            gens,
+            base,
+            n,
+            s,
+            w,
+            None,
+            &config.visited,
+        );
+        sgs_table.write_to_file(&sgs_table_path);
+        sgs_table
+    }
+}
+
+fn get_base_check_if_exists(
+    puzzle: &Puzzle,
+    puzzle_info: &HashMap<PuzzleType, Vec<Move>>,
+    bases_path: &str,
+) -> Option<minkwitz::GroupBase> {
+    let base_path = format!("{}/{}.csv", bases_path, puzzle.puzzle_type);
+    let perm_size = puzzle.initial_state.len();
+    if Path::new(&base_path).exists() {
+        let base = minkwitz::GroupBase::load_from_file(&base_path);
+        info!(
+            "We found an existing base of length {:?} vs perm_size {:?} for this puzzle of type {:?}. Loading it...",
+            base.elements.len(),
+            perm_size,
+            puzzle.puzzle_type,
+        );
+        return Some(base);
+    }
+    info!(
+        "We did not find an existing base for this puzzle of type {:?}. Creating it...",
+        puzzle.puzzle_type
+    );
+    let mut index_to_perm: Vec<Permutation> = Vec::new();
+    for move_elm in puzzle_info.get(&puzzle.puzzle_type).unwrap() {
+        index_to_perm.push(move_elm.permutation.clone());
+    }
+    let base_vec = SchreierSims::find_base(index_to_perm);
+    let base = minkwitz::GroupBase::new(base_vec);
+    base.write_to_file(&base_path);
+    info!(
+        "Base of length {:?} vs. perm_size {:?} created and written to file.",
+        base.elements.len(),
+        perm_size
+    );
+    Some(base)
+}
+
+/*
+ * Loads the SGS table of the puzzle's type (creating or improving it) together with the
+ * generators and base it was built for.
+ */
+pub fn build_type_table(
+    puzzle: &Puzzle,
+    puzzles_info: &HashMap<PuzzleType, Vec<Move>>,
+    config: &Config,
+) -> TypeTable {
+    let puzzle_info_types = puzzles_info.get(&puzzle.puzzle_type).unwrap();
+    let mut gens = minkwitz::GroupGens::new(vec![]);
+    let mut index_to_gen_name = vec![];
+    for move_elm in puzzle_info_types.iter() {
+        let new_gen = minkwitz::GroupGen::new(move_elm.name.clone(), move_elm.permutation.clone());
+        gens.add(new_gen);
+        index_to_gen_name.push(move_elm.name.to_string());
+    }
+    // basevec from 0 to base length
+    let base_vec: Vec<usize> = match config.minkwitz.base {
+        BaseKind::Custom => crate::coordinate_calc::get_coords::get_moves_to_solve(puzzle),
+        BaseKind::Schreier => {
+            let bases_path = config.paths.resolve(&config.paths.bases);
+            get_base_check_if_exists(puzzle, puzzles_info, &bases_path)
+                .unwrap()
+                .elements
+        }
+        BaseKind::Identity => (0..puzzle.initial_state.len()).collect(),
+    };
+    info!("Base vector: {:?}", base_vec);
+
+    let base = minkwitz::GroupBase::new(base_vec);
+    let sgs_table: TransTable = create_sgs_table_wrapper(puzzle, &gens, &base, config);
+    TypeTable {
+        gens,
+        base,
+        sgs_table,
+        index_to_gen_name,
+    }
+}
+
+fn solve_puzzle(
+    puzzle: &Puzzle,
+    target: &Permutation,
+    table: &TypeTable,
+    use_dijkstra_search: bool,
+) -> Option<String> {
+    let TypeTable {
+        gens,
+        base,
+        sgs_table,
+        index_to_gen_name,
+    } = table;
+
+    let valid_indices: Vec<HashSet<usize>> =
+        schreier::SchreierSims::get_stabilizing_color_gens(&puzzle.goal_string);
+    // test that sets are valid, todo: remove
+    let test_set = &valid_indices[0];
+    let test_str: Vec<&str> = puzzle.goal_string.split(";").collect();
+    let mut prev_letter: Option<&str> = None;
+    for elm in test_set {
+        let letter_at_index = test_str[*elm];
+        if let Some(prev) = prev_letter {
+            assert_eq!(prev, letter_at_index);
+        } else {
+            prev_letter = Some(letter_at_index);
+        }
+    }
+    let fact: Option<Vec<usize>>;
+    if use_dijkstra_search {
+        let target_pw = PermAndWord::new(target.clone(), vec![]);
+        info!("Searching for a path to the target permutation...");
+        let djikstra_res = minkwitz_search::minkwitz_djikstra(
+            valid_indices.clone(),
+            target_pw,
+            sgs_table.clone(),
+            1000,
+        );
+        if let Some(djikstra_res) = djikstra_res {
+            fact = Some(djikstra_res.word);
+        } else {
+            error!("Could not find a path to the target permutation!");
+            return None;
+        }
+    } else {
+        fact = Some(minkwitz::MinkwitzTable::factorize_minkwitz(
+            gens, base, sgs_table, target,
+        ));
+    }
+    if fact.is_none() {
+        error!("Could not find a path to the target permutation!");
+        return None;
+    }
+    // let factorization = fact.unwrap().word;
+    let factorization = fact.unwrap();
+
+    if factorization.is_empty() {
+        return None;
+    }
+    let factorization_length = &factorization.len();
+    info!("----------------------------------------");
+    info!(
+        "Found target path for this problem! Length: {:?}. Index Path is verified!",
+        factorization_length
+    );
+    info!(
+        "Average number of steps to solve one move: {:?}",
+        factorization_length / puzzle.goal_string.len()
+    );
+    info!("----------------------------------------");
+    let path = PermutationPath::new(factorization);
+    let sol_string_dot_format = path.to_string(index_to_gen_name);
+    TestingUtils::assert_applying_sol_string_to_initial_string_results_in_target(
+        puzzle.init_string.clone(),
+        puzzle.goal_string.clone(),
+        sol_string_dot_format.clone(),
+        puzzle.puzzle_type.clone(),
+        puzzle.num_wildcards,
+    );
+    Some(sol_string_dot_format)
+}
+
+/*
+ * Factorizes the targets of the puzzles with the SGS tables of their types. Every round improves
+ * the tables once and solves all puzzles again, keeping the shortest solution of each puzzle.
+ */
+pub fn solve_puzzles(
+    puzzles: &Vec<Puzzle>,
+    puzzles_info: &HashMap<PuzzleType, Vec<Move>>,
+    id_to_target: &HashMap<usize, Permutation>,
+    config: &Config,
+    options: &SolveOptions,
+) -> HashMap<usize, String> {
+    let rounds = config.minkwitz.rounds;
+    let mut chains: HashMap<PuzzleType, StabilizerChain> = HashMap::new();
+    let mut results: HashMap<usize, String> = HashMap::new();
+    for round in 0..rounds {
+        info!("Starting round {} of {}", round + 1, rounds);
+        // 1) Generate (or improve) the Strong Generating Set Table of every group, the tables are
+        // shared by all puzzles of a type and solved against in parallel
+        let mut tables: HashMap<PuzzleType, TypeTable> = HashMap::new();
+        for puzzle in puzzles {
+            if tables.contains_key(&puzzle.puzzle_type) {
+                continue;
+            }
+            chains
+                .entry(puzzle.puzzle_type.clone())
+                .or_insert_with(|| StabilizerChain::from_moves(&puzzle.moves));
+            tables.insert(
+                puzzle.puzzle_type.clone(),
+                build_type_table(puzzle, puzzles_info, config),
+            );
+        }
+
+        // 2) Factorize the target permutations
+        let round_results = parallel::solve_in_parallel(puzzles, options, |puzzle, _| {
+            info!(
+                "Solving puzzle {} of type {:?}",
+                puzzle.id, puzzle.puzzle_type,
+            );
+            let target = match id_to_target.get(&puzzle.id) {
+                Some(target) => target,
+                None => {
+                    error!(
+                        "Could not find a target for puzzle {}. Skipping...",
+                        puzzle.id
+                    );
+                    return None;
+                }
+            };
+            debug!(
+                "We want to reach following target: {:?}",
+                target.compute_info()
+            );
+            let chain = &chains[&puzzle.puzzle_type];
+            if !chain.contains(target) {
+                error!(
+                    "The target of puzzle {} is not in the group of type {:?} (order {}). Skipping...",
+                    puzzle.id,
+                    puzzle.puzzle_type,
+                    chain.order()
+                );
+                return None;
+            }
+            solve_puzzle(
+                puzzle,
+                target,
+                &tables[&puzzle.puzzle_type],
+                config.minkwitz.use_dijkstra_search,
+            )
+        });
+        for (id, solution) in round_results {
+            let is_shorter = results
+                .get(&id)
+                .is_none_or(|s| s.split('.').count() > solution.split('.').count());
+            if is_shorter {
+                results.insert(id, solution);
+            }
+        }
+    }
+    results
+}
@@ -0,0 +1,275 @@
+use crate::config::WildcardConfig;
+use crate::peephole::PeepholeOptimizer;
+use crate::puzzle::{Puzzle, PuzzleType};
+use crate::wildcards;
+use crate::window_optimizer::{WindowOptimizer, WindowOptions};
+use log::{info, warn};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/*
+ * Point in time after which a solver should give up on a puzzle. Solvers check it at natural
+ * checkpoints (e.g. between the iterations of a search) and return what they have.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct Deadline {
+    end: Option<Instant>,
+}
+
+impl Deadline {
+    pub fn after(budget: Option<Duration>) -> Deadline {
+        Deadline {
+            end: budget.map(|budget| Instant::now() + budget),
+        }
+    }
+
+    pub fn unlimited() -> Deadline {
+        Deadline { end: None }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        match self.end {
+            Some(end) => Instant::now() >= end,
+            None => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SolveOptions {
+    // Number of worker threads, all cores if None
+    pub num_threads: Option<usize>,
+    // Time budget per puzzle, unlimited if None
+    pub time_budget: Option<Duration>,
+    // Every solution is published here as soon as it is found
+    pub solution_store: Option<SolutionStore>,
+    // Shorten every solution with the peephole optimizer of its puzzle type
+    pub optimize: bool,
+    // Afterwards replace segments of every solution by shorter words of its puzzle type
+    pub window: Option<WindowOptions>,
+    // Finally cut every solution once it is within the wildcards of its puzzle
+    pub wildcards: Option<WildcardConfig>,
+}
+
+/*
+ * Solutions shared between the solver threads and whoever has to write them out early, e.g. an
+ * interrupt handler. Cloning the store gives another handle to the same solutions.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct SolutionStore {
+    solutions: Arc<Mutex<HashMap<usize, String>>>,
+}
+
+impl SolutionStore {
+    pub fn new() -> SolutionStore {
+        SolutionStore::default()
+    }
+
+    /*
+     * Keeps the solution unless the store already has a solution with fewer moves for the id.
+     */
+    pub fn insert(&self, id: usize, solution: String) {
+        let mut solutions = self.solutions.lock().unwrap();
+        let is_shorter = solutions
+            .get(&id)
+            .is_none_or(|s| s.split('.').count() > solution.split('.').count());
+        if is_shorter {
+            solutions.insert(id, solution);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.solutions.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn snapshot(&self) -> HashMap<usize, String> {
+        self.solutions.lock().unwrap().clone()
+    }
+
+    /*
+     * Writes the solutions sorted by id to a temporary file next to path and renames it, so the
+     * file at path is never left half written, even if the process is killed while writing.
+     */
+    pub fn write_to_file(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut solutions = self
+            .snapshot()
+            .into_iter()
+            .collect::<Vec<(usize, String)>>();
+        solutions.sort();
+        let tmp_path = format!("{}.tmp", path);
+        let mut writer = csv::Writer::from_path(&tmp_path)?;
+        writer.write_record(["id", "moves"])?;
+        for (id, moves) in solutions.iter() {
+            writer.write_record([&id.to_string(), moves])?;
+        }
+        writer.flush()?;
+        drop(writer);
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
+
+/*
+ * Logs the score of a solved puzzle, in the same format for every method.
+ */
+pub fn log_solved(puzzle: &Puzzle, score: usize) {
+    info!(
+        "Solved puzzle {} of type {:?} with score {}",
+        puzzle.id, puzzle.puzzle_type, score
+    );
+}
+
+/*
+ * Runs solve for every puzzle on a thread pool and merges the solutions by puzzle id. Each call
+ * gets a deadline according to the time budget of the options, and every solution is published
+ * to the solution store of the options right away.
+ */
+pub fn solve_in_parallel<F>(
+    puzzles: &[Puzzle],
+    options: &SolveOptions,
+    solve: F,
+) -> HashMap<usize, String>
+where
+    F: Fn(&Puzzle, &Deadline) -> Option<String> + Sync,
+{
+    let mut builder = rayon::ThreadPoolBuilder::new();
+    if let Some(num_threads) = options.num_threads {
+        builder = builder.num_threads(num_threads);
+    }
+    let pool = builder.build().expect("Could not create thread pool");
+    info!(
+        "Solving {} puzzles on {} threads",
+        puzzles.len(),
+        pool.current_num_threads()
+    );
+    pool.install(|| {
+        let mut moves_by_type: HashMap<&PuzzleType, &Puzzle> = HashMap::new();
+        for puzzle in puzzles {
+            moves_by_type.entry(&puzzle.puzzle_type).or_insert(puzzle);
+        }
+        let mut optimizers: HashMap<&PuzzleType, PeepholeOptimizer> = HashMap::new();
+        if options.optimize {
+            optimizers = moves_by_type
+                .par_iter()
+                .map(|(puzzle_type, puzzle)| (*puzzle_type, PeepholeOptimizer::new(&puzzle.moves)))
+                .collect();
+        }
+        let mut window_optimizers: HashMap<&PuzzleType, WindowOptimizer> = HashMap::new();
+        if let Some(window) = &options.window {
+            window_optimizers = moves_by_type
+                .par_iter()
+                .map(|(puzzle_type, puzzle)| {
+                    let optimizer =
+                        WindowOptimizer::load_or_build(puzzle_type, &puzzle.moves, window);
+                    (*puzzle_type, optimizer)
+                })
+                .collect();
+        }
+        puzzles
+            .par_iter()
+            .filter_map(|puzzle| {
+                let start = Instant::now();
+                let deadline = Deadline::after(options.time_budget);
+                let solution = solve(puzzle, &deadline);
+                if deadline.is_expired() {
+                    warn!(
+                        "Puzzle {} exceeded its time budget after {:?}",
+                        puzzle.id,
+                        start.elapsed()
+                    );
+                }
+                let mut solution = solution?;
+                if let Some(optimizer) = optimizers.get(&puzzle.puzzle_type) {
+                    match optimizer.optimize_string(&solution) {
+                        Some(optimized) => solution = optimized,
+                        None => warn!("Solution of puzzle {} has unknown moves", puzzle.id),
+                    }
+                }
+                if let Some(optimizer) = window_optimizers.get(&puzzle.puzzle_type) {
+                    if let Some(optimized) = optimizer.optimize_string(&solution) {
+                        solution = optimized;
+                    }
+                }
+                if let Some(config) = &options.wildcards {
+                    if let Some(shortened) = wildcards::shorten_solution(puzzle, &solution, config)
+                    {
+                        solution = shortened;
+                    }
+                }
+                if let Some(store) = &options.solution_store {
+                    store.insert(puzzle.id, solution.clone());
+                }
+                Some((puzzle.id, solution))
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::puzzle::PuzzleType;
+
+    #[test]
+    fn test_solve_in_parallel_merges_results() {
+        let puzzles = (0..20)
+            .map(|id| Puzzle {
+                id,
+                puzzle_type: PuzzleType::WREATH(6),
+                initial_state: vec![],
+                goal_state: vec![],
+                moves: vec![],
+                init_string: String::new(),
+                goal_string: String::new(),
+                num_wildcards: 0,
+            })
+            .collect::<Vec<Puzzle>>();
+        let options = SolveOptions {
+            num_threads: Some(4),
+            time_budget: Some(Duration::from_secs(60)),
+            solution_store: Some(SolutionStore::new()),
+            optimize: false,
+            window: None,
+            wildcards: None,
+        };
+        let results = solve_in_parallel(&puzzles, &options, |puzzle, _| match puzzle.id % 2 {
+            0 => Some(format!("r.{}", puzzle.id)),
+            _ => None,
+        });
+        assert_eq!(results.len(), 10);
+        assert_eq!(results[&4], "r.4");
+        assert!(!results.contains_key(&5));
+        assert_eq!(options.solution_store.unwrap().snapshot(), results);
+    }
+
+    #[test]
+    fn test_solution_store_write_to_file() {
+        let store = SolutionStore::new();
+        store.insert(3, "f0.-r1".to_string());
+        store.clone().insert(1, "r0".to_string());
+        let dir = std::env::temp_dir().join("santa_solution_store_test");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("solutions.csv");
+        store.write_to_file(path.to_str().unwrap()).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "id,moves\n1,r0\n3,f0.-r1\n"
+        );
+        assert!(!dir.join("solutions.csv.tmp").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_deadline() {
+        assert!(!Deadline::unlimited().is_expired());
+        assert!(!Deadline::after(None).is_expired());
+        assert!(Deadline::after(Some(Duration::ZERO)).is_expired());
+    }
+}
@@ -0,0 +1,430 @@
+use crate::ida_star::Heuristic;
+use crate::permutation::Permutation;
+use crate::puzzle::Puzzle;
+use crate::versioned_file;
+use log::{debug, info, warn};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::error::Error;
+use std::hash::{Hash, Hasher};
+
+const PATTERN_DATABASE_MAGIC: [u8; 4] = *b"SPDB";
+// Increase whenever the serialized format of the database changes
+const PATTERN_DATABASE_VERSION: u32 = 1;
+// Nibble of the states that were not reached, which are at least this far from the goal
+const UNKNOWN: u8 = 0xF;
+// Frontier states whose neighbours are ranked at once while building
+const FRONTIER_CHUNK: usize = 1 << 16;
+
+/*
+ * Number of ways to arrange the labels with the given counts on their total number of facelets,
+ * None if it exceeds max.
+ */
+fn arrangements(counts: &[usize], max: u64) -> Option<u64> {
+    let mut result: u128 = 1;
+    let mut placed: u128 = 0;
+    for count in counts {
+        // Multiply by the binomial coefficient (placed + count choose count) step by step
+        for i in 1..=*count as u128 {
+            placed += 1;
+            result = result * placed / i;
+            if result > max as u128 {
+                return None;
+            }
+        }
+    }
+    Some(result as u64)
+}
+
+/*
+ * Distances to the goal of the abstract states of a puzzle in which only some values (colors) are
+ * told apart, stored with four bits per state. An abstract state labels every facelet with the
+ * label of its value, 0 for the untracked values, and is ranked among all arrangements of the
+ * labels. The distances ignore wildcards, so a database is only admissible for puzzles without
+ * them.
+ */
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PatternDatabase {
+    move_names: Vec<String>,
+    // Value of every tracked label, label i + 1 stands for values[i]
+    values: Vec<usize>,
+    // Number of facelets of every label, starting with label 0
+    counts: Vec<usize>,
+    goal: Vec<u8>,
+    size: u64,
+    // Two distances per byte, the one of the even rank in the low nibble
+    nibbles: Vec<u8>,
+    // Label of every value, rebuilt after loading
+    #[serde(skip)]
+    label_of: Vec<u8>,
+}
+
+impl PatternDatabase {
+    /*
+     * Tracks the goal values of the facelets in their order, as many as fit into max_size states.
+     * All facelets of a tracked value are tracked, as facelets of the same value can not be told
+     * apart.
+     */
+    fn projection(
+        goal_state: &[usize],
+        facelets: &[usize],
+        max_size: u64,
+    ) -> Result<(Vec<usize>, Vec<usize>), Box<dyn Error>> {
+        // Ranks multiply the size by up to the number of facelets, which must not overflow
+        let max_size = max_size.min(u64::MAX / (goal_state.len() as u64 + 1));
+        let mut values = Vec::new();
+        let mut counts = vec![goal_state.len()];
+        let mut seen = HashSet::new();
+        for facelet in facelets {
+            let value = goal_state[*facelet];
+            if !seen.insert(value) {
+                continue;
+            }
+            let count = goal_state.iter().filter(|v| **v == value).count();
+            let mut new_counts = counts.clone();
+            new_counts[0] -= count;
+            new_counts.push(count);
+            if arrangements(&new_counts, max_size).is_none() {
+                break;
+            }
+            values.push(value);
+            counts = new_counts;
+        }
+        if values.is_empty() {
+            return Err(format!("No value of the facelets fits into {} states", max_size).into());
+        }
+        Ok((values, counts))
+    }
+
+    fn new(
+        puzzle: &Puzzle,
+        values: Vec<usize>,
+        counts: Vec<usize>,
+    ) -> Result<PatternDatabase, Box<dyn Error>> {
+        let size = arrangements(&counts, u64::MAX).ok_or("Pattern database is too large")?;
+        let mut database = PatternDatabase {
+            move_names: puzzle.moves.iter().map(|m| m.name.clone()).collect(),
+            values,
+            counts,
+            goal: Vec::new(),
+            size,
+            nibbles: vec![UNKNOWN << 4 | UNKNOWN; (size as usize).div_ceil(2)],
+            label_of: Vec::new(),
+        };
+        database.build_labels();
+        database.goal = database.abstract_state(&puzzle.goal_state);
+        Ok(database)
+    }
+
+    fn build_labels(&mut self) {
+        let max_value = self.values.iter().copied().max().unwrap_or(0);
+        self.label_of = vec![0; max_value + 1];
+        for (i, value) in self.values.iter().enumerate() {
+            self.label_of[*value] = (i + 1) as u8;
+        }
+    }
+
+    /*
+     * Breadth first search from the goal over the inverse moves, as a move m takes an abstract
+     * state s to m.apply(s). Stops at the largest distance a nibble can hold.
+     */
+    pub fn build(
+        puzzle: &Puzzle,
+        facelets: &[usize],
+        max_size: u64,
+    ) -> Result<PatternDatabase, Box<dyn Error>> {
+        let (values, counts) = PatternDatabase::projection(&puzzle.goal_state, facelets, max_size)?;
+        let mut database = PatternDatabase::new(puzzle, values, counts)?;
+        info!(
+            "Building pattern database of {:?} with {} values and {} states",
+            puzzle.puzzle_type,
+            database.values.len(),
+            database.size
+        );
+        let inverses = puzzle
+            .moves
+            .iter()
+            .map(|m| m.permutation.inverse())
+            .collect::<Vec<Permutation>>();
+        let goal_rank = database.rank(&database.goal);
+        database.set(goal_rank, 0);
+        let mut frontier = vec![goal_rank];
+        let mut depth = 0;
+        let mut reached = 1;
+        while !frontier.is_empty() && depth + 1 < UNKNOWN {
+            let mut next = Vec::new();
+            // Ranking dominates, so the neighbours of a chunk are ranked in parallel
+            for chunk in frontier.chunks(FRONTIER_CHUNK) {
+                let neighbours = chunk
+                    .par_iter()
+                    .flat_map_iter(|rank| {
+                        let state = database.unrank(*rank);
+                        inverses
+                            .iter()
+                            .map(|inverse| database.rank(&inverse.apply(&state)))
+                            .collect::<Vec<u64>>()
+                    })
+                    .collect::<Vec<u64>>();
+                for new_rank in neighbours {
+                    if database.get(new_rank) == UNKNOWN {
+                        database.set(new_rank, depth + 1);
+                        next.push(new_rank);
+                    }
+                }
+            }
+            depth += 1;
+            reached += next.len();
+            debug!(
+                "Pattern database reached {} of {} states at depth {}",
+                reached, database.size, depth
+            );
+            frontier = next;
+        }
+        info!(
+            "Built pattern database of {:?} with {} reachable states up to depth {}",
+            puzzle.puzzle_type, reached, depth
+        );
+        Ok(database)
+    }
+
+    fn abstract_state(&self, state: &[usize]) -> Vec<u8> {
+        state
+            .iter()
+            .map(|v| self.label_of.get(*v).copied().unwrap_or(0))
+            .collect()
+    }
+
+    /*
+     * Position of the labels among all arrangements in lexicographic order. The arrangements of
+     * the remaining facelets starting with label x are arrangements * remaining[x] / facelets.
+     */
+    fn rank(&self, labels: &[u8]) -> u64 {
+        let mut remaining = self.counts.clone();
+        let mut arrangements = self.size;
+        let mut rank = 0;
+        for (i, label) in labels.iter().enumerate() {
+            let facelets = (labels.len() - i) as u64;
+            let label = *label as usize;
+            for count in remaining.iter().take(label) {
+                rank += arrangements * *count as u64 / facelets;
+            }
+            arrangements = arrangements * remaining[label] as u64 / facelets;
+            remaining[label] -= 1;
+        }
+        rank
+    }
+
+    fn unrank(&self, mut rank: u64) -> Vec<u8> {
+        let n = self.goal.len();
+        let mut remaining = self.counts.clone();
+        let mut arrangements = self.size;
+        let mut labels = Vec::with_capacity(n);
+        for i in 0..n {
+            let facelets = (n - i) as u64;
+            for (label, count) in remaining.iter_mut().enumerate() {
+                let starting_with = arrangements * *count as u64 / facelets;
+                if rank < starting_with {
+                    labels.push(label as u8);
+                    arrangements = starting_with;
+                    *count -= 1;
+                    break;
+                }
+                rank -= starting_with;
+            }
+        }
+        labels
+    }
+
+    fn get(&self, rank: u64) -> u8 {
+        let byte = self.nibbles[(rank / 2) as usize];
+        match rank % 2 {
+            0 => byte & 0xF,
+            _ => byte >> 4,
+        }
+    }
+
+    fn set(&mut self, rank: u64, distance: u8) {
+        let byte = &mut self.nibbles[(rank / 2) as usize];
+        *byte = match rank % 2 {
+            0 => (*byte & 0xF0) | distance,
+            _ => (*byte & 0x0F) | distance << 4,
+        };
+    }
+
+    // Number of abstract states
+    pub fn len(&self) -> u64 {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn num_values(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn write_to_file(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        versioned_file::write_versioned(
+            path,
+            PATTERN_DATABASE_MAGIC,
+            PATTERN_DATABASE_VERSION,
+            self,
+        )
+    }
+
+    /*
+     * Returns None if the file does not exist, was written by another version or is broken.
+     */
+    pub fn read_from_file(path: &str) -> Option<PatternDatabase> {
+        let mut database: PatternDatabase = versioned_file::read_versioned(
+            path,
+            PATTERN_DATABASE_MAGIC,
+            PATTERN_DATABASE_VERSION,
+            "Pattern database",
+        )?;
+        database.build_labels();
+        Some(database)
+    }
+
+    /*
+     * Loads the database of the puzzle type, goal state and projection from the directory, or
+     * builds and stores it if it does not exist or does not match the moves.
+     */
+    pub fn load_or_build(
+        databases_path: &str,
+        puzzle: &Puzzle,
+        facelets: &[usize],
+        max_size: u64,
+    ) -> Result<PatternDatabase, Box<dyn Error>> {
+        let (values, counts) = PatternDatabase::projection(&puzzle.goal_state, facelets, max_size)?;
+        let mut hasher = DefaultHasher::new();
+        puzzle.goal_state.hash(&mut hasher);
+        values.hash(&mut hasher);
+        let path = format!(
+            "{}/{}_{:016x}.pdb",
+            databases_path,
+            puzzle.puzzle_type,
+            hasher.finish()
+        );
+        if let Some(database) = PatternDatabase::read_from_file(&path) {
+            let goal_matches = database.goal == database.abstract_state(&puzzle.goal_state);
+            if database
+                .move_names
+                .iter()
+                .eq(puzzle.moves.iter().map(|m| &m.name))
+                && database.values == values
+                && database.counts == counts
+                && goal_matches
+            {
+                info!("Loaded pattern database from {}", path);
+                return Ok(database);
+            }
+            warn!("Pattern database {} does not match, rebuilding it", path);
+        }
+        let database = PatternDatabase::build(puzzle, facelets, max_size)?;
+        if let Err(e) = database.write_to_file(&path) {
+            warn!("Could not write pattern database to {}: {}", path, e);
+        }
+        Ok(database)
+    }
+}
+
+impl Heuristic for PatternDatabase {
+    fn estimate(&self, state: &[usize]) -> usize {
+        self.get(self.rank(&self.abstract_state(state))) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinate_calc::get_coords::get_pattern_facelets;
+    use crate::puzzle::PuzzleType;
+    use crate::testing_utils::TestingUtils;
+    use std::fs;
+
+    // Wreath 6/6 with the moves of the puzzle info
+    fn get_puzzle() -> Puzzle {
+        let moves =
+            TestingUtils::moves_from_cycles(&[("l", "(1,2,3,4,5,6)"), ("r", "(1,7,8,3,9,10)")], 10);
+        Puzzle {
+            puzzle_type: PuzzleType::WREATH(6),
+            ..TestingUtils::puzzle_from_moves(
+                moves,
+                vec![1, 1, 1, 1, 2, 3, 2, 3, 2, 2],
+                vec![3, 1, 3, 1, 1, 1, 2, 2, 2, 2],
+                0,
+            )
+        }
+    }
+
+    #[test]
+    fn test_rank_unrank() {
+        let puzzle = get_puzzle();
+        let database = PatternDatabase::new(&puzzle, vec![3, 2], vec![4, 2, 4]).unwrap();
+        // 10! / (4! 2! 4!)
+        assert_eq!(database.len(), 3150);
+        assert_eq!(database.goal, vec![1, 0, 1, 0, 0, 0, 2, 2, 2, 2]);
+        for rank in 0..database.len() {
+            let labels = database.unrank(rank);
+            assert_eq!(labels.iter().filter(|l| **l == 1).count(), 2);
+            assert_eq!(database.rank(&labels), rank);
+        }
+        assert_eq!(database.rank(&vec![0, 0, 0, 0, 1, 1, 2, 2, 2, 2]), 0);
+    }
+
+    #[test]
+    fn test_pattern_database_distances() {
+        let puzzle = get_puzzle();
+        let facelets = get_pattern_facelets(&puzzle.puzzle_type);
+        let database = PatternDatabase::build(&puzzle, &facelets, 1_000_000).unwrap();
+        // The left ring holds A and C, all other facelets are B, so the projection is exact
+        assert_eq!(database.num_values(), 2);
+        assert_eq!(database.estimate(&puzzle.goal_state), 0);
+        // Breadth first search on the actual states gives the same distances
+        let mut distances = std::collections::HashMap::from([(puzzle.goal_state.clone(), 0)]);
+        let mut frontier = vec![puzzle.goal_state.clone()];
+        let mut depth = 0;
+        while !frontier.is_empty() {
+            depth += 1;
+            let mut next = Vec::new();
+            for state in frontier.iter() {
+                for m in puzzle.moves.iter() {
+                    let new_state = m.permutation.inverse().apply(state);
+                    if !distances.contains_key(&new_state) {
+                        distances.insert(new_state.clone(), depth);
+                        next.push(new_state);
+                    }
+                }
+            }
+            frontier = next;
+        }
+        for (state, distance) in distances.iter() {
+            assert_eq!(database.estimate(state), (*distance).min(UNKNOWN as usize));
+        }
+
+        let dir = std::env::temp_dir().join(format!("santa_pdb_{}", std::process::id()));
+        let dir = dir.to_string_lossy().to_string();
+        let built = PatternDatabase::load_or_build(&dir, &puzzle, &facelets, 1_000_000).unwrap();
+        let loaded = PatternDatabase::load_or_build(&dir, &puzzle, &facelets, 1_000_000).unwrap();
+        assert_eq!(built.nibbles, loaded.nibbles);
+        assert_eq!(
+            loaded.estimate(&puzzle.initial_state),
+            built.estimate(&puzzle.initial_state)
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_projection_fits_size() {
+        let goal = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        let (values, counts) = PatternDatabase::projection(&goal, &vec![7, 6, 5, 4], 400).unwrap();
+        // 8 * 7 * 6 = 336 states for three values, 1680 for four
+        assert_eq!(values, vec![8, 7, 6]);
+        assert_eq!(counts, vec![5, 1, 1, 1]);
+        assert!(PatternDatabase::projection(&goal, &vec![0], 4).is_err());
+    }
+}
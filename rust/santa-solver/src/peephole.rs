@@ -0,0 +1,318 @@
+use crate::permutation::Permutation;
+use crate::puzzle::{self, Move};
+use std::collections::{HashMap, VecDeque};
+
+// Generators of a larger order are not merged into powers, only moves equal to them are combined
+const MAX_MERGE_ORDER: usize = 10_000;
+
+/*
+ * Shortens move sequences of a puzzle type without changing their permutation. Every move is
+ * written as a power g^e of a generator g (e.g. -r is r^(k-1) if r has order k). A move is merged
+ * into an earlier power of its generator if all moves in between commute with it, i.e. have a
+ * support disjoint from it, and powers that become the identity are removed. Merged powers are
+ * emitted as the shortest word in the moves of their generator.
+ */
+pub struct PeepholeOptimizer {
+    moves: Vec<Move>,
+    index_of: HashMap<String, usize>,
+    // Generator and exponent of every move
+    powers: Vec<(usize, usize)>,
+    // Order of every generator (indexed by the move index of the generator)
+    orders: Vec<usize>,
+    // Shortest word for every exponent of every generator, empty for moves that are no generator
+    words: Vec<Vec<Vec<usize>>>,
+    commutes: Vec<Vec<bool>>,
+}
+
+fn support(perm: &Permutation) -> Vec<u64> {
+    let mut bits = vec![0u64; perm.len().div_ceil(64)];
+    for (i, x) in perm.p.iter().enumerate() {
+        if *x != i + 1 {
+            bits[i / 64] |= 1 << (i % 64);
+        }
+    }
+    bits
+}
+
+fn order(perm: &Permutation) -> usize {
+    let lengths = perm
+        .compute_info()
+        .cycles
+        .iter()
+        .map(|c| c.len())
+        .collect::<Vec<usize>>();
+    Permutation::lcm(lengths)
+}
+
+impl PeepholeOptimizer {
+    pub fn new(moves: &[Move]) -> PeepholeOptimizer {
+        let supports = moves
+            .iter()
+            .map(|m| support(&m.permutation))
+            .collect::<Vec<Vec<u64>>>();
+        let mut orders = vec![0; moves.len()];
+        let mut powers: Vec<(usize, usize)> = Vec::with_capacity(moves.len());
+        // Powers of a generator have the same support, so only generators with the same support
+        // have to be compared
+        let mut generators_by_support: HashMap<&Vec<u64>, Vec<usize>> = HashMap::new();
+        for (i, m) in moves.iter().enumerate() {
+            let candidates = generators_by_support.entry(&supports[i]).or_default();
+            let mut power = None;
+            for g in candidates.iter() {
+                if orders[*g] > MAX_MERGE_ORDER {
+                    continue;
+                }
+                let generator = &moves[*g].permutation;
+                let mut p = generator.clone();
+                for e in 1..orders[*g] {
+                    if p == m.permutation {
+                        power = Some((*g, e));
+                        break;
+                    }
+                    p = generator.compose(&p);
+                }
+                if power.is_some() {
+                    break;
+                }
+            }
+            match power {
+                Some(power) => powers.push(power),
+                None => {
+                    orders[i] = order(&m.permutation);
+                    candidates.push(i);
+                    powers.push((i, 1));
+                }
+            }
+        }
+
+        // Breadth first search over the exponents of every generator
+        let mut words: Vec<Vec<Vec<usize>>> = vec![Vec::new(); moves.len()];
+        for g in 0..moves.len() {
+            if powers[g] != (g, 1) || orders[g] > MAX_MERGE_ORDER {
+                continue;
+            }
+            let steps = (0..moves.len())
+                .filter(|i| powers[*i].0 == g)
+                .collect::<Vec<usize>>();
+            let mut g_words: Vec<Option<Vec<usize>>> = vec![None; orders[g]];
+            g_words[0] = Some(Vec::new());
+            let mut queue = VecDeque::from([0]);
+            while let Some(e) = queue.pop_front() {
+                for step in &steps {
+                    let next = (e + powers[*step].1) % orders[g];
+                    if g_words[next].is_none() {
+                        let mut word = g_words[e].clone().unwrap();
+                        word.push(*step);
+                        g_words[next] = Some(word);
+                        queue.push_back(next);
+                    }
+                }
+            }
+            words[g] = g_words.into_iter().map(|w| w.unwrap()).collect();
+        }
+
+        let commutes = supports
+            .iter()
+            .map(|a| {
+                supports
+                    .iter()
+                    .map(|b| a.iter().zip(b.iter()).all(|(x, y)| x & y == 0))
+                    .collect()
+            })
+            .collect();
+        PeepholeOptimizer {
+            moves: moves.to_vec(),
+            index_of: moves
+                .iter()
+                .enumerate()
+                .map(|(i, m)| (m.name.clone(), i))
+                .collect(),
+            powers,
+            orders,
+            words,
+            commutes,
+        }
+    }
+
+    fn is_mergeable(&self, generator: usize) -> bool {
+        self.orders[generator] <= MAX_MERGE_ORDER
+    }
+
+    /*
+     * Merges and cancels the powers of the word (move indices) in one pass. Returns the powers
+     * (generator, exponent) of the result.
+     */
+    fn merge_powers(&self, word: &[usize]) -> Vec<(usize, usize)> {
+        let mut result: Vec<(usize, usize)> = Vec::with_capacity(word.len());
+        for m in word {
+            let (g, e) = self.powers[*m];
+            let mut merged = false;
+            if self.is_mergeable(g) {
+                for j in (0..result.len()).rev() {
+                    let (h, f) = result[j];
+                    if h == g {
+                        let exponent = (e + f) % self.orders[g];
+                        if exponent == 0 {
+                            result.remove(j);
+                        } else {
+                            result[j] = (g, exponent);
+                        }
+                        merged = true;
+                        break;
+                    }
+                    if !self.commutes[g][h] {
+                        break;
+                    }
+                }
+            }
+            if !merged {
+                result.push((g, e));
+            }
+        }
+        result
+    }
+
+    /*
+     * Returns the move indices of a sequence with the same permutation as the word, which is at
+     * most as long as the word.
+     */
+    pub fn optimize_indices(&self, word: &[usize]) -> Vec<usize> {
+        let mut word = word.to_vec();
+        loop {
+            let mut optimized = Vec::with_capacity(word.len());
+            for (g, e) in self.merge_powers(&word) {
+                if self.is_mergeable(g) {
+                    optimized.extend(&self.words[g][e]);
+                } else {
+                    optimized.push(g);
+                }
+            }
+            if optimized.len() >= word.len() {
+                return word;
+            }
+            word = optimized;
+        }
+    }
+
+    /*
+     * Same as optimize_indices for a sequence of moves. Sequences with moves of another puzzle
+     * type are returned unchanged.
+     */
+    pub fn optimize(&self, moves: &[Move]) -> Vec<Move> {
+        let mut word = Vec::with_capacity(moves.len());
+        for m in moves {
+            match self.index_of.get(&m.name) {
+                Some(i) => word.push(*i),
+                None => return moves.to_vec(),
+            }
+        }
+        self.optimize_indices(&word)
+            .iter()
+            .map(|i| self.moves[*i].clone())
+            .collect()
+    }
+
+    /*
+     * Optimizes a solution in the dot separated submission format. Returns None if it contains
+     * moves of another puzzle type.
+     */
+    pub fn optimize_string(&self, solution: &str) -> Option<String> {
+        if solution.is_empty() {
+            return Some(String::new());
+        }
+        let mut word = Vec::new();
+        for name in solution.split('.') {
+            word.push(*self.index_of.get(name)?);
+        }
+        Some(puzzle::word_to_string(
+            &self.moves,
+            &self.optimize_indices(&word),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing_utils::TestingUtils;
+    use rand::Rng;
+
+    fn get_move(name: &str, cycle: &str, n: usize) -> Move {
+        Move {
+            name: name.to_string(),
+            permutation: TestingUtils::cycle_str_to_perm(cycle, n),
+        }
+    }
+
+    fn get_moves() -> Vec<Move> {
+        let mut moves = Vec::new();
+        for (name, cycle) in [
+            ("a", "(1,2,3,4)"),
+            ("b", "(5,6)"),
+            ("c", "(4,5,7)"),
+            ("d", "(8,9,10,11,12,13)"),
+        ] {
+            let m = get_move(name, cycle, 13);
+            let inverse = Move {
+                name: format!("-{}", name),
+                permutation: m.permutation.inverse(),
+            };
+            moves.push(m);
+            moves.push(inverse);
+        }
+        moves
+    }
+
+    fn apply(optimizer: &PeepholeOptimizer, solution: &str) -> Permutation {
+        let mut result = Permutation::identity(13);
+        for name in solution.split('.').filter(|name| !name.is_empty()) {
+            result = optimizer.moves[optimizer.index_of[name]]
+                .permutation
+                .compose(&result);
+        }
+        result
+    }
+
+    #[test]
+    fn test_peephole_cancels_and_merges() {
+        let optimizer = PeepholeOptimizer::new(&get_moves());
+        let optimize = |s: &str| optimizer.optimize_string(s).unwrap();
+        assert_eq!(optimize("a.-a"), "");
+        assert_eq!(optimize("b.b"), "");
+        assert_eq!(optimize("-b.b.b"), "b");
+        assert_eq!(optimize("a.a.a"), "-a");
+        assert_eq!(optimize("d.d.d.d.d"), "-d");
+        assert_eq!(optimize("d.d.d.d.d.d.c"), "c");
+        // b and d commute with a, c does not
+        assert_eq!(optimize("a.b.d.-a.-b"), "d");
+        assert_eq!(optimize("a.c.-a"), "a.c.-a");
+        assert_eq!(optimize("c.a.b.-b.-a.-c"), "");
+        assert_eq!(optimizer.optimize_string("a.x"), None);
+
+        let moves = get_moves();
+        let optimized = optimizer.optimize(&[moves[1].clone(), moves[0].clone()]);
+        assert!(optimized.is_empty());
+        let unknown = [moves[0].clone(), get_move("x", "(1,2)", 13), moves[1].clone()];
+        let optimized = optimizer.optimize(&unknown);
+        assert_eq!(
+            optimized.iter().map(|m| &m.name).collect::<Vec<_>>(),
+            vec!["a", "x", "-a"]
+        );
+    }
+
+    #[test]
+    fn test_peephole_keeps_permutation() {
+        let moves = get_moves();
+        let optimizer = PeepholeOptimizer::new(&moves);
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let solution = (0..rng.gen_range(0, 30))
+                .map(|_| moves[rng.gen_range(0, moves.len())].name.clone())
+                .collect::<Vec<String>>()
+                .join(".");
+            let optimized = optimizer.optimize_string(&solution).unwrap();
+            assert!(optimized.split('.').count() <= solution.split('.').count());
+            assert_eq!(apply(&optimizer, &optimized), apply(&optimizer, &solution));
+        }
+    }
+}
@@ -1,7 +1,9 @@
-use crate::groups::DepthLimitedPermutationGroupIterator;
+use crate::config::VisitedConfig;
+use crate::groups::{search_targets, MultiTargetResult};
+use crate::parallel::Deadline;
 use log::debug;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::fmt::{Display, Formatter};
 use std::hash::{Hash, Hasher};
 
@@ -137,7 +139,7 @@ impl Permutation {
         &self.p
     }
 
-    pub fn apply<T: Clone>(&self, v: &Vec<T>) -> Vec<T> {
+    pub fn apply<T: Clone>(&self, v: &[T]) -> Vec<T> {
         let mut result = vec![v[0].clone(); self.len()];
         for i in 0..self.len() {
             result[i] = v[self.p[i] - 1].clone();
@@ -145,8 +147,31 @@ impl Permutation {
         result
     }
 
+    /*
+     * Replaces self by self.compose(other) without allocating. The entries are moved along the
+     * cycles of other, and the highest bit marks the entries that are already in place.
+     */
     pub fn compose_in_place(&mut self, other: &Permutation) {
-        todo!();
+        const DONE: usize = 1 << (usize::BITS - 1);
+        for start in 0..self.len() {
+            if self.p[start] & DONE != 0 {
+                continue;
+            }
+            let first = self.p[start];
+            let mut i = start;
+            loop {
+                let j = other.p[i] - 1;
+                if j == start {
+                    self.p[i] = first | DONE;
+                    break;
+                }
+                self.p[i] = self.p[j] | DONE;
+                i = j;
+            }
+        }
+        for x in self.p.iter_mut() {
+            *x &= !DONE;
+        }
     }
 
     pub fn compose(&self, other: &Permutation) -> Permutation {
@@ -340,13 +365,22 @@ impl CompressedPermutation {
         return self.perm_length;
     }
 
+    // Number of points that are not fixed
+    pub fn support_size(&self) -> usize {
+        self.m.len()
+    }
+
     pub fn compose(&self, other: &CompressedPermutation) -> CompressedPermutation {
         let mut m = HashMap::new();
         for (i, j) in other.m.iter() {
-            m.insert(*i, self.get(*j));
+            // Drop points that become fixed, so that equal permutations have equal maps
+            let k = self.get(*j);
+            if k != *i {
+                m.insert(*i, k);
+            }
         }
         for (i, j) in self.m.iter() {
-            if !m.contains_key(i) {
+            if !other.m.contains_key(i) {
                 m.insert(*i, *j);
             }
         }
@@ -388,6 +422,8 @@ impl PartialEq for CompressedPermutation {
     }
 }
 
+impl Eq for CompressedPermutation {}
+
 pub fn get_permutation<T: PartialEq>(source: &Vec<T>, target: &Vec<T>) -> Permutation {
     let mut p = vec![0; source.len()];
     for i in 0..source.len() {
@@ -396,19 +432,109 @@ pub fn get_permutation<T: PartialEq>(source: &Vec<T>, target: &Vec<T>) -> Permut
     Permutation::new(p)
 }
 
+/*
+ * Labels every point (0-indexed) with the smallest point of its orbit under the generators.
+ */
+pub fn get_orbits(generators: &Vec<Permutation>) -> Vec<usize> {
+    let n = generators[0].len();
+    let mut orbit_of: Vec<usize> = (0..n).collect();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for gen in generators {
+            for x in 0..n {
+                let y = gen.p[x] - 1;
+                let min = orbit_of[x].min(orbit_of[y]);
+                if orbit_of[x] != min || orbit_of[y] != min {
+                    orbit_of[x] = min;
+                    orbit_of[y] = min;
+                    changed = true;
+                }
+            }
+        }
+    }
+    orbit_of
+}
+
+/*
+ * Parity of the permutation restricted to each of the orbits (orbit ids as given by get_orbits),
+ * true if odd.
+ */
+pub fn orbit_parities(perm: &Permutation, orbit_of: &[usize], orbits: &[usize]) -> Vec<bool> {
+    let mut visited = vec![false; perm.len()];
+    let mut parities = vec![false; orbits.len()];
+    for (i, orbit) in orbits.iter().enumerate() {
+        for x in (0..perm.len()).filter(|x| orbit_of[*x] == *orbit) {
+            let mut y = x;
+            while !visited[y] {
+                visited[y] = true;
+                y = perm.p[y] - 1;
+                if !visited[y] {
+                    parities[i] = !parities[i];
+                }
+            }
+        }
+    }
+    parities
+}
+
+/*
+ * Shortest word in elements with the given orbit parities (see orbit_parities) whose product has
+ * the target parities, found by a breadth first search over the parity vectors. The word consists
+ * of indices into element_parities. None if there is none or the deadline expired.
+ */
+pub fn find_parity_word(
+    element_parities: &[Vec<bool>],
+    parities: &[bool],
+    deadline: &Deadline,
+) -> Option<Vec<usize>> {
+    let start = vec![false; parities.len()];
+    let mut parents: HashMap<Vec<bool>, (Vec<bool>, usize)> = HashMap::new();
+    let mut queue: VecDeque<Vec<bool>> = VecDeque::new();
+    queue.push_back(start.clone());
+    while let Some(current) = queue.pop_front() {
+        if deadline.is_expired() {
+            return None;
+        }
+        if current == parities {
+            let mut word = Vec::new();
+            let mut current = current;
+            while current != start {
+                let (parent, index) = parents.remove(&current).unwrap();
+                word.push(index);
+                current = parent;
+            }
+            word.reverse();
+            return Some(word);
+        }
+        for (i, element_parity) in element_parities.iter().enumerate() {
+            let next = current
+                .iter()
+                .zip(element_parity.iter())
+                .map(|(a, b)| a ^ b)
+                .collect::<Vec<bool>>();
+            if next != start && !parents.contains_key(&next) {
+                parents.insert(next.clone(), (current.clone(), i));
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+/*
+ * Attempts to write p as a product of permutations in t by searching every nontrivial cycle of p
+ * individually in the Cayley graph of t, up to depth generators each. The words are indices into
+ * t; as the cycles are disjoint, the concatenated words of a complete result give p. Cycles that
+ * are not found are left in missing. The visited config bounds the memory of the search.
+ */
 pub fn decompose(
     p: &PermutationInfo,
-    t: &Vec<Permutation>,
+    t: &[Permutation],
     depth: usize,
-) -> Option<Vec<Permutation>> {
-    // Attempts to write p as a product of permutations in t
-    // Perform a BFS on the Cayley graph of the group generated by t
-    // Decompose every cycle of p individually  // TODO: Check whether this is necessary
-    // Attempts to write p as a product of permutations in t
-    // Perform a BFS on the Cayley graph of the group generated by t
-    // Decompose every cycle of p individually  // TODO: Check whether this is necessary
-    let mut result = Vec::new();
-    let mut targets = p
+    visited: &VisitedConfig,
+) -> MultiTargetResult {
+    let targets = p
         .cycles
         .iter()
         .filter(|c| c.len() > 1)
@@ -418,38 +544,11 @@ pub fn decompose(
     for cycle in &targets {
         debug!("{}", cycle.compute_info());
     }
-    let generator = DepthLimitedPermutationGroupIterator::new(t, depth);
-    let mut counter = 0;
-    for (p, path) in generator {
-        if counter % 200000 == 0 {
-            debug!(
-                "We have searched through {:?} elements in the generated cayley graph",
-                counter
-            );
-            debug!(
-                "For example, the current element is (in cyclic form): {}",
-                p.compute_info()
-            );
-        }
-        // todo; why is targets not a hashset?
-        if targets.contains(&p) {
-            debug!(
-                "Hit! For cycle {:?} in target decomp, we found this path: {:?}",
-                p, path
-            );
-            // Found a decomposition
-            for i in path {
-                result.push(t[i].clone());
-            }
-            targets.retain(|x| x != &p);
-        }
-        counter += 1;
-    }
-    if targets.is_empty() {
-        Some(result)
-    } else {
-        None // TODO: Maybe return the partial decomposition?
+    let result = search_targets(t, &targets, depth, visited);
+    for cycle in &result.missing {
+        debug!("No decomposition found for cycle {}", cycle.compute_info());
     }
+    result
 }
 
 #[cfg(test)]
@@ -514,6 +613,19 @@ mod permutation_tests {
         assert_eq!(r.p, vec![2, 3, 1]);
     }
 
+    #[test]
+    fn test_compose_in_place() {
+        let q = Permutation::new(vec![2, 3, 1]);
+        let mut s = q.clone();
+        s.compose_in_place(&q);
+        assert_eq!(s, q.compose(&q));
+        let a = Permutation::new(vec![3, 1, 2, 5, 4, 6]);
+        let b = Permutation::new(vec![2, 1, 4, 6, 5, 3]);
+        let mut c = a.clone();
+        c.compose_in_place(&b);
+        assert_eq!(c, a.compose(&b));
+    }
+
     #[test]
     fn test_apply() {
         let p = Permutation::new(vec![2, 3, 1]);
@@ -599,12 +711,21 @@ mod permutation_tests {
             Permutation::new(vec![1, 3, 2]),
             Permutation::new(vec![3, 2, 1]),
         ];
-        let result = decompose(&p.compute_info(), &t, 5);
-        assert_eq!(result.is_some(), true);
-        let result = result.unwrap();
-        assert_eq!(result.len(), 2);
-        assert_eq!(result[0].p, vec![3, 2, 1]);
-        assert_eq!(result[1].p, vec![1, 3, 2]);
+        let result = decompose(&p.compute_info(), &t, 5, &VisitedConfig::default());
+        assert!(result.is_complete());
+        assert_eq!(result.words[&p], vec![1, 0]);
+        assert_eq!(result.concatenated_words(), vec![1, 0]);
+
+        // Partial results keep the cycles that were found
+        let p = Permutation::new(vec![2, 3, 1, 5, 4]); // (1,2,3)(4,5)
+        let t = vec![
+            Permutation::new(vec![1, 3, 2, 4, 5]),
+            Permutation::new(vec![3, 2, 1, 4, 5]),
+        ];
+        let result = decompose(&p.compute_info(), &t, 5, &VisitedConfig::default());
+        assert!(!result.is_complete());
+        assert_eq!(result.missing, vec![Permutation::new(vec![1, 2, 3, 5, 4])]);
+        assert_eq!(result.concatenated_words(), vec![1, 0]);
     }
 
     #[test]
@@ -672,8 +793,14 @@ mod compressed_permutation_tests {
         let mut m = HashMap::new();
         m.insert(1, 2);
         m.insert(2, 1);
-        m.insert(3, 3);
         assert_eq!(cp3.m, m);
+        assert!(cp.compose(&cp.inverse()).is_identity());
+        assert_eq!(
+            cp3,
+            Permutation::new(vec![2, 1, 3])
+                .compose(&Permutation::new(vec![1, 2, 3]))
+                .compress()
+        );
     }
 
     #[test]
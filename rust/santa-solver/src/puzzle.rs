@@ -61,11 +61,11 @@ pub struct Puzzle {
 impl PuzzleType {
     pub fn from_str(s: &str) -> Result<PuzzleType, Box<dyn Error>> {
         let parts: Vec<&str> = s.split(['_', '/'].as_ref()).collect();
-        match parts[0] {
-            "cube" => Ok(PuzzleType::CUBE(parts[1].parse()?)),
-            "wreath" => Ok(PuzzleType::WREATH(parts[1].parse()?)),
-            "globe" => Ok(PuzzleType::GLOBE(parts[1].parse()?, parts[2].parse()?)),
-            _ => Err(format!("Unknown puzzle type {}", parts[0]).into()),
+        match parts.as_slice() {
+            ["cube", n, ..] => Ok(PuzzleType::CUBE(n.parse()?)),
+            ["wreath", n, ..] => Ok(PuzzleType::WREATH(n.parse()?)),
+            ["globe", rows, columns, ..] => Ok(PuzzleType::GLOBE(rows.parse()?, columns.parse()?)),
+            _ => Err(format!("Unknown puzzle type {}", s).into()),
         }
     }
 }
@@ -193,6 +193,16 @@ pub fn load_puzzles(
     Ok(puzzles)
 }
 
+/*
+ * Dot separated names of a word of move indices, the format of a submission.
+ */
+pub fn word_to_string(moves: &[Move], word: &[usize]) -> String {
+    word.iter()
+        .map(|m| moves[*m].name.as_str())
+        .collect::<Vec<&str>>()
+        .join(".")
+}
+
 pub fn moves_to_string(moves: &Vec<Move>) -> String {
     let mut s = String::new();
     for (i, m) in moves.iter().enumerate() {
@@ -204,6 +214,21 @@ pub fn moves_to_string(moves: &Vec<Move>) -> String {
     s
 }
 
+/*
+ * Index of the inverse of every move, None if the inverse is not a move. Of several moves with the
+ * same permutation, the first one is used.
+ */
+pub fn move_inverses(moves: &[Move]) -> Vec<Option<usize>> {
+    let mut index_of: HashMap<&Permutation, usize> = HashMap::new();
+    for (i, m) in moves.iter().enumerate() {
+        index_of.entry(&m.permutation).or_insert(i);
+    }
+    moves
+        .iter()
+        .map(|m| index_of.get(&m.permutation.inverse()).copied())
+        .collect()
+}
+
 pub fn moves_from_string(s: &str, moves: &Vec<Move>) -> Vec<Move> {
     let mut result = Vec::new();
     for name in s.split('.') {
@@ -234,10 +259,85 @@ impl Display for PuzzleType {
     }
 }
 
+/*
+ * Selects puzzles by type and id. An empty list of types or ids does not restrict the puzzles.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct PuzzleFilter {
+    pub puzzle_types: Vec<PuzzleType>,
+    pub ids: Vec<usize>,
+}
+
+impl PuzzleFilter {
+    pub fn matches_type(&self, puzzle_type: &PuzzleType) -> bool {
+        self.puzzle_types.is_empty() || self.puzzle_types.contains(puzzle_type)
+    }
+
+    pub fn matches_id(&self, id: usize) -> bool {
+        self.ids.is_empty() || self.ids.contains(&id)
+    }
+
+    pub fn matches(&self, puzzle: &Puzzle) -> bool {
+        self.matches_type(&puzzle.puzzle_type) && self.matches_id(puzzle.id)
+    }
+
+    pub fn apply(&self, puzzles: &[Puzzle]) -> Vec<Puzzle> {
+        puzzles
+            .iter()
+            .filter(|p| self.matches(p))
+            .cloned()
+            .collect()
+    }
+}
+
+/*
+ * Parses a list of puzzle ids like "1,4,10-20", ranges include both ends.
+ */
+pub fn parse_ids(s: &str) -> Result<Vec<usize>, Box<dyn Error>> {
+    let mut ids = Vec::new();
+    for part in s.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let (start, end): (usize, usize) = (start.trim().parse()?, end.trim().parse()?);
+                if start > end {
+                    return Err(format!("Invalid id range {}", part).into());
+                }
+                ids.extend(start..=end);
+            }
+            None => ids.push(part.parse()?),
+        }
+    }
+    Ok(ids)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_ids() {
+        assert_eq!(parse_ids("1,4, 10-12").unwrap(), vec![1, 4, 10, 11, 12]);
+        assert_eq!(parse_ids("").unwrap(), Vec::<usize>::new());
+        assert!(parse_ids("3-1").is_err());
+        assert!(parse_ids("a").is_err());
+    }
+
+    #[test]
+    fn test_puzzle_filter() {
+        let puzzle_info = load_puzzle_info("./../../data/puzzle_info.csv").unwrap();
+        let puzzles = load_puzzles("./../../data/puzzles.csv", &puzzle_info).unwrap();
+        assert_eq!(PuzzleFilter::default().apply(&puzzles).len(), puzzles.len());
+        let filter = PuzzleFilter {
+            puzzle_types: vec![PuzzleType::CUBE(2)],
+            ids: vec![0, 1, 300],
+        };
+        let selected = filter.apply(&puzzles);
+        assert_eq!(
+            selected.iter().map(|p| p.id).collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+    }
+
     #[test]
     fn test_puzzle_type_from_str() {
         assert_eq!(
@@ -253,6 +353,8 @@ mod tests {
             PuzzleType::GLOBE(3, 4)
         );
         assert!(PuzzleType::from_str("foo").is_err());
+        assert!(PuzzleType::from_str("cube").is_err());
+        assert!(PuzzleType::from_str("globe_3").is_err());
     }
 
     #[test]
@@ -1,11 +1,11 @@
 use crate::groups;
 use crate::minkwitz::PermAndWord;
-use crate::permutation::Permutation;
-use log::info;
-use rand;
-use rust_schreier::perm::Perm;
-use rust_schreier::schreier;
+use crate::permutation::{get_permutation, Permutation};
+use crate::puzzle::Move;
+use log::{debug, info};
+use num_bigint::BigUint;
 use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 #[derive(Debug, PartialEq)]
 pub struct SchreierSims {
@@ -13,21 +13,323 @@ pub struct SchreierSims {
     k: usize,
 }
 
-impl SchreierSims {
-    pub fn find_base(gens: Vec<Permutation>) -> Vec<usize> {
-        let n = gens[0].p.len();
-        let mut base: Vec<usize> = vec![];
-        let mut rnd = rand::thread_rng();
-        let mut gen: Vec<Perm> = vec![];
-        for perm in gens {
-            let new_perm = Perm::new(perm.p.iter().map(|x| *x - 1).collect());
-            gen.push(new_perm);
+/*
+ * One level G^(i) of a stabilizer chain. All generators fix the base points of the previous
+ * levels. The basic orbit of the base point is stored as a Schreier tree: vector[x] is
+ * Some((j, y)) if generators[j] maps y to x, where y is the parent of x in the tree.
+ * Points are 0-indexed, i.e. g maps x to g.p[x] - 1.
+ * The tree is only ever extended, so checked[j] orbit points (in orbit order) are known to give
+ * Schreier generators with generators[j] that sift through the levels below.
+ * Generators are shared between the levels, as most of them are contained in several levels.
+ */
+#[derive(Debug, Clone)]
+pub struct ChainLevel {
+    pub base_point: usize,
+    pub generators: Vec<Arc<Permutation>>,
+    inverses: Vec<Arc<Permutation>>,
+    pub orbit: Vec<usize>,
+    pub vector: Vec<Option<(usize, usize)>>,
+    checked: Vec<usize>,
+}
+
+/*
+ * Base and strong generating set of a permutation group, computed with the deterministic
+ * Schreier-Sims algorithm. The base points are chosen as the smallest points moved by the
+ * generators, so the same generators always result in the same chain.
+ */
+#[derive(Debug, Clone)]
+pub struct StabilizerChain {
+    pub n: usize,
+    pub levels: Vec<ChainLevel>,
+}
+
+impl ChainLevel {
+    pub fn new(base_point: usize) -> ChainLevel {
+        // the Schreier vector is only allocated once the orbit grows
+        ChainLevel {
+            base_point,
+            generators: Vec::new(),
+            inverses: Vec::new(),
+            orbit: vec![base_point],
+            vector: Vec::new(),
+            checked: Vec::new(),
+        }
+    }
+
+    pub fn add_generator(&mut self, gen: Arc<Permutation>, inverse: Arc<Permutation>) {
+        self.generators.push(gen);
+        self.inverses.push(inverse);
+        self.checked.push(0);
+        self.extend_orbit(self.generators.len() - 1);
+    }
+
+    fn extend_orbit(&mut self, new_gen: usize) {
+        // old orbit points only have to be mapped by the new generator, new ones by all of them
+        let old_len = self.orbit.len();
+        let mut i = 0;
+        while i < self.orbit.len() {
+            let x = self.orbit[i];
+            let first_gen = if i < old_len { new_gen } else { 0 };
+            for j in first_gen..self.generators.len() {
+                let y = self.generators[j].p[x] - 1;
+                if !self.contains_point(y) {
+                    if self.vector.is_empty() {
+                        self.vector = vec![None; self.generators[j].len()];
+                    }
+                    self.vector[y] = Some((j, x));
+                    self.orbit.push(y);
+                }
+            }
+            i += 1;
+        }
+    }
+
+    pub fn contains_point(&self, x: usize) -> bool {
+        x == self.base_point || (!self.vector.is_empty() && self.vector[x].is_some())
+    }
+
+    /*
+     * Returns the coset representative u of this level with u(base_point) = x.
+     */
+    pub fn transversal(&self, x: usize) -> Option<Permutation> {
+        if !self.contains_point(x) {
+            return None;
+        }
+        let mut u = Permutation::identity(self.generators[0].len());
+        let mut y = x;
+        while y != self.base_point {
+            let (j, parent) = self.vector[y].unwrap();
+            u = u.compose(&self.generators[j]);
+            y = parent;
+        }
+        Some(u)
+    }
+
+    /*
+     * Multiplies g from the left with the inverse of the transversal element of g(base_point),
+     * such that the result fixes the base point. Returns None if g(base_point) is not in the
+     * basic orbit.
+     */
+    fn strip(&self, g: &Permutation) -> Option<Permutation> {
+        let mut h = g.clone();
+        let mut x = h.p[self.base_point] - 1;
+        if !self.contains_point(x) {
+            return None;
         }
-        let (betas, _) = schreier::incrementally_build_bsgs(n, &base, &gen, &mut rnd);
-        for elm in &betas {
-            base.push(elm.0);
+        while x != self.base_point {
+            let (j, parent) = self.vector[x].unwrap();
+            h = self.inverses[j].compose(&h);
+            x = parent;
         }
-        base
+        Some(h)
+    }
+}
+
+impl StabilizerChain {
+    /*
+     * Chain of the group generated by the generators. Without generators this is the trivial chain
+     * of degree 0.
+     */
+    pub fn new(generators: &[Permutation]) -> StabilizerChain {
+        StabilizerChain::build(generators, None)
+    }
+
+    /*
+     * Builds a chain whose base points appear in the given order of all points. Levels with a
+     * trivial basic orbit are removed, so the base contains the first point of the order moved by
+     * the group, then the first point moved by its stabilizer, and so on.
+     */
+    pub fn with_point_order(generators: &[Permutation], order: &[usize]) -> StabilizerChain {
+        StabilizerChain::build(generators, Some(order))
+    }
+
+    fn build(generators: &[Permutation], order: Option<&[usize]>) -> StabilizerChain {
+        let Some(n) = generators.first().map(|g| g.len()) else {
+            return StabilizerChain {
+                n: 0,
+                levels: Vec::new(),
+            };
+        };
+        let mut gens: Vec<Arc<Permutation>> = Vec::new();
+        for gen in generators {
+            if !gen.is_identity() && gens.iter().all(|g| **g != *gen) {
+                gens.push(Arc::new(gen.clone()));
+            }
+        }
+        let mut chain = StabilizerChain {
+            n,
+            levels: Vec::new(),
+        };
+        let default_order: Vec<usize> = (0..n).collect();
+        let base: Vec<usize> = match order {
+            Some(order) => order.to_vec(),
+            None => {
+                // Initial base: every generator has to move at least one base point
+                let mut base: Vec<usize> = Vec::new();
+                for gen in &gens {
+                    if base.iter().all(|b| gen.p[*b] - 1 == *b) {
+                        base.push(Self::first_moved_point(gen, &default_order).unwrap());
+                    }
+                }
+                base
+            }
+        };
+        let order = order.unwrap_or(&default_order);
+        chain.levels = base.iter().map(|b| ChainLevel::new(*b)).collect();
+        for gen in gens.iter() {
+            // A generator belongs to all levels up to the first base point it moves
+            let first_moved = base.iter().position(|b| gen.p[*b] - 1 != *b).unwrap();
+            let inverse = Arc::new(gen.inverse());
+            for level in chain.levels[..=first_moved].iter_mut() {
+                level.add_generator(gen.clone(), inverse.clone());
+            }
+        }
+        // Check the Schreier generators of each level, starting with the last one
+        let mut i = chain.levels.len();
+        while i > 0 {
+            let level = i - 1;
+            match chain.find_non_sifting_schreier_generator(level) {
+                Some((residue, j)) => {
+                    if j == chain.levels.len() {
+                        let b = Self::first_moved_point(&residue, order).unwrap();
+                        chain.levels.push(ChainLevel::new(b));
+                    }
+                    let inverse = Arc::new(residue.inverse());
+                    let residue = Arc::new(residue);
+                    for l in (level + 1)..=j {
+                        chain.levels[l].add_generator(residue.clone(), inverse.clone());
+                    }
+                    i = j + 1;
+                }
+                None => i -= 1,
+            }
+        }
+        chain.levels.retain(|l| l.orbit.len() > 1);
+        debug!(
+            "Built stabilizer chain with base of length {} and {} strong generators",
+            chain.levels.len(),
+            chain.strong_generating_set().len()
+        );
+        chain
+    }
+
+    pub fn from_moves(moves: &[Move]) -> StabilizerChain {
+        let gens = moves
+            .iter()
+            .map(|m| m.permutation.clone())
+            .collect::<Vec<Permutation>>();
+        StabilizerChain::new(&gens)
+    }
+
+    fn first_moved_point(perm: &Permutation, order: &[usize]) -> Option<usize> {
+        order.iter().find(|x| perm.p[**x] - 1 != **x).cloned()
+    }
+
+    fn find_non_sifting_schreier_generator(
+        &mut self,
+        level: usize,
+    ) -> Option<(Permutation, usize)> {
+        // With a trivial orbit, the Schreier generators are the generators of the next level
+        if self.levels[level].orbit.len() == 1 {
+            return None;
+        }
+        for j in 0..self.levels[level].generators.len() {
+            while self.levels[level].checked[j] < self.levels[level].orbit.len() {
+                let chain_level = &self.levels[level];
+                let x = chain_level.orbit[chain_level.checked[j]];
+                let u_x = chain_level.transversal(x).unwrap();
+                // u_{s(x)}^-1 * s * u_x fixes the base point of this level
+                let schreier_gen = chain_level
+                    .strip(&chain_level.generators[j].compose(&u_x))
+                    .unwrap();
+                self.levels[level].checked[j] += 1;
+                if schreier_gen.is_identity() {
+                    continue;
+                }
+                let (residue, k) = self.sift_from(&schreier_gen, level + 1);
+                if k < self.levels.len() || !residue.is_identity() {
+                    return Some((residue, k));
+                }
+            }
+        }
+        None
+    }
+
+    fn sift_from(&self, g: &Permutation, start: usize) -> (Permutation, usize) {
+        let mut h = g.clone();
+        for i in start..self.levels.len() {
+            if h.p[self.levels[i].base_point] - 1 == self.levels[i].base_point {
+                continue;
+            }
+            match self.levels[i].strip(&h) {
+                Some(stripped) => h = stripped,
+                None => return (h, i),
+            }
+        }
+        (h, self.levels.len())
+    }
+
+    /*
+     * Sifts g through the chain. Returns the residue and the level at which sifting failed, which
+     * is the length of the base if g could be sifted through all levels.
+     */
+    pub fn sift(&self, g: &Permutation) -> (Permutation, usize) {
+        self.sift_from(g, 0)
+    }
+
+    pub fn order(&self) -> BigUint {
+        let mut order = BigUint::from(1_usize);
+        for level in &self.levels {
+            order *= level.orbit.len();
+        }
+        order
+    }
+
+    pub fn contains(&self, perm: &Permutation) -> bool {
+        if perm.len() != self.n {
+            return false;
+        }
+        let (residue, level) = self.sift(perm);
+        level == self.levels.len() && residue.is_identity()
+    }
+
+    /*
+     * Checks whether the permutation that get_permutation assigns to the pair of states is an
+     * element of the group. If the states contain repeated colors, this is only one of several
+     * preimages, so a negative result does not prove that the puzzle is unsolvable.
+     */
+    pub fn is_target_reachable(&self, initial_state: &Vec<usize>, goal_state: &Vec<usize>) -> bool {
+        self.contains(&get_permutation(initial_state, goal_state))
+    }
+
+    pub fn base(&self) -> Vec<usize> {
+        self.levels.iter().map(|l| l.base_point).collect()
+    }
+
+    pub fn strong_generating_set(&self) -> Vec<Permutation> {
+        let mut result: Vec<Permutation> = Vec::new();
+        for level in &self.levels {
+            for gen in &level.generators {
+                if !result.contains(gen) {
+                    result.push(gen.as_ref().clone());
+                }
+            }
+        }
+        result
+    }
+
+    pub fn basic_orbit(&self, i: usize) -> &Vec<usize> {
+        &self.levels[i].orbit
+    }
+
+    pub fn transversal(&self, i: usize, x: usize) -> Option<Permutation> {
+        self.levels[i].transversal(x)
+    }
+}
+
+impl SchreierSims {
+    pub fn find_base(gens: Vec<Permutation>) -> Vec<usize> {
+        StabilizerChain::new(&gens).base()
     }
 
     pub fn get_schreier_vector(
@@ -283,6 +585,7 @@ mod tests {
 
     use super::*;
     use crate::permutation::Permutation;
+    use crate::testing_utils::TestingUtils;
 
     #[test]
     fn test_find_base() {
@@ -293,6 +596,80 @@ mod tests {
         assert_eq!(base.len(), 5);
     }
 
+    #[test]
+    fn test_stabilizer_chain_s_n() {
+        let gens = TestingUtils::get_s_n_generators(5);
+        let chain = StabilizerChain::new(&gens);
+        assert_eq!(chain.base(), vec![0, 1, 2, 3]);
+        let order: usize = (0..chain.levels.len())
+            .map(|i| chain.basic_orbit(i).len())
+            .product();
+        assert_eq!(order, 120);
+    }
+
+    #[test]
+    fn test_stabilizer_chain_transversals() {
+        let perm1 = Permutation::parse_permutation_from_cycle("(1,5,7)(2,6,8)", 8);
+        let perm2 = Permutation::parse_permutation_from_cycle("(1,5)(3,4,8,2)", 8);
+        let chain = StabilizerChain::new(&vec![perm1, perm2]);
+        let base = chain.base();
+        for i in 0..base.len() {
+            for x in chain.basic_orbit(i) {
+                let u = chain.transversal(i, *x).unwrap();
+                assert_eq!(u.p[base[i]] - 1, *x);
+                for b in &base[..i] {
+                    assert_eq!(u.p[*b] - 1, *b);
+                }
+            }
+        }
+        for gen in chain.strong_generating_set() {
+            let (residue, level) = chain.sift(&gen);
+            assert!(residue.is_identity());
+            assert_eq!(level, base.len());
+        }
+    }
+
+    #[test]
+    fn test_order_and_membership() {
+        let gens = TestingUtils::get_s_n_generators(6);
+        let chain = StabilizerChain::new(&gens);
+        assert_eq!(chain.order(), BigUint::from(720_usize));
+        assert!(chain.contains(&Permutation::parse_permutation_from_cycle("(1,6)(2,4,5)", 6)));
+
+        // the 3-cycles generate the alternating group
+        let gens = vec![
+            Permutation::parse_permutation_from_cycle("(1,2,3)", 5),
+            Permutation::parse_permutation_from_cycle("(3,4,5)", 5),
+        ];
+        let chain = StabilizerChain::new(&gens);
+        assert_eq!(chain.order(), BigUint::from(60_usize));
+        assert!(chain.contains(&Permutation::parse_permutation_from_cycle("(1,2)(4,5)", 5)));
+        assert!(!chain.contains(&Permutation::parse_permutation_from_cycle("(1,2)", 5)));
+        assert!(!chain.contains(&Permutation::identity(6)));
+
+        let chain = StabilizerChain::new(&[]);
+        assert_eq!(chain.order(), BigUint::from(1_usize));
+        assert!(chain.base().is_empty());
+    }
+
+    #[test]
+    fn test_order_rubik_small() {
+        let gens = vec![
+            Permutation::parse_permutation_from_cycle("(9,10,12,11)(3,13,22,8)(4,15,21,6)", 24),
+            Permutation::parse_permutation_from_cycle("(17,18,20,19)(1,7,24,14)(2,5,23,16)", 24),
+            Permutation::parse_permutation_from_cycle("(1,2,4,3)(9,5,17,13)(10,6,18,14)", 24),
+            Permutation::parse_permutation_from_cycle("(21,22,24,23)(11,15,19,7)(12,16,20,8)", 24),
+            Permutation::parse_permutation_from_cycle("(5,6,8,7)(9,21,20,1)(11,23,18,3)", 24),
+            Permutation::parse_permutation_from_cycle("(13,14,16,15)(10,2,19,22)(12,4,17,24)", 24),
+        ];
+        let chain = StabilizerChain::new(&gens);
+        // 8! placements of the corners times 3^7 orientations, the last twist being determined
+        assert_eq!(chain.order(), BigUint::from(88_179_840_usize));
+        let target = gens[0].compose(&gens[3]).compose(&gens[5].inverse());
+        assert!(chain.contains(&target));
+        assert!(!chain.contains(&Permutation::parse_permutation_from_cycle("(1,2)", 24)));
+    }
+
     // #[test]
     fn test_get_schreier_vector_single() {
         let a = Permutation::parse_permutation_from_cycle("(1,5,3,2)(4,7,9)", 9);
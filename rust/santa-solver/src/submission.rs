@@ -1,22 +1,29 @@
+use crate::config::{Config, PathsConfig};
+use crate::peephole::PeepholeOptimizer;
+use crate::puzzle::{Puzzle, PuzzleType};
+use crate::wildcards;
+use crate::window_optimizer::WindowOptimizer;
 use log::info;
 use std::collections::HashMap;
-use std::fs::OpenOptions;
 use std::path::Path;
 
-fn main() {
-    env_logger::init();
-    let solutions_path: String = "./../../data/solutions/".to_string();
-    let submission_file_path: String = "./../../data/baseline.csv".to_string();
-    let new_submission_file_path: String = "./../../data/submission_new.csv".to_string();
-    let other_submissions_dir: String = "./../../data/improvement_complete_csvs".to_string();
+/*
+ * Merges the shortest solutions of the solution directory and of other submissions into the
+ * submission and writes the result to the merged submission file. Returns the new score.
+ */
+pub fn merge(paths: &PathsConfig) -> usize {
+    let solutions_path = &paths.resolve(&paths.solutions_dir);
+    let submission_file_path = &paths.resolve(&paths.submission);
+    let new_submission_file_path = &paths.resolve(&paths.merged_submission);
+    let other_submissions_dir = &paths.resolve(&paths.other_submissions);
 
     let mut overall_decrease = 0;
 
     // open submission file as csv
     let mut id_to_sol_string: HashMap<usize, String> =
-        get_current_id_to_sol_string(&submission_file_path);
+        get_current_id_to_sol_string(submission_file_path);
     for id in 0..398 {
-        let csv_sol_path = format!("{}{}.csv", solutions_path, id);
+        let csv_sol_path = format!("{}/{}.csv", solutions_path, id);
         if !Path::new(&csv_sol_path).exists() {
             continue;
         }
@@ -24,7 +31,7 @@ fn main() {
         let min_path_len = get_path_len(&min_path);
 
         let cur_min_path = id_to_sol_string.get(&id);
-        let cur_min_path_len = get_path_len(&cur_min_path.unwrap());
+        let cur_min_path_len = get_path_len(cur_min_path.unwrap());
 
         if min_path_len < cur_min_path_len {
             info!(
@@ -65,7 +72,7 @@ fn main() {
             id_to_sol_string = other_id_to_sol_string;
         }
     }
-    id_to_sol_string_to_csv(&new_submission_file_path, &id_to_sol_string);
+    id_to_sol_string_to_csv(new_submission_file_path, &id_to_sol_string);
     info!("-------------------");
     info!(
         "Overall decrease of {} steps in submission file",
@@ -77,10 +84,58 @@ fn main() {
     );
     info!("Find the submission file at: {}", new_submission_file_path);
     info!("-------------------");
+    score_id_to_sol_string(&id_to_sol_string)
 }
 
-pub fn get_path_len(path: &String) -> usize {
-    return path.split(".").count();
+/*
+ * Shortens every solution of the submission with the peephole optimizer of its puzzle type, the
+ * window optimizer and the wildcard truncation if they are enabled, and writes the result to the
+ * merged submission file. Returns the new score.
+ */
+pub fn optimize(puzzles: &Vec<Puzzle>, config: &Config) -> usize {
+    let submission_file_path = &config.paths.resolve(&config.paths.submission);
+    let output_path = &config.paths.resolve(&config.paths.merged_submission);
+    let window = config.window_options();
+    let wildcard_config = config.wildcard_options();
+    let mut id_to_sol_string = get_current_id_to_sol_string(submission_file_path);
+    let mut optimizers: HashMap<&PuzzleType, PeepholeOptimizer> = HashMap::new();
+    let mut window_optimizers: HashMap<&PuzzleType, WindowOptimizer> = HashMap::new();
+    let mut overall_decrease = 0;
+    for puzzle in puzzles {
+        let sol_string = match id_to_sol_string.get(&puzzle.id) {
+            Some(sol_string) => sol_string,
+            None => continue,
+        };
+        let optimizer = optimizers
+            .entry(&puzzle.puzzle_type)
+            .or_insert_with(|| PeepholeOptimizer::new(&puzzle.moves));
+        let mut optimized = optimizer.optimize_string(sol_string).unwrap();
+        if let Some(window) = &window {
+            let window_optimizer =
+                window_optimizers
+                    .entry(&puzzle.puzzle_type)
+                    .or_insert_with(|| {
+                        WindowOptimizer::load_or_build(&puzzle.puzzle_type, &puzzle.moves, window)
+                    });
+            optimized = window_optimizer.optimize_string(&optimized).unwrap();
+        }
+        if let Some(wildcard_config) = &wildcard_config {
+            optimized = wildcards::shorten_solution(puzzle, &optimized, wildcard_config).unwrap();
+        }
+        let decrease = get_path_len(sol_string) - get_path_len(&optimized);
+        if decrease > 0 {
+            info!("Shortened path for id: {} by {}", puzzle.id, decrease);
+            overall_decrease += decrease;
+            id_to_sol_string.insert(puzzle.id, optimized);
+        }
+    }
+    info!("Overall decrease: {}", overall_decrease);
+    id_to_sol_string_to_csv(output_path, &id_to_sol_string);
+    score_id_to_sol_string(&id_to_sol_string)
+}
+
+pub fn get_path_len(path: &str) -> usize {
+    path.split('.').count()
 }
 
 pub fn get_min_path_for_id(path: &String) -> Option<String> {
@@ -98,7 +153,7 @@ pub fn get_min_path_for_id(path: &String) -> Option<String> {
             min_path = Some(record_path);
         }
     }
-    return min_path;
+    min_path
 }
 
 pub fn get_current_id_to_sol_string(path: &String) -> HashMap<usize, String> {
@@ -110,7 +165,7 @@ pub fn get_current_id_to_sol_string(path: &String) -> HashMap<usize, String> {
         let sol = record[1].to_string();
         result.insert(id, sol);
     }
-    return result;
+    result
 }
 
 pub fn read_other_id_to_sol_string_for_improvement(
@@ -125,29 +180,23 @@ pub fn read_other_id_to_sol_string_for_improvement(
         let sol = record[1].to_string();
         if current_map.contains_key(&id) {
             let current_sol = current_map.get(&id).unwrap();
-            if get_path_len(&sol) < get_path_len(&current_sol) {
+            if get_path_len(&sol) < get_path_len(current_sol) {
                 result.insert(id, sol);
             } else {
                 result.insert(id, current_sol.to_string());
             }
         }
     }
-    return result;
+    result
 }
 
 pub fn id_to_sol_string_to_csv(path: &String, id_to_sol_string: &HashMap<usize, String>) {
-    let file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(true)
-        .open(path)
-        .unwrap();
-    let mut writer = csv::Writer::from_writer(file);
-    writer.write_record(&["id", "moves"]).unwrap();
+    let mut writer = csv::Writer::from_path(path).unwrap();
+    writer.write_record(["id", "moves"]).unwrap();
     for i in 0..398 {
         if id_to_sol_string.contains_key(&i) {
             let sol_string: String = id_to_sol_string.get(&i).unwrap().to_string();
-            writer.write_record(&[i.to_string(), sol_string]).unwrap();
+            writer.write_record([i.to_string(), sol_string]).unwrap();
         }
     }
     writer.flush().unwrap();
@@ -163,5 +212,5 @@ pub fn score_id_to_sol_string(id_to_sol_string: &HashMap<usize, String>) -> usiz
             score += path_len;
         }
     }
-    return score;
+    score
 }
@@ -0,0 +1,292 @@
+use crate::parallel::Deadline;
+use crate::permutation::{get_orbits, Permutation};
+use crate::puzzle::{Move, Puzzle};
+use crate::schreier::{SchreierSims, StabilizerChain};
+use log::debug;
+use std::collections::HashMap;
+
+/*
+ * Backtracking search over the stabilizer chain for a target permutation t of a puzzle, i.e. a
+ * group element with goal_state[t(i)] == initial_state[i] for all i. Every group element can be
+ * written uniquely as u_0 * u_1 * ... * u_{m-1}, where u_k is a coset representative of level k.
+ * The search picks one representative per level and checks the color of every point whose image
+ * is known once the prefix u_0 * ... * u_k is fixed, which are the points fixed by G^(k+1).
+ */
+struct TargetSearch<'a> {
+    chain: &'a StabilizerChain,
+    // color class of each goal position and the class each initial position has to be mapped to
+    goal_class: Vec<usize>,
+    initial_class: Vec<usize>,
+    determined: Vec<Vec<usize>>,
+    lower_bound: usize,
+    nodes: usize,
+    deepest: usize,
+    node_limit: usize,
+    deadline: &'a Deadline,
+    best: Option<(Permutation, usize)>,
+}
+
+impl<'a> TargetSearch<'a> {
+    fn new(
+        chain: &'a StabilizerChain,
+        goal_class: Vec<usize>,
+        initial_class: Vec<usize>,
+        node_limit: usize,
+        deadline: &'a Deadline,
+    ) -> TargetSearch<'a> {
+        let n = chain.n;
+        // fixed[k]: points fixed by every generator of level k, fixed[m] contains all points
+        let mut fixed: Vec<Vec<bool>> = Vec::new();
+        for level in &chain.levels {
+            fixed.push(
+                (0..n)
+                    .map(|x| level.generators.iter().all(|g| g.p[x] - 1 == x))
+                    .collect(),
+            );
+        }
+        fixed.push(vec![true; n]);
+        let determined = (0..chain.levels.len())
+            .map(|k| {
+                (0..n)
+                    .filter(|x| fixed[k + 1][*x] && !fixed[k][*x])
+                    .collect()
+            })
+            .collect();
+        let lower_bound = (0..n)
+            .filter(|x| goal_class[*x] != initial_class[*x])
+            .count();
+        TargetSearch {
+            chain,
+            goal_class,
+            initial_class,
+            determined,
+            lower_bound,
+            nodes: 0,
+            deepest: 0,
+            node_limit,
+            deadline,
+            best: None,
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        if self.nodes >= self.node_limit || self.deadline.is_expired() {
+            return true;
+        }
+        match &self.best {
+            Some((_, displaced)) => *displaced == self.lower_bound,
+            None => false,
+        }
+    }
+
+    fn search(&mut self, k: usize, prefix: &Permutation, displaced: usize) {
+        self.nodes += 1;
+        self.deepest = self.deepest.max(k);
+        if let Some((_, best)) = &self.best {
+            if displaced >= *best {
+                return;
+            }
+        }
+        if k == self.chain.levels.len() {
+            self.best = Some((prefix.clone(), displaced));
+            return;
+        }
+        let level = &self.chain.levels[k];
+        let wanted = self.initial_class[level.base_point];
+        let mut candidates: Vec<usize> = level
+            .orbit
+            .iter()
+            .filter(|x| self.goal_class[prefix.p[**x] - 1] == wanted)
+            .cloned()
+            .collect();
+        // Prefer representatives that keep the base point in place
+        candidates.sort_by_key(|x| prefix.p[*x] - 1 != level.base_point);
+        for x in candidates {
+            if self.is_done() {
+                return;
+            }
+            let next = prefix.compose(&level.transversal(x).unwrap());
+            let mut next_displaced = displaced;
+            let mut valid = true;
+            for p in &self.determined[k] {
+                let y = next.p[*p] - 1;
+                if self.goal_class[y] != self.initial_class[*p] {
+                    valid = false;
+                    break;
+                }
+                if y != *p {
+                    next_displaced += 1;
+                }
+            }
+            if valid {
+                self.search(k + 1, &next, next_displaced);
+            }
+        }
+    }
+}
+
+/*
+ * Orders the points orbit by orbit, starting with the orbits that leave the least freedom in
+ * choosing equally colored positions. Constraints between orbits (e.g. permutation parity) are
+ * then resolved within the last, most flexible orbits instead of at the end of a mixed base,
+ * which keeps the backtracking in choose_target local.
+ */
+pub fn search_point_order(moves: &[Move], goal_string: &str) -> Vec<usize> {
+    let n = moves[0].permutation.len();
+    let orbit_of = get_orbits(&moves.iter().map(|m| m.permutation.clone()).collect());
+    let goal: Vec<&str> = goal_string.split(';').collect();
+    let mut freedom = vec![0.0; n];
+    for (orbit, orbit_freedom) in freedom.iter_mut().enumerate() {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for x in (0..n).filter(|x| orbit_of[*x] == orbit) {
+            *counts.entry(goal[x]).or_insert(0) += 1;
+        }
+        // logarithm of the number of color preserving bijections of the orbit
+        *orbit_freedom = counts
+            .values()
+            .map(|c| (1..=*c).map(|i| (i as f64).ln()).sum::<f64>())
+            .sum();
+    }
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|a, b| {
+        let (orbit_a, orbit_b) = (orbit_of[*a], orbit_of[*b]);
+        freedom[orbit_a]
+            .partial_cmp(&freedom[orbit_b])
+            .unwrap()
+            .then(orbit_a.cmp(&orbit_b))
+            .then(a.cmp(b))
+    });
+    order
+}
+
+/*
+ * Builds the stabilizer chain choose_target works best with for puzzles of this move set and
+ * goal. The chain can be reused for all puzzles with the same moves and goal.
+ */
+pub fn build_search_chain(moves: &[Move], goal_string: &str) -> StabilizerChain {
+    let gens = moves
+        .iter()
+        .map(|m| m.permutation.clone())
+        .collect::<Vec<Permutation>>();
+    StabilizerChain::with_point_order(&gens, &search_point_order(moves, goal_string))
+}
+
+/*
+ * Chooses a target permutation for the puzzle that is an element of the group described by the
+ * chain. With repeated colors, every bijection between equally colored positions is a valid
+ * target, so we search over these bijections using the color classes of the goal state and
+ * return the one that displaces the fewest elements. The search visits at most node_limit
+ * nodes or stops at the deadline and returns the best target found so far, or None if no target
+ * was found at all.
+ */
+pub fn choose_target(
+    chain: &StabilizerChain,
+    puzzle: &Puzzle,
+    node_limit: usize,
+    deadline: &Deadline,
+) -> Option<Permutation> {
+    let n = puzzle.goal_state.len();
+    if chain.n != n || puzzle.initial_state.len() != n {
+        return None;
+    }
+    let mut color_classes: Vec<Vec<usize>> =
+        SchreierSims::get_stabilizing_color_gens(&puzzle.goal_string)
+            .into_iter()
+            .map(|class| {
+                let mut class: Vec<usize> = class.into_iter().collect();
+                class.sort();
+                class
+            })
+            .collect();
+    color_classes.sort();
+    let mut goal_class = vec![0; n];
+    for (i, class) in color_classes.iter().enumerate() {
+        for x in class {
+            goal_class[*x] = i;
+        }
+    }
+    let mut initial_class = vec![0; n];
+    let mut class_sizes = vec![0; color_classes.len()];
+    for (initial, value) in initial_class.iter_mut().zip(puzzle.initial_state.iter()) {
+        let class = color_classes
+            .iter()
+            .position(|c| puzzle.goal_state[c[0]] == *value);
+        match class {
+            Some(class) => {
+                *initial = class;
+                class_sizes[class] += 1;
+            }
+            None => return None,
+        }
+    }
+    if class_sizes
+        .iter()
+        .zip(color_classes.iter())
+        .any(|(size, class)| *size != class.len())
+    {
+        return None;
+    }
+
+    let mut search = TargetSearch::new(chain, goal_class, initial_class, node_limit, deadline);
+    // Points fixed by the whole group can never be moved
+    let fixed_by_group = (0..n).filter(|x| {
+        chain
+            .levels
+            .first()
+            .is_none_or(|l| l.generators.iter().all(|g| g.p[*x] - 1 == *x))
+    });
+    for x in fixed_by_group {
+        if search.goal_class[x] != search.initial_class[x] {
+            return None;
+        }
+    }
+    search.search(0, &Permutation::identity(n), 0);
+    debug!(
+        "Target search for puzzle {} visited {} nodes and reached level {} of {}",
+        puzzle.id,
+        search.nodes,
+        search.deepest,
+        chain.levels.len()
+    );
+    search.best.map(|(target, _)| target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::puzzle::{self, PuzzleType};
+
+    #[test]
+    fn test_choose_target_is_valid_and_in_group() {
+        let puzzle_info = puzzle::load_puzzle_info("./../../data/puzzle_info.csv").unwrap();
+        let puzzles = puzzle::load_puzzles("./../../data/puzzles.csv", &puzzle_info).unwrap();
+        for puzzle in puzzles
+            .iter()
+            .filter(|p| p.puzzle_type == PuzzleType::CUBE(4))
+            .take(2)
+        {
+            let chain = build_search_chain(&puzzle.moves, &puzzle.goal_string);
+            let target = choose_target(&chain, puzzle, 10_000, &Deadline::unlimited()).unwrap();
+            assert!(chain.contains(&target));
+            for i in 0..target.len() {
+                assert_eq!(puzzle.goal_state[target.p[i] - 1], puzzle.initial_state[i]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_choose_target_prefers_identity() {
+        let puzzle_info = puzzle::load_puzzle_info("./../../data/puzzle_info.csv").unwrap();
+        let puzzles = puzzle::load_puzzles("./../../data/puzzles.csv", &puzzle_info).unwrap();
+        let mut puzzle = puzzles
+            .iter()
+            .find(|p| p.puzzle_type == PuzzleType::CUBE(3))
+            .unwrap()
+            .clone();
+        puzzle.initial_state = puzzle.goal_state.clone();
+        puzzle.init_string = puzzle.goal_string.clone();
+        let chain = build_search_chain(&puzzle.moves, &puzzle.goal_string);
+        let target = choose_target(&chain, &puzzle, 100_000, &Deadline::unlimited()).unwrap();
+        assert!(target.is_identity());
+    }
+}
@@ -10,6 +10,49 @@ impl TestingUtils {
         Permutation::parse_permutation_from_cycle(cycle_str, n)
     }
 
+    /*
+     * Moves of degree n given by their names and cycles, each preceded by its inverse "-name" as
+     * in the puzzle info, so move i ^ 1 is the inverse of move i.
+     */
+    pub fn moves_from_cycles(cycles: &[(&str, &str)], n: usize) -> Vec<puzzle::Move> {
+        let mut moves = Vec::new();
+        for (name, cycle) in cycles {
+            let permutation = Permutation::parse_permutation_from_cycle(cycle, n);
+            moves.push(puzzle::Move {
+                name: format!("-{}", name),
+                permutation: permutation.inverse(),
+            });
+            moves.push(puzzle::Move {
+                name: name.to_string(),
+                permutation,
+            });
+        }
+        moves
+    }
+
+    /*
+     * Toy puzzle with the given moves and states for the tests of the solvers. It is labelled as
+     * a 1x1x1 cube, a type without puzzles in the data, so it shares nothing keyed by the puzzle
+     * type with a real puzzle.
+     */
+    pub fn puzzle_from_moves(
+        moves: Vec<puzzle::Move>,
+        initial_state: Vec<usize>,
+        goal_state: Vec<usize>,
+        num_wildcards: usize,
+    ) -> puzzle::Puzzle {
+        puzzle::Puzzle {
+            id: 0,
+            initial_state,
+            goal_state,
+            init_string: String::new(),
+            goal_string: String::new(),
+            moves,
+            num_wildcards,
+            puzzle_type: PuzzleType::CUBE(1),
+        }
+    }
+
     pub fn get_s_n_generators(n: usize) -> Vec<Permutation> {
         let mut generators = Vec::new();
         for i in 1..n {
@@ -0,0 +1,199 @@
+use crate::permutation::Permutation;
+use crate::puzzle::{Puzzle, PuzzleFilter};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    UnknownPuzzle,
+    DuplicateRow,
+    UnknownMove(String),
+    TooManyMismatches {
+        mismatches: usize,
+        num_wildcards: usize,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::UnknownPuzzle => write!(f, "unknown puzzle"),
+            ValidationError::DuplicateRow => write!(f, "duplicate row"),
+            ValidationError::UnknownMove(name) => write!(f, "unknown move {:?}", name),
+            ValidationError::TooManyMismatches {
+                mismatches,
+                num_wildcards,
+            } => write!(
+                f,
+                "{} mismatches but only {} wildcards",
+                mismatches, num_wildcards
+            ),
+        }
+    }
+}
+
+/*
+ * Applies the moves of the solution (in the dot separated submission format) to the initial state
+ * of the puzzle and checks that at most num_wildcards elements differ from the goal state.
+ * Returns the score of the solution, i.e. its number of moves.
+ */
+pub fn validate_solution(puzzle: &Puzzle, solution: &str) -> Result<usize, ValidationError> {
+    let moves: HashMap<&str, &Permutation> = puzzle
+        .moves
+        .iter()
+        .map(|m| (m.name.as_str(), &m.permutation))
+        .collect();
+    let mut state = puzzle.initial_state.clone();
+    let mut score = 0;
+    for name in solution.split('.').filter(|name| !name.is_empty()) {
+        let perm = moves
+            .get(name)
+            .ok_or_else(|| ValidationError::UnknownMove(name.to_string()))?;
+        state = perm.apply(&state);
+        score += 1;
+    }
+    let mismatches = state
+        .iter()
+        .zip(puzzle.goal_state.iter())
+        .filter(|(a, b)| a != b)
+        .count();
+    if mismatches > puzzle.num_wildcards {
+        return Err(ValidationError::TooManyMismatches {
+            mismatches,
+            num_wildcards: puzzle.num_wildcards,
+        });
+    }
+    Ok(score)
+}
+
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    // Score or error of every row of the submission, in the order of the rows
+    pub rows: Vec<(usize, Result<usize, ValidationError>)>,
+    // Puzzles selected by the filter without a row in the submission
+    pub missing: Vec<usize>,
+}
+
+impl ValidationReport {
+    pub fn total_score(&self) -> usize {
+        self.rows.iter().filter_map(|(_, r)| r.as_ref().ok()).sum()
+    }
+
+    pub fn num_invalid(&self) -> usize {
+        self.rows.iter().filter(|(_, r)| r.is_err()).count()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.num_invalid() == 0
+    }
+}
+
+/*
+ * Validates every row (id, moves) of the submission whose puzzle is selected by the filter.
+ */
+pub fn validate_submission(
+    puzzles: &[Puzzle],
+    filter: &PuzzleFilter,
+    submission_path: &str,
+) -> Result<ValidationReport, Box<dyn Error>> {
+    let puzzles: HashMap<usize, &Puzzle> = puzzles.iter().map(|p| (p.id, p)).collect();
+    let mut reader = csv::Reader::from_path(submission_path)?;
+    let mut report = ValidationReport::default();
+    let mut seen = HashSet::new();
+    for record in reader.records() {
+        let record = record?;
+        let id: usize = record[0].parse()?;
+        let result = match puzzles.get(&id) {
+            Some(puzzle) if !filter.matches(puzzle) => continue,
+            Some(_) if !seen.insert(id) => Err(ValidationError::DuplicateRow),
+            Some(puzzle) => validate_solution(puzzle, &record[1]),
+            None if !filter.matches_id(id) => continue,
+            None => Err(ValidationError::UnknownPuzzle),
+        };
+        report.rows.push((id, result));
+    }
+    let mut missing = puzzles
+        .values()
+        .filter(|p| filter.matches(p) && !seen.contains(&p.id))
+        .map(|p| p.id)
+        .collect::<Vec<usize>>();
+    missing.sort();
+    report.missing = missing;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::puzzle::{Move, PuzzleType};
+    use std::fs;
+
+    fn get_puzzle(id: usize, num_wildcards: usize) -> Puzzle {
+        let r = Permutation::new(vec![2, 3, 1, 4]);
+        Puzzle {
+            id,
+            initial_state: vec![1, 2, 3, 4],
+            goal_state: vec![2, 3, 1, 4],
+            init_string: "A;B;C;D".to_string(),
+            goal_string: "B;C;A;D".to_string(),
+            moves: vec![
+                Move {
+                    name: "r".to_string(),
+                    permutation: r.clone(),
+                },
+                Move {
+                    name: "-r".to_string(),
+                    permutation: r.inverse(),
+                },
+            ],
+            num_wildcards,
+            puzzle_type: PuzzleType::WREATH(6),
+        }
+    }
+
+    #[test]
+    fn test_validate_solution() {
+        let puzzle = get_puzzle(0, 0);
+        assert_eq!(validate_solution(&puzzle, "r"), Ok(1));
+        assert_eq!(validate_solution(&puzzle, "-r.-r"), Ok(2));
+        assert_eq!(validate_solution(&puzzle, "r.r.r.r"), Ok(4));
+        assert_eq!(
+            validate_solution(&puzzle, "r.x"),
+            Err(ValidationError::UnknownMove("x".to_string()))
+        );
+        assert_eq!(
+            validate_solution(&puzzle, ""),
+            Err(ValidationError::TooManyMismatches {
+                mismatches: 3,
+                num_wildcards: 0
+            })
+        );
+        assert_eq!(validate_solution(&get_puzzle(0, 3), ""), Ok(0));
+    }
+
+    #[test]
+    fn test_validate_submission() {
+        let puzzles = vec![get_puzzle(0, 0), get_puzzle(1, 0), get_puzzle(2, 0)];
+        let path = std::env::temp_dir().join(format!("santa_validate_{}.csv", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        fs::write(&path, "id,moves\n0,r.r.r.r\n1,r\n1,r\n5,r\n").unwrap();
+
+        let report = validate_submission(&puzzles, &PuzzleFilter::default(), &path).unwrap();
+        assert_eq!(report.total_score(), 5);
+        assert_eq!(report.num_invalid(), 2);
+        assert!(!report.is_valid());
+        assert_eq!(report.rows[2], (1, Err(ValidationError::DuplicateRow)));
+        assert_eq!(report.rows[3], (5, Err(ValidationError::UnknownPuzzle)));
+        assert_eq!(report.missing, vec![2]);
+
+        let filter = PuzzleFilter {
+            ids: vec![0],
+            ..PuzzleFilter::default()
+        };
+        let report = validate_submission(&puzzles, &filter, &path).unwrap();
+        assert!(report.is_valid());
+        assert_eq!(report.total_score(), 4);
+        fs::remove_file(&path).unwrap();
+    }
+}
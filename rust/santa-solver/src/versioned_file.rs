@@ -0,0 +1,83 @@
+use log::warn;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/*
+ * Writes value as bincode after a header of the magic number and the format version. The file is
+ * written to a temporary file first, so an interrupted run never leaves a broken file behind.
+ */
+pub fn write_versioned<T: Serialize>(
+    path: &str,
+    magic: [u8; 4],
+    version: u32,
+    value: &T,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = Path::new(path).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp_path = format!("{}.tmp", path);
+    let file = fs::File::create(&tmp_path)?;
+    let mut writer = io::BufWriter::new(file);
+    bincode::serialize_into(&mut writer, &(magic, version))?;
+    bincode::serialize_into(&mut writer, value)?;
+    drop(writer);
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/*
+ * Reads a file written by write_versioned. Returns None if the file does not exist, was written by
+ * another version or is broken; name describes the file in the warning about another version.
+ */
+pub fn read_versioned<T: DeserializeOwned>(
+    path: &str,
+    magic: [u8; 4],
+    version: u32,
+    name: &str,
+) -> Option<T> {
+    let file = fs::File::open(path).ok()?;
+    let mut reader = io::BufReader::new(file);
+    let header: ([u8; 4], u32) = bincode::deserialize_from(&mut reader).ok()?;
+    if header != (magic, version) {
+        warn!(
+            "{} {} has version {} instead of {}",
+            name, path, header.1, version
+        );
+        return None;
+    }
+    bincode::deserialize_from(&mut reader).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versioned_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("santa_versioned_{}", std::process::id()));
+        let path = dir.join("values.bin").to_string_lossy().to_string();
+        let values = vec![3_u16, 1, 4, 1, 5];
+        write_versioned(&path, *b"TEST", 2, &values).unwrap();
+        assert_eq!(
+            read_versioned::<Vec<u16>>(&path, *b"TEST", 2, "Test values"),
+            Some(values)
+        );
+        assert_eq!(
+            read_versioned::<Vec<u16>>(&path, *b"TEST", 3, "Test values"),
+            None
+        );
+        assert_eq!(
+            read_versioned::<Vec<u16>>(&path, *b"ELSE", 2, "Test values"),
+            None
+        );
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            read_versioned::<Vec<u16>>(&path, *b"TEST", 2, "Test values"),
+            None
+        );
+    }
+}
@@ -0,0 +1,306 @@
+use crate::config::{VisitedConfig, VisitedStrategy};
+use log::{info, warn};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::fs::{self, File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Disk sets are grown once this fraction of their slots is used
+const DISK_MAX_LOAD: f64 = 0.7;
+
+static DISK_SET_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+fn fingerprint<T: Hash + ?Sized>(item: &T, seed: u8) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    seed.hash(&mut hasher);
+    item.hash(&mut hasher);
+    hasher.finish()
+}
+
+/*
+ * Open addressing hash table of 64-bit fingerprints in a temporary file, for searches whose
+ * visited elements do not fit into memory. The empty slot is 0, so the fingerprint 0 is stored as 1.
+ */
+pub struct DiskSet {
+    path: PathBuf,
+    file: File,
+    slots: u64,
+    len: usize,
+}
+
+impl DiskSet {
+    fn create(dir: &PathBuf, slots: u64) -> std::io::Result<DiskSet> {
+        fs::create_dir_all(dir)?;
+        let id = DISK_SET_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!("visited_{}_{}.bin", std::process::id(), id));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        file.set_len(slots * 8)?;
+        Ok(DiskSet {
+            path,
+            file,
+            slots,
+            len: 0,
+        })
+    }
+
+    fn read_slot(&self, slot: u64) -> u64 {
+        let mut buffer = [0u8; 8];
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(slot * 8))
+            .and_then(|_| file.read_exact(&mut buffer))
+            .expect("Could not read disk backed visited set");
+        u64::from_le_bytes(buffer)
+    }
+
+    fn write_slot(&self, slot: u64, value: u64) {
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(slot * 8))
+            .and_then(|_| file.write_all(&value.to_le_bytes()))
+            .expect("Could not write disk backed visited set");
+    }
+
+    // Slot of the fingerprint, or the empty slot where it belongs
+    fn find(&self, key: u64) -> (u64, bool) {
+        let mut slot = key % self.slots;
+        loop {
+            match self.read_slot(slot) {
+                0 => return (slot, false),
+                value if value == key => return (slot, true),
+                _ => slot = (slot + 1) % self.slots,
+            }
+        }
+    }
+
+    fn contains(&self, key: u64) -> bool {
+        self.find(key.max(1)).1
+    }
+
+    // Returns false if the fingerprint was already in the set
+    fn insert(&mut self, key: u64) -> bool {
+        let key = key.max(1);
+        if (self.len + 1) as f64 > self.slots as f64 * DISK_MAX_LOAD {
+            self.grow();
+        }
+        let (slot, found) = self.find(key);
+        if !found {
+            self.write_slot(slot, key);
+            self.len += 1;
+        }
+        !found
+    }
+
+    fn grow(&mut self) {
+        let dir = self.path.parent().unwrap().to_path_buf();
+        let mut grown =
+            DiskSet::create(&dir, self.slots * 2).expect("Could not grow disk backed visited set");
+        info!(
+            "Growing disk backed visited set {} to {} slots",
+            self.path.display(),
+            grown.slots
+        );
+        for slot in 0..self.slots {
+            let key = self.read_slot(slot);
+            if key != 0 {
+                let (slot, _) = grown.find(key);
+                grown.write_slot(slot, key);
+                grown.len += 1;
+            }
+        }
+        std::mem::swap(self, &mut grown);
+    }
+}
+
+impl Drop for DiskSet {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+enum Storage {
+    Exact64(HashSet<u64>),
+    Exact128(HashSet<u128>),
+    Bloom(bloomfilter::Bloom<u64>),
+    Disk(DiskSet),
+}
+
+/*
+ * Visited elements of a breadth first search, stored as fingerprints according to the strategy of
+ * the config. The exact strategies only confuse elements whose fingerprints collide, the bloom
+ * filter drops reachable elements at its false positive rate, which grows once it holds more than
+ * the number of items it was sized for. Every strategy logs when it holds more elements than its
+ * capacity, and describes itself through Display.
+ */
+pub struct VisitedSet {
+    storage: Storage,
+    strategy: VisitedStrategy,
+    capacity: usize,
+    len: usize,
+}
+
+impl VisitedSet {
+    pub fn new(config: &VisitedConfig) -> VisitedSet {
+        // The exact sets start empty and grow as needed, as many searches are short-lived
+        let storage = match config.strategy {
+            VisitedStrategy::Exact64 => Storage::Exact64(HashSet::new()),
+            VisitedStrategy::Exact128 => Storage::Exact128(HashSet::new()),
+            VisitedStrategy::Bloom => Storage::Bloom(bloomfilter::Bloom::new_for_fp_rate(
+                config.items,
+                config.fp_rate,
+            )),
+            VisitedStrategy::Disk => {
+                let dir = match &config.disk_dir {
+                    Some(dir) => PathBuf::from(dir),
+                    None => std::env::temp_dir(),
+                };
+                let slots = ((config.items as f64 / DISK_MAX_LOAD) as u64).max(16);
+                Storage::Disk(
+                    DiskSet::create(&dir, slots).expect("Could not create disk backed visited set"),
+                )
+            }
+        };
+        let set = VisitedSet {
+            storage,
+            strategy: config.strategy,
+            capacity: config.items,
+            len: 0,
+        };
+        info!("Created visited set: {}", set);
+        set
+    }
+
+    pub fn check<T: Hash + ?Sized>(&self, item: &T) -> bool {
+        match &self.storage {
+            Storage::Exact64(set) => set.contains(&fingerprint(item, 0)),
+            Storage::Exact128(set) => set.contains(&Self::fingerprint128(item)),
+            Storage::Bloom(bloom) => bloom.check(&fingerprint(item, 0)),
+            Storage::Disk(set) => set.contains(fingerprint(item, 0)),
+        }
+    }
+
+    pub fn set<T: Hash + ?Sized>(&mut self, item: &T) {
+        let is_new = match &mut self.storage {
+            Storage::Exact64(set) => set.insert(fingerprint(item, 0)),
+            Storage::Exact128(set) => set.insert(Self::fingerprint128(item)),
+            Storage::Bloom(bloom) => {
+                let key = fingerprint(item, 0);
+                let is_new = !bloom.check(&key);
+                bloom.set(&key);
+                is_new
+            }
+            Storage::Disk(set) => set.insert(fingerprint(item, 0)),
+        };
+        if is_new {
+            self.len += 1;
+            if self.len == self.capacity + 1 {
+                warn!("Visited set exceeds its capacity: {}", self);
+            }
+        }
+    }
+
+    // Number of distinct fingerprints inserted (as far as the strategy can tell)
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn strategy(&self) -> VisitedStrategy {
+        self.strategy
+    }
+
+    fn fingerprint128<T: Hash + ?Sized>(item: &T) -> u128 {
+        ((fingerprint(item, 0) as u128) << 64) | fingerprint(item, 1) as u128
+    }
+}
+
+impl Display for VisitedSet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.storage {
+            Storage::Exact64(_) => write!(f, "exact set of 64-bit fingerprints")?,
+            Storage::Exact128(_) => write!(f, "exact set of 128-bit fingerprints")?,
+            Storage::Bloom(bloom) => write!(
+                f,
+                "bloom filter with {} bits and {} hash functions",
+                bloom.number_of_bits(),
+                bloom.number_of_hash_functions()
+            )?,
+            Storage::Disk(set) => write!(
+                f,
+                "disk backed set with {} slots in {}",
+                set.slots,
+                set.path.display()
+            )?,
+        }
+        write!(f, ", {} of {} elements", self.len, self.capacity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_visited_strategies() {
+        for strategy in [
+            VisitedStrategy::Exact64,
+            VisitedStrategy::Exact128,
+            VisitedStrategy::Bloom,
+            VisitedStrategy::Disk,
+        ] {
+            // The bloom filter is sized so that false positives are practically impossible, the
+            // other sets exceed their capacity, which grows the disk set
+            let items = match strategy {
+                VisitedStrategy::Bloom => 100_000,
+                _ => 100,
+            };
+            let config = VisitedConfig {
+                strategy,
+                items,
+                ..VisitedConfig::default()
+            };
+            let mut visited = VisitedSet::new(&config);
+            for i in 0..1000usize {
+                assert!(!visited.check(&vec![i, i + 1]));
+                visited.set(&vec![i, i + 1]);
+                visited.set(&vec![i, i + 1]);
+                assert!(visited.check(&vec![i, i + 1]));
+            }
+            assert_eq!(visited.len(), 1000);
+            assert!((0..1000usize).all(|i| visited.check(&vec![i, i + 1])));
+            assert_eq!(visited.strategy(), strategy);
+            let report = format!("1000 of {} elements", items);
+            assert!(visited.to_string().ends_with(&report));
+        }
+    }
+
+    #[test]
+    fn test_disk_set_is_removed() {
+        let dir = std::env::temp_dir().join(format!("santa_visited_{}", std::process::id()));
+        let config = VisitedConfig {
+            strategy: VisitedStrategy::Disk,
+            disk_dir: Some(dir.to_string_lossy().to_string()),
+            ..VisitedConfig::default()
+        };
+        let mut visited = VisitedSet::new(&config);
+        visited.set(&1);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        drop(visited);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
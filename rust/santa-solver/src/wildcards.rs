@@ -0,0 +1,215 @@
+use crate::config::WildcardConfig;
+use crate::puzzle::{self, Puzzle};
+use std::collections::HashMap;
+
+fn mismatches(state: &[usize], goal: &[usize]) -> usize {
+    state
+        .iter()
+        .zip(goal.iter())
+        .filter(|(a, b)| a != b)
+        .count()
+}
+
+/*
+ * Depth limited search for a short sequence of moves that brings a state within num_wildcards
+ * mismatches of the goal state. All searches of a solution share the node limit.
+ */
+struct SuffixSearch<'a> {
+    puzzle: &'a Puzzle,
+    // Index of the inverse of every move, if it is a move
+    inverses: Vec<Option<usize>>,
+    // Largest number of elements moved by a single move
+    max_support: usize,
+    nodes: usize,
+    node_limit: usize,
+}
+
+impl<'a> SuffixSearch<'a> {
+    fn new(puzzle: &'a Puzzle, node_limit: usize) -> SuffixSearch<'a> {
+        let inverses = puzzle::move_inverses(&puzzle.moves);
+        let max_support = puzzle
+            .moves
+            .iter()
+            .map(|m| m.permutation.compress().support_size())
+            .max()
+            .unwrap_or(0);
+        SuffixSearch {
+            puzzle,
+            inverses,
+            max_support,
+            nodes: 0,
+            node_limit,
+        }
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.nodes >= self.node_limit
+    }
+
+    /*
+     * Returns true and leaves the moves in path if the state can be brought within the wildcards
+     * with at most depth moves.
+     */
+    fn search(&mut self, state: &[usize], depth: usize, path: &mut Vec<usize>) -> bool {
+        self.nodes += 1;
+        let wrong = mismatches(state, &self.puzzle.goal_state);
+        if wrong <= self.puzzle.num_wildcards {
+            return true;
+        }
+        // Every move fixes at most max_support mismatches
+        if depth == 0
+            || self.is_exhausted()
+            || wrong > self.puzzle.num_wildcards + depth * self.max_support
+        {
+            return false;
+        }
+        for (i, m) in self.puzzle.moves.iter().enumerate() {
+            if let Some(last) = path.last() {
+                if self.inverses[*last] == Some(i) {
+                    continue;
+                }
+            }
+            path.push(i);
+            if self.search(&m.permutation.apply(state), depth - 1, path) {
+                return true;
+            }
+            path.pop();
+        }
+        false
+    }
+}
+
+/*
+ * Shortens a solution (move indices) of a puzzle with wildcards. The solution is cut after the
+ * first move whose state is within num_wildcards mismatches of the goal state. If the puzzle has
+ * wildcards, the last suffix_window moves of the cut solution are additionally tried to be
+ * replaced by a shorter sequence found by a depth limited search. Returns the word unchanged if
+ * no prefix reaches the goal.
+ */
+pub fn shorten_indices(puzzle: &Puzzle, word: &[usize], config: &WildcardConfig) -> Vec<usize> {
+    let mut state = puzzle.initial_state.clone();
+    let mut end = None;
+    for (i, m) in word.iter().enumerate() {
+        if mismatches(&state, &puzzle.goal_state) <= puzzle.num_wildcards {
+            end = Some(i);
+            break;
+        }
+        state = puzzle.moves[*m].permutation.apply(&state);
+    }
+    let end = match end {
+        Some(end) => end,
+        None if mismatches(&state, &puzzle.goal_state) <= puzzle.num_wildcards => word.len(),
+        None => return word.to_vec(),
+    };
+    if puzzle.num_wildcards == 0 || end == 0 {
+        return word[..end].to_vec();
+    }
+
+    // Replay the cut solution up to the first position whose suffix is searched
+    let first = end.saturating_sub(config.suffix_window);
+    let mut state = puzzle.initial_state.clone();
+    for m in &word[..first] {
+        state = puzzle.moves[*m].permutation.apply(&state);
+    }
+    let mut search = SuffixSearch::new(puzzle, config.node_limit);
+    let mut best_len = end;
+    let mut best: Option<(usize, Vec<usize>)> = None;
+    for (p, m) in word.iter().enumerate().take(end).skip(first) {
+        if p + 1 >= best_len {
+            break;
+        }
+        // Iterative deepening, so the first suffix found is the shortest one from p
+        for depth in 1..=config.suffix_depth.min(best_len - p - 1) {
+            let mut path = Vec::new();
+            if search.search(&state, depth, &mut path) {
+                best_len = p + path.len();
+                best = Some((p, path));
+                break;
+            }
+        }
+        if search.is_exhausted() {
+            break;
+        }
+        state = puzzle.moves[*m].permutation.apply(&state);
+    }
+    match best {
+        Some((p, path)) => {
+            let mut result = word[..p].to_vec();
+            result.extend(path);
+            result
+        }
+        None => word[..end].to_vec(),
+    }
+}
+
+/*
+ * Shortens a solution in the dot separated submission format, see shorten_indices. Returns None if
+ * it contains moves of another puzzle type.
+ */
+pub fn shorten_solution(
+    puzzle: &Puzzle,
+    solution: &str,
+    config: &WildcardConfig,
+) -> Option<String> {
+    let index_of: HashMap<&str, usize> = puzzle
+        .moves
+        .iter()
+        .enumerate()
+        .map(|(i, m)| (m.name.as_str(), i))
+        .collect();
+    let mut word = Vec::new();
+    for name in solution.split('.').filter(|name| !name.is_empty()) {
+        word.push(*index_of.get(name)?);
+    }
+    Some(puzzle::word_to_string(
+        &puzzle.moves,
+        &shorten_indices(puzzle, &word, config),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing_utils::TestingUtils;
+    use crate::validation::validate_solution;
+
+    fn get_puzzle(num_wildcards: usize) -> Puzzle {
+        let moves =
+            TestingUtils::moves_from_cycles(&[("a", "(1,2)"), ("b", "(3,4)"), ("c", "(1,2,5)")], 5);
+        TestingUtils::puzzle_from_moves(
+            moves,
+            vec![1, 2, 3, 4, 5],
+            vec![2, 1, 4, 3, 5],
+            num_wildcards,
+        )
+    }
+
+    #[test]
+    fn test_truncate_to_goal() {
+        let puzzle = get_puzzle(0);
+        let config = WildcardConfig::default();
+        let shorten = |s: &str| shorten_solution(&puzzle, s, &config).unwrap();
+        assert_eq!(shorten("a.b.c.-c"), "a.b");
+        assert_eq!(shorten("a.b"), "a.b");
+        // Solutions that do not reach the goal are kept
+        assert_eq!(shorten("a.c"), "a.c");
+        assert_eq!(shorten_solution(&puzzle, "a.x", &config), None);
+    }
+
+    #[test]
+    fn test_shorten_with_wildcards() {
+        let puzzle = get_puzzle(2);
+        let config = WildcardConfig {
+            suffix_depth: 0,
+            ..WildcardConfig::default()
+        };
+        // The state after c.-c.c.-c.a is within 2 mismatches of the goal
+        let truncated = shorten_solution(&puzzle, "c.-c.c.-c.a.b", &config).unwrap();
+        assert_eq!(truncated, "c.-c.c.-c.a");
+
+        let config = WildcardConfig::default();
+        let shortened = shorten_solution(&puzzle, "c.-c.c.-c.a.b", &config).unwrap();
+        assert_eq!(shortened.split('.').count(), 1);
+        assert_eq!(validate_solution(&puzzle, &shortened), Ok(1));
+    }
+}
@@ -0,0 +1,380 @@
+use crate::config::{ShortWordConfig, VisitedConfig};
+use crate::groups::DepthLimitedPermutationGroupIteratorCompressed;
+use crate::permutation::CompressedPermutation;
+use crate::puzzle::{self, Move, PuzzleType};
+use crate::versioned_file;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+const SHORT_WORD_TABLE_MAGIC: [u8; 4] = *b"SSWT";
+// Increase whenever the serialized format of the table changes
+const SHORT_WORD_TABLE_VERSION: u32 = 1;
+
+/*
+ * Shortest words (move indices) of all group elements up to a word length, found by a breadth
+ * first search over the moves of a puzzle type. The identity has the empty word.
+ */
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShortWordTable {
+    move_names: Vec<String>,
+    depth: usize,
+    // Largest number of non-fixed points of a stored element
+    max_support: usize,
+    words: HashMap<CompressedPermutation, Vec<u16>>,
+}
+
+impl ShortWordTable {
+    /*
+     * Stops early (with an incomplete last level) once the stored permutations have more than
+     * config.entries non-fixed points in total.
+     */
+    pub fn build(
+        moves: &[Move],
+        config: &ShortWordConfig,
+        visited: &VisitedConfig,
+    ) -> ShortWordTable {
+        let generators = moves
+            .iter()
+            .map(|m| m.permutation.compress())
+            .collect::<Vec<CompressedPermutation>>();
+        let mut words = HashMap::new();
+        words.insert(
+            CompressedPermutation::identity(moves[0].permutation.len()),
+            Vec::new(),
+        );
+        let mut entries = 0;
+        // The iterator stops after the first element of max_depth
+        for (perm, path) in DepthLimitedPermutationGroupIteratorCompressed::with_visited(
+            &generators,
+            config.depth + 1,
+            visited,
+        ) {
+            if path.len() > config.depth || entries > config.entries {
+                break;
+            }
+            if let Entry::Vacant(entry) = words.entry(perm) {
+                entries += entry.key().support_size();
+                entry.insert(path.iter().map(|i| *i as u16).collect());
+            }
+        }
+        info!(
+            "Built short word table with {} elements up to depth {}",
+            words.len(),
+            config.depth
+        );
+        ShortWordTable {
+            move_names: moves.iter().map(|m| m.name.clone()).collect(),
+            depth: config.depth,
+            max_support: words.keys().map(|p| p.support_size()).max().unwrap(),
+            words,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn get(&self, perm: &CompressedPermutation) -> Option<&Vec<u16>> {
+        self.words.get(perm)
+    }
+
+    pub fn write_to_file(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        versioned_file::write_versioned(
+            path,
+            SHORT_WORD_TABLE_MAGIC,
+            SHORT_WORD_TABLE_VERSION,
+            self,
+        )
+    }
+
+    /*
+     * Returns None if the file does not exist, was written by another version or is broken.
+     */
+    pub fn read_from_file(path: &str) -> Option<ShortWordTable> {
+        versioned_file::read_versioned(
+            path,
+            SHORT_WORD_TABLE_MAGIC,
+            SHORT_WORD_TABLE_VERSION,
+            "Short word table",
+        )
+    }
+
+    /*
+     * Loads the table of the puzzle type and depth from the directory, or builds and stores it if
+     * it does not exist or does not match the moves.
+     */
+    pub fn load_or_build(
+        tables_path: &str,
+        puzzle_type: &PuzzleType,
+        moves: &[Move],
+        config: &ShortWordConfig,
+        visited: &VisitedConfig,
+    ) -> ShortWordTable {
+        let path = format!("{}/{}_d{}.bin", tables_path, puzzle_type, config.depth);
+        if let Some(table) = ShortWordTable::read_from_file(&path) {
+            if table.move_names.iter().eq(moves.iter().map(|m| &m.name)) {
+                info!("Loaded short word table for {} from {}", puzzle_type, path);
+                return table;
+            }
+            warn!(
+                "Moves of short word table {} do not match, rebuilding it",
+                path
+            );
+        }
+        let table = ShortWordTable::build(moves, config, visited);
+        if let Err(e) = table.write_to_file(&path) {
+            warn!("Could not write short word table to {}: {}", path, e);
+        }
+        table
+    }
+}
+
+/*
+ * Where the short word tables are cached and how they are built, see ShortWordConfig.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct WindowOptions {
+    pub tables_path: String,
+    pub short_words: ShortWordConfig,
+    pub visited: VisitedConfig,
+}
+
+/*
+ * Slides a window over a move sequence and replaces every segment whose permutation has a shorter
+ * word in the short word table of the puzzle type.
+ */
+pub struct WindowOptimizer {
+    moves: Vec<Move>,
+    index_of: HashMap<String, usize>,
+    generators: Vec<CompressedPermutation>,
+    table: ShortWordTable,
+    window: usize,
+    // Largest number of non-fixed points of a move
+    max_move_support: usize,
+}
+
+impl WindowOptimizer {
+    pub fn new(moves: &[Move], table: ShortWordTable, window: usize) -> WindowOptimizer {
+        WindowOptimizer {
+            moves: moves.to_vec(),
+            index_of: moves
+                .iter()
+                .enumerate()
+                .map(|(i, m)| (m.name.clone(), i))
+                .collect(),
+            generators: moves.iter().map(|m| m.permutation.compress()).collect(),
+            table,
+            window,
+            max_move_support: moves
+                .iter()
+                .map(|m| m.permutation.compress().support_size())
+                .max()
+                .unwrap_or(0),
+        }
+    }
+
+    pub fn load_or_build(
+        puzzle_type: &PuzzleType,
+        moves: &[Move],
+        options: &WindowOptions,
+    ) -> WindowOptimizer {
+        let table = ShortWordTable::load_or_build(
+            &options.tables_path,
+            puzzle_type,
+            moves,
+            &options.short_words,
+            &options.visited,
+        );
+        WindowOptimizer::new(moves, table, options.short_words.window)
+    }
+
+    /*
+     * Returns the end and the replacement of the segment starting at start with the largest
+     * saving, if any segment of at most window moves has a shorter word.
+     */
+    fn best_replacement(&self, word: &[usize], start: usize) -> Option<(usize, &Vec<u16>)> {
+        let mut best: Option<(usize, &Vec<u16>)> = None;
+        let mut best_saving = 0;
+        let mut segment = self.generators[word[start]].clone();
+        let last = word.len().min(start + self.window);
+        for (end, m) in word.iter().enumerate().take(last).skip(start + 1) {
+            segment = self.generators[*m].compose(&segment);
+            // Every further move fixes at most max_move_support points of the segment
+            let reachable = (last - end - 1) * self.max_move_support + self.table.max_support;
+            if segment.support_size() > reachable {
+                break;
+            }
+            if segment.support_size() > self.table.max_support {
+                continue;
+            }
+            if let Some(replacement) = self.table.get(&segment) {
+                let length = end + 1 - start;
+                if length > replacement.len() + best_saving {
+                    best_saving = length - replacement.len();
+                    best = Some((end + 1, replacement));
+                }
+            }
+        }
+        best
+    }
+
+    /*
+     * Returns the move indices of a sequence with the same permutation as the word, which is at
+     * most as long as the word.
+     */
+    pub fn optimize_indices(&self, word: &[usize]) -> Vec<usize> {
+        let mut word = word.to_vec();
+        let mut start = 0;
+        while start < word.len() {
+            match self.best_replacement(&word, start) {
+                Some((end, replacement)) => {
+                    let replacement = replacement.iter().map(|i| *i as usize);
+                    word.splice(start..end, replacement);
+                    // Windows starting before the replaced segment may be shortened now
+                    start = start.saturating_sub(self.window);
+                }
+                None => start += 1,
+            }
+        }
+        word
+    }
+
+    /*
+     * Same as optimize_indices for a sequence of moves. Sequences with moves of another puzzle
+     * type are returned unchanged.
+     */
+    pub fn optimize(&self, moves: &[Move]) -> Vec<Move> {
+        let mut word = Vec::with_capacity(moves.len());
+        for m in moves {
+            match self.index_of.get(&m.name) {
+                Some(i) => word.push(*i),
+                None => return moves.to_vec(),
+            }
+        }
+        self.optimize_indices(&word)
+            .iter()
+            .map(|i| self.moves[*i].clone())
+            .collect()
+    }
+
+    /*
+     * Optimizes a solution in the dot separated submission format. Returns None if it contains
+     * moves of another puzzle type.
+     */
+    pub fn optimize_string(&self, solution: &str) -> Option<String> {
+        if solution.is_empty() {
+            return Some(String::new());
+        }
+        let mut word = Vec::new();
+        for name in solution.split('.') {
+            word.push(*self.index_of.get(name)?);
+        }
+        Some(puzzle::word_to_string(
+            &self.moves,
+            &self.optimize_indices(&word),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::permutation::Permutation;
+    use crate::testing_utils::TestingUtils;
+    use rand::Rng;
+    use std::fs;
+
+    fn get_moves() -> Vec<Move> {
+        TestingUtils::moves_from_cycles(
+            &[("a", "(1,2,3,4)"), ("b", "(3,4,5)"), ("c", "(6,7)")],
+            7,
+        )
+    }
+
+    fn get_optimizer(window: usize) -> WindowOptimizer {
+        let moves = get_moves();
+        let config = ShortWordConfig {
+            depth: 4,
+            window,
+            ..ShortWordConfig::default()
+        };
+        let table = ShortWordTable::build(&moves, &config, &VisitedConfig::default());
+        WindowOptimizer::new(&moves, table, window)
+    }
+
+    fn apply(optimizer: &WindowOptimizer, solution: &str) -> Permutation {
+        let mut result = Permutation::identity(7);
+        for name in solution.split('.').filter(|name| !name.is_empty()) {
+            result = optimizer.moves[optimizer.index_of[name]]
+                .permutation
+                .compose(&result);
+        }
+        result
+    }
+
+    #[test]
+    fn test_short_word_table() {
+        let moves = get_moves();
+        let config = ShortWordConfig {
+            depth: 2,
+            ..ShortWordConfig::default()
+        };
+        let table = ShortWordTable::build(&moves, &config, &VisitedConfig::default());
+        let a = moves[1].permutation.compress();
+        assert_eq!(table.get(&a.compose(&a)).unwrap().len(), 2);
+        assert_eq!(table.get(&a.compose(&a).compose(&a)).unwrap().len(), 1);
+        assert_eq!(table.get(&a.compose(&a.inverse())), Some(&Vec::new()));
+
+        let dir = std::env::temp_dir().join(format!("santa_short_words_{}", std::process::id()));
+        let dir = dir.to_string_lossy().to_string();
+        let puzzle_type = PuzzleType::WREATH(7);
+        let visited = VisitedConfig::default();
+        let built = ShortWordTable::load_or_build(&dir, &puzzle_type, &moves, &config, &visited);
+        let loaded = ShortWordTable::read_from_file(&format!("{}/wreath_7_7_d2.bin", dir)).unwrap();
+        assert_eq!(loaded.len(), built.len());
+        assert_eq!(loaded.get(&a.compose(&a)), built.get(&a.compose(&a)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_window_optimizer_replaces_segments() {
+        let optimizer = get_optimizer(8);
+        let optimize = |s: &str| optimizer.optimize_string(s).unwrap();
+        assert_eq!(optimize("a.a.a"), "-a");
+        assert_eq!(optimize("c.a.b.-b.-a.c"), "");
+        // c commutes with a
+        assert_eq!(optimize("c.a.c.a").split('.').count(), 2);
+        assert_eq!(optimize("b.c"), "b.c");
+        assert_eq!(optimizer.optimize_string("a.x"), None);
+
+        let moves = get_moves();
+        let unknown = TestingUtils::moves_from_cycles(&[("x", "(1,2)")], 7);
+        let word = [moves[0].clone(), unknown[0].clone(), moves[0].clone()];
+        let optimized = optimizer.optimize(&word);
+        assert_eq!(
+            optimized.iter().map(|m| &m.name).collect::<Vec<_>>(),
+            vec!["-a", "-x", "-a"]
+        );
+    }
+
+    #[test]
+    fn test_window_optimizer_keeps_permutation() {
+        let optimizer = get_optimizer(6);
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let solution = (0..rng.gen_range(0, 40))
+                .map(|_| optimizer.moves[rng.gen_range(0, 6)].name.clone())
+                .collect::<Vec<String>>()
+                .join(".");
+            let optimized = optimizer.optimize_string(&solution).unwrap();
+            assert!(optimized.split('.').count() <= solution.split('.').count());
+            assert_eq!(apply(&optimizer, &optimized), apply(&optimizer, &solution));
+        }
+    }
+}
@@ -1,6 +1,7 @@
+use crate::config::Config;
+use crate::parallel::{self, Deadline, SolveOptions};
 use crate::permutation::Permutation;
 use crate::puzzle::{Move, Puzzle, PuzzleType};
-use log::info;
 use std::collections::HashMap;
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
@@ -68,7 +69,11 @@ fn search(
     second_c: usize,
     bound: usize,
     last_move: Option<&str>,
+    deadline: &Deadline,
 ) -> Option<Vec<Move>> {
+    if deadline.is_expired() {
+        return None;
+    }
     let wrong_elements = state.wrong_elements(second_c);
     let f = wrong_elements / 2;
     if wrong_elements <= puzzle.num_wildcards {
@@ -86,7 +91,14 @@ fn search(
             }
         }
         let new_state = state.apply_permutation(&mov.permutation);
-        let result = search(puzzle, &new_state, second_c, bound - 1, Some(&mov.name));
+        let result = search(
+            puzzle,
+            &new_state,
+            second_c,
+            bound - 1,
+            Some(&mov.name),
+            deadline,
+        );
         if result.is_some() {
             let mut result = result.unwrap();
             result.insert(0, mov.clone());
@@ -100,8 +112,10 @@ fn search(
     None
 }
 
-fn ida_star(puzzle: &Puzzle) -> Option<Vec<Move>> {
-    let PuzzleType::WREATH(n) = puzzle.puzzle_type else { panic!("Invalid puzzle type") };
+fn ida_star(puzzle: &Puzzle, max_bound: usize, deadline: &Deadline) -> Option<Vec<Move>> {
+    let PuzzleType::WREATH(n) = puzzle.puzzle_type else {
+        panic!("Invalid puzzle type")
+    };
     let second_c = match n {
         6 | 7 => 2,
         12 => 3,
@@ -114,32 +128,27 @@ fn ida_star(puzzle: &Puzzle) -> Option<Vec<Move>> {
     // Perform IDA* search
     let mut bound = state.wrong_elements(second_c) / 2;
     loop {
-        let result = search(puzzle, &state, second_c, bound, None);
+        let result = search(puzzle, &state, second_c, bound, None, deadline);
         if result.is_some() {
             return result;
         }
         bound += 1;
-        if bound > 10000 {
+        if bound > max_bound || deadline.is_expired() {
             return None;
         }
     }
 }
 
-pub fn solve_puzzles(puzzles: &Vec<Puzzle>) -> HashMap<usize, String> {
-    let mut results = HashMap::new();
-    for puzzle in puzzles {
-        let result = ida_star(puzzle);
-        if result.is_some() {
-            let result = result.unwrap();
-            let score = result.len();
-            results.insert(puzzle.id, crate::puzzle::moves_to_string(&result));
-            info!(
-                "Solved puzzle {:?} of type {:?} with score {}",
-                puzzle.id, puzzle.puzzle_type, score
-            );
-        }
-    }
-    results
+pub fn solve_puzzles(
+    puzzles: &[Puzzle],
+    config: &Config,
+    options: &SolveOptions,
+) -> HashMap<usize, String> {
+    parallel::solve_in_parallel(puzzles, options, |puzzle, deadline| {
+        let result = ida_star(puzzle, config.search.wreath_max_bound, deadline)?;
+        parallel::log_solved(puzzle, result.len());
+        Some(crate::puzzle::moves_to_string(&result))
+    })
 }
 
 #[cfg(test)]